@@ -4,10 +4,83 @@
 
 use once_cell::sync::Lazy;
 use regex::Regex;
+use serde::Serialize;
+use utils::password_policy::{PasswordViolation, PasswordViolations};
 
 pub mod login;
+/// User logout types.
+pub mod logout;
+/// Passwordless magic link login types.
+pub mod magic_link;
+/// OpenID Connect single sign-on types.
+pub mod oidc;
+/// Forgotten password and reset types.
+pub mod password;
+/// Session refresh types.
+pub mod refresh;
 /// User registration types and validation.
 pub mod register;
+/// Session listing and revocation types.
+pub mod sessions;
+/// Two-factor login step types.
+pub mod two_factor;
+/// Email verification types.
+pub mod verify_email;
+
+/// Session tokens returned in the response body to clients that opted in
+/// with `returnTokens`, instead of being set as cookies.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthTokens {
+    /// JWT access token, sent as `Authorization: Bearer <accessToken>`.
+    pub access_token: String,
+    /// Opaque refresh token, exchanged for new tokens at `/api/auth/refresh`.
+    pub refresh_token: String,
+    /// Number of seconds until the access token expires.
+    pub expires_in: i64,
+}
+
+/// Error response for a new password that breaks the password policy.
+///
+/// The `error` message is in English; clients can explain the rules in the
+/// user's language from the codes of the `violations`.
+#[derive(Serialize)]
+pub struct ApiPasswordPolicyErrorResponse {
+    /// A human-readable message listing the broken rules.
+    pub error: String,
+    /// The broken rules, each with its `code` and the limit it refers to.
+    pub violations: Vec<PasswordViolation>,
+}
+
+impl From<PasswordViolations> for ApiPasswordPolicyErrorResponse {
+    fn from(violations: PasswordViolations) -> Self {
+        Self {
+            error: violations.to_string(),
+            violations: violations.0,
+        }
+    }
+}
+
+/// Maximum number of characters of a username, as enforced by the `users` table.
+pub const MAX_USERNAME_LENGTH: usize = 16;
+
+/// Validates a username.
+///
+/// # Returns
+///
+/// - `Ok(())` if the username is 1 to [`MAX_USERNAME_LENGTH`] characters long and not blank
+/// - `Err(String)` with a descriptive error message otherwise
+pub fn validate_username(username: &str) -> Result<(), String> {
+    if username.trim().is_empty() {
+        return Err("Username is required".to_string());
+    }
+    if username.chars().count() > MAX_USERNAME_LENGTH {
+        return Err(format!(
+            "Username must be at most {MAX_USERNAME_LENGTH} characters"
+        ));
+    }
+    Ok(())
+}
 
 pub static EMAIL_REGEX: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}$")
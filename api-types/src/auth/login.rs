@@ -2,7 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::auth::EMAIL_REGEX;
+use crate::auth::{ApiAuthTokens, EMAIL_REGEX};
 
 /// Request payload for user login.
 ///
@@ -17,6 +17,10 @@ pub struct ApiAuthLoginRequest {
     /// Indicates whether the username_or_email field is an email address.
     #[serde(rename = "isEmail")]
     pub is_email: bool,
+
+    /// Return the session tokens in the response body instead of setting cookies.
+    #[serde(rename = "returnTokens", default)]
+    pub return_tokens: bool,
 }
 
 impl ApiAuthLoginRequest {
@@ -56,4 +60,30 @@ pub struct ApiAuthLoginResponse {
     pub message: String,
     /// The ID of the newly created user (only present if registration succeeded).
     pub id: Option<i64>,
+    /// The session tokens (only present if requested with `returnTokens`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<ApiAuthTokens>,
+    /// Token to exchange for a session at `/api/auth/login/2fa` (only present
+    /// if the user has two-factor authentication enabled).
+    #[serde(rename = "mfaToken", skip_serializing_if = "Option::is_none")]
+    pub mfa_token: Option<String>,
+    /// Failed attempts to log into the account since the previous login (only
+    /// present on a successful login, if there were any).
+    #[serde(rename = "securityNotice", skip_serializing_if = "Option::is_none")]
+    pub security_notice: Option<ApiLoginSecurityNotice>,
+}
+
+/// Notice about failed attempts to log into an account, shown to the user on
+/// their next successful login.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiLoginSecurityNotice {
+    /// A human-readable message for the user.
+    pub message: String,
+    /// Number of failed attempts since the previous login.
+    pub failed_attempts: i64,
+    /// When the latest failed attempt happened.
+    pub last_attempt_at: String,
+    /// IP address of the latest failed attempt.
+    pub last_ip_address: Option<String>,
 }
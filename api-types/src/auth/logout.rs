@@ -0,0 +1,12 @@
+//! User logout response types.
+
+use serde::Serialize;
+
+/// Response payload for user logout.
+#[derive(Serialize)]
+pub struct ApiAuthLogoutResponse {
+    /// Whether the logout was successful.
+    pub ok: bool,
+    /// A human-readable message describing the result.
+    pub message: String,
+}
@@ -0,0 +1,5 @@
+/// Magic link request endpoint types.
+pub mod send;
+
+/// Magic link login endpoint types.
+pub mod consume;
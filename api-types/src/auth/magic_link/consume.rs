@@ -0,0 +1,17 @@
+//! Magic link login request types.
+//!
+//! The response is an [`ApiAuthLoginResponse`](crate::auth::login::ApiAuthLoginResponse),
+//! as for a login with a password.
+
+use serde::Deserialize;
+
+/// Request payload for logging in with a magic link.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthMagicLinkConsumeRequest {
+    /// The token from the magic link.
+    pub token: String,
+    /// Return the session tokens in the response body instead of setting cookies.
+    #[serde(default)]
+    pub return_tokens: bool,
+}
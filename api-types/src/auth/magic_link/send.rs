@@ -0,0 +1,21 @@
+//! Magic link request and response types.
+
+use serde::{Deserialize, Serialize};
+
+/// Request payload for emailing a magic login link.
+#[derive(Deserialize)]
+pub struct ApiAuthMagicLinkRequest {
+    /// The email address of the account.
+    pub email: String,
+}
+
+/// Response payload for emailing a magic login link.
+///
+/// The response is the same whether or not an account uses the email address.
+#[derive(Serialize)]
+pub struct ApiAuthMagicLinkResponse {
+    /// Whether the request was accepted.
+    pub ok: bool,
+    /// A human-readable message describing the result.
+    pub message: String,
+}
@@ -0,0 +1,8 @@
+/// Identity provider listing types.
+pub mod providers;
+
+/// Provider redirect types.
+pub mod callback;
+
+/// Single sign-on signup types.
+pub mod signup;
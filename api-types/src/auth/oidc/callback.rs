@@ -0,0 +1,16 @@
+//! Provider redirect types.
+
+use serde::Deserialize;
+
+/// Query parameters the identity provider redirects back with.
+#[derive(Deserialize)]
+pub struct ApiAuthOidcCallbackQuery {
+    /// The authorization code, on success.
+    pub code: Option<String>,
+    /// The state sent with the authorization request.
+    pub state: Option<String>,
+    /// Error code, if the provider refused the login.
+    pub error: Option<String>,
+    /// Human-readable description of the error.
+    pub error_description: Option<String>,
+}
@@ -0,0 +1,19 @@
+//! Identity provider listing response types.
+
+use serde::Serialize;
+
+/// An identity provider users can log in with.
+#[derive(Serialize)]
+pub struct ApiOidcProvider {
+    /// ID of the provider, used in `/api/auth/oidc/{id}/start`.
+    pub id: String,
+    /// Name to show on the login button.
+    pub name: String,
+}
+
+/// Response payload listing the identity providers.
+#[derive(Serialize)]
+pub struct ApiAuthOidcProvidersResponse {
+    /// The configured providers, possibly none.
+    pub providers: Vec<ApiOidcProvider>,
+}
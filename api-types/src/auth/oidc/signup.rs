@@ -0,0 +1,33 @@
+//! Single sign-on signup request types.
+//!
+//! The response is an [`ApiRegisterResponse`](crate::auth::register::ApiRegisterResponse),
+//! as for a registration with a password.
+
+use serde::Deserialize;
+
+use crate::auth::validate_username;
+
+/// Request payload for creating the account of a new single sign-on user.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthOidcSignupRequest {
+    /// The token the provider redirect handed to the signup page.
+    pub signup_token: String,
+    /// The username chosen by the user.
+    pub username: String,
+    /// Return the session tokens in the response body instead of setting cookies.
+    #[serde(default)]
+    pub return_tokens: bool,
+}
+
+impl ApiAuthOidcSignupRequest {
+    /// Validates the signup request.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the username is valid
+    /// - `Err(String)` with a descriptive error message otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        validate_username(&self.username)
+    }
+}
@@ -0,0 +1,5 @@
+/// Forgotten password endpoint types.
+pub mod forgot;
+
+/// Password reset endpoint types.
+pub mod reset;
@@ -0,0 +1,21 @@
+//! Forgotten password request and response types.
+
+use serde::{Deserialize, Serialize};
+
+/// Request payload for requesting a password reset email.
+#[derive(Deserialize)]
+pub struct ApiAuthPasswordForgotRequest {
+    /// The email address of the account.
+    pub email: String,
+}
+
+/// Response payload for requesting a password reset email.
+///
+/// The response is the same whether or not an account uses the email address.
+#[derive(Serialize)]
+pub struct ApiAuthPasswordForgotResponse {
+    /// Whether the request was accepted.
+    pub ok: bool,
+    /// A human-readable message describing the result.
+    pub message: String,
+}
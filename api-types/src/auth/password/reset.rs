@@ -0,0 +1,22 @@
+//! Password reset request and response types.
+
+use serde::{Deserialize, Serialize};
+
+/// Request payload for setting a new password with a reset token.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthPasswordResetRequest {
+    /// The token from the password reset email.
+    pub token: String,
+    /// The new password.
+    pub new_password: String,
+}
+
+/// Response payload for resetting a password.
+#[derive(Serialize)]
+pub struct ApiAuthPasswordResetResponse {
+    /// Whether the password was reset.
+    pub ok: bool,
+    /// A human-readable message describing the result.
+    pub message: String,
+}
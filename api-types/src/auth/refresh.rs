@@ -0,0 +1,28 @@
+//! Session refresh request and response types.
+
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiAuthTokens;
+
+/// Optional request payload for refreshing a session.
+///
+/// Clients that don't use cookies pass their refresh token in the body; the
+/// new tokens are then returned in the response body as well.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthRefreshRequest {
+    /// The refresh token. Read from the `refresh_token` cookie when omitted.
+    pub refresh_token: Option<String>,
+}
+
+/// Response payload for refreshing a session.
+#[derive(Serialize)]
+pub struct ApiAuthRefreshResponse {
+    /// Whether the refresh was successful.
+    pub ok: bool,
+    /// A human-readable message describing the result.
+    pub message: String,
+    /// The new session tokens (only present if the refresh token was passed in the body).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<ApiAuthTokens>,
+}
@@ -2,7 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::auth::EMAIL_REGEX;
+use crate::auth::{ApiAuthTokens, EMAIL_REGEX, validate_username};
 
 /// Request payload for user registration.
 ///
@@ -17,6 +17,9 @@ pub struct ApiAuthRegisterRequest {
     pub password: String,
     /// The user's bio.
     pub bio: Option<String>,
+    /// Return the session tokens in the response body instead of setting cookies.
+    #[serde(rename = "returnTokens", default)]
+    pub return_tokens: bool,
 }
 
 /// Response payload for user registration.
@@ -30,26 +33,33 @@ pub struct ApiRegisterResponse {
     pub message: String,
     /// The ID of the newly created user (only present if registration succeeded).
     pub id: Option<i64>,
+    /// The session tokens (only present if requested with `returnTokens`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<ApiAuthTokens>,
 }
 
 impl ApiAuthRegisterRequest {
     /// Validates the registration request.
     ///
     /// Checks that:
+    /// - The username is 1 to 16 characters long
     /// - The email is in a valid format
-    /// - The password is at least 6 characters long
-    /// - The password contains at least one uppercase letter, one lowercase letter, and one digit
+    ///
+    /// The password is checked against the password policy separately, as its
+    /// violations are reported in their own response.
     ///
     /// # Returns
     ///
     /// - `Ok(())` if all validation passes
     /// - `Err(String)` with a descriptive error message if validation fails
     pub fn validate(&self) -> Result<(), String> {
+        validate_username(&self.username)?;
+
         if !EMAIL_REGEX.is_match(&self.email) {
             tracing::debug!("Invalid email address");
             return Err("Email format is invalid".into());
         }
 
-        utils::hashing::is_password_suitable(&self.password)
+        Ok(())
     }
 }
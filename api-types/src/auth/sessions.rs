@@ -0,0 +1,5 @@
+/// List sessions endpoint types.
+pub mod get;
+
+/// Revoke sessions endpoint types.
+pub mod delete;
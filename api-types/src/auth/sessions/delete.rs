@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Request payload for revoking sessions on other devices.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthSessionsDeleteRequest {
+    /// The session to revoke. When omitted, every session except the current one is revoked.
+    pub session_id: Option<Uuid>,
+}
+
+/// Response payload for revoking sessions.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthSessionsDeleteResponse {
+    /// Number of sessions that were revoked.
+    pub revoked: u64,
+}
@@ -0,0 +1,32 @@
+//! List sessions response types.
+
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Response payload for listing the authenticated user's active sessions.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthSessionsGetResponse {
+    /// Active sessions, most recently used first.
+    pub sessions: Vec<SessionItem>,
+}
+
+/// Represents a single active session in the response.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionItem {
+    /// Unique identifier for the session.
+    pub id: Uuid,
+    /// User agent of the client the session was issued to.
+    pub user_agent: Option<String>,
+    /// IP address of the client the session was issued to.
+    pub ip_address: Option<String>,
+    /// Whether the request was made with this session.
+    pub current: bool,
+    /// Timestamp when the session was created (RFC3339).
+    pub created_at: String,
+    /// Timestamp when the session was last used (RFC3339).
+    pub last_used_at: String,
+    /// Timestamp when the session expires (RFC3339).
+    pub expires_at: String,
+}
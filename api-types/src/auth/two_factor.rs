@@ -0,0 +1,19 @@
+//! Two-factor login step request types.
+//!
+//! The response is an [`ApiAuthLoginResponse`](crate::auth::login::ApiAuthLoginResponse),
+//! as for a login without two-factor authentication.
+
+use serde::Deserialize;
+
+/// Request payload for completing a login with a second factor.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuthTwoFactorRequest {
+    /// The token returned by `/api/auth/login`.
+    pub mfa_token: String,
+    /// A TOTP code from the authenticator app, or one of the recovery codes.
+    pub code: String,
+    /// Return the session tokens in the response body instead of setting cookies.
+    #[serde(default)]
+    pub return_tokens: bool,
+}
@@ -0,0 +1,21 @@
+//! Email verification request and response types.
+
+use serde::{Deserialize, Serialize};
+
+/// Request payload for confirming an email address.
+#[derive(Deserialize)]
+pub struct ApiAuthVerifyEmailRequest {
+    /// The token from the verification email.
+    pub token: String,
+}
+
+/// Response payload for confirming an email address.
+#[derive(Serialize)]
+pub struct ApiAuthVerifyEmailResponse {
+    /// Whether the email address was verified.
+    pub ok: bool,
+    /// A human-readable message describing the result.
+    pub message: String,
+    /// The verified email address, now the account's email address.
+    pub email: String,
+}
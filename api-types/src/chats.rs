@@ -3,10 +3,18 @@
 //! This module contains all chat-related request and response types
 //! for creating, deleting, and communicating in chat conversations.
 
+/// List conversations endpoint types.
+pub mod get;
 pub mod messages;
 /// Create new chat endpoint types.
 pub mod post;
 
+/// Read marker endpoint types.
+pub mod reads;
+
+/// Chat request endpoint types.
+pub mod requests;
+
 /// WebSocket chat communication types.
 pub mod ws;
 
@@ -1,2 +1,30 @@
+use serde::{Deserialize, Serialize};
+
 pub mod delete;
+/// List chat codes types.
+pub mod get;
 pub mod post;
+/// Chat code QR image types.
+pub mod qr;
+
+/// A chat code, as exchanged with clients: a 5-digit number, or an invite
+/// code string such as `7K3M-Q9TX-C2HD-W5R*`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChatCode {
+    /// A 5-digit numeric code.
+    Numeric(u16),
+    /// A Crockford base32 invite code with its check symbol.
+    Invite(String),
+}
+
+/// The format of a new chat code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatCodeFormat {
+    /// A 5-digit number, easy to read out but easy to guess.
+    #[default]
+    Numeric,
+    /// A high-entropy invite code.
+    Invite,
+}
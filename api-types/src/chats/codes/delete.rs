@@ -1,10 +1,12 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::ChatCode;
+
 #[derive(Deserialize)]
 pub struct ApiChatsCodesDeleteRequest {
-    /// The chat code to be deleted or submitted.
-    pub code: u16,
+    /// The chat code to be deleted, numeric or invite code.
+    pub code: ChatCode,
 }
 
 /// Response payload for successful code operations (deletion or submission).
@@ -0,0 +1,39 @@
+//! List chat codes response types.
+
+use serde::Serialize;
+
+use super::ChatCode;
+
+/// Response payload for listing the authenticated user's active chat codes.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsCodesGetResponse {
+    /// Active codes, most recently created first.
+    pub codes: Vec<ChatCodeItem>,
+    /// Number of active codes the user can have at once.
+    pub max_codes: i64,
+}
+
+/// Represents a single active chat code in the response.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatCodeItem {
+    /// The code to share, a number or an invite code.
+    pub code: ChatCode,
+    /// Shareable link redeeming the code, if invite links are enabled.
+    pub invite_url: Option<String>,
+    /// Label reminding the user who they shared the code with.
+    pub label: Option<String>,
+    /// Number of times the code was redeemed.
+    pub uses: i32,
+    /// Number of times the code can be redeemed.
+    pub max_uses: i32,
+    /// Number of times the code can still be redeemed.
+    pub remaining_uses: i32,
+    /// Whether redemptions wait for the user's approval.
+    pub requires_approval: bool,
+    /// Timestamp when the code was created (RFC3339).
+    pub created_at: String,
+    /// Timestamp when the code expires (RFC3339).
+    pub expires_at: String,
+}
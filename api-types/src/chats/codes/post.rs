@@ -1,10 +1,29 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::ChatCode;
+
 #[derive(Deserialize)]
 pub struct ApiChatsCodesPostRequest {
-    /// The chat code to be deleted or submitted.
-    pub code: u16,
+    /// The chat code to be submitted, numeric or invite code.
+    pub code: Option<ChatCode>,
+    /// The token of an invite link, instead of a code.
+    pub token: Option<String>,
+}
+
+impl ApiChatsCodesPostRequest {
+    /// Validates the submission request.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if exactly one of `code` and `token` is given
+    /// - `Err(String)` with a descriptive error message otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.code, &self.token) {
+            (Some(_), None) | (None, Some(_)) => Ok(()),
+            _ => Err("Either a code or an invite link token is required".into()),
+        }
+    }
 }
 
 /// Response payload for successful code operations (deletion or submission).
@@ -15,4 +34,7 @@ pub struct ApiChatsCodesPostResponse {
 
     /// The ID of the created conversation (only for submission).
     pub conversation_id: Option<Uuid>,
+
+    /// The ID of the chat request sent instead, when the code requires approval.
+    pub request_id: Option<Uuid>,
 }
@@ -0,0 +1,91 @@
+//! Chat code QR image request types.
+
+use serde::Deserialize;
+
+/// Smallest width and height of a QR image, in pixels.
+pub const MIN_QR_SIZE: u32 = 64;
+
+/// Largest width and height of a QR image, in pixels.
+pub const MAX_QR_SIZE: u32 = 2048;
+
+/// Width and height of a QR image requested without a size, in pixels.
+pub const DEFAULT_QR_SIZE: u32 = 256;
+
+/// Query parameters for rendering a chat code as a QR image.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsCodesQrRequest {
+    /// Image format, SVG by default.
+    #[serde(default)]
+    pub format: QrImageFormat,
+    /// Largest width and height of the image, in pixels.
+    pub size: Option<u32>,
+    /// How much of the QR code can be damaged and still scan, medium by default.
+    #[serde(default)]
+    pub error_correction: QrErrorCorrection,
+    /// What the QR code encodes. Defaults to the invite link when invite
+    /// links are enabled, and to the code otherwise.
+    pub content: Option<QrContent>,
+}
+
+impl ApiChatsCodesQrRequest {
+    /// Validates the request.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the size is between [`MIN_QR_SIZE`] and [`MAX_QR_SIZE`]
+    /// - `Err(String)` with a descriptive error message otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_QR_SIZE..=MAX_QR_SIZE).contains(&self.size()) {
+            return Err(format!(
+                "Size must be between {MIN_QR_SIZE} and {MAX_QR_SIZE} pixels"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the requested size, or [`DEFAULT_QR_SIZE`].
+    pub fn size(&self) -> u32 {
+        self.size.unwrap_or(DEFAULT_QR_SIZE)
+    }
+}
+
+/// Format of a QR image.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QrImageFormat {
+    /// Scalable vector image, `image/svg+xml`.
+    #[default]
+    Svg,
+    /// Grayscale bitmap, `image/png`.
+    Png,
+}
+
+/// Error correction level of a QR code. Higher levels survive more damage
+/// but need denser codes.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QrErrorCorrection {
+    /// Recovers 7% of the code.
+    #[serde(rename = "L", alias = "l")]
+    Low,
+    /// Recovers 15% of the code.
+    #[default]
+    #[serde(rename = "M", alias = "m")]
+    Medium,
+    /// Recovers 25% of the code.
+    #[serde(rename = "Q", alias = "q")]
+    Quartile,
+    /// Recovers 30% of the code.
+    #[serde(rename = "H", alias = "h")]
+    High,
+}
+
+/// What a QR code encodes.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QrContent {
+    /// The chat code itself, to be typed or pasted.
+    Code,
+    /// The signed invite link redeeming the code.
+    Link,
+}
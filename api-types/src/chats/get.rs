@@ -0,0 +1,44 @@
+//! List conversations response types.
+
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Response payload for listing the authenticated user's conversations.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsGetResponse {
+    /// Conversations the user is part of, most recently active first.
+    pub conversations: Vec<ConversationItem>,
+}
+
+/// Represents a single conversation in the response.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationItem {
+    /// Unique identifier for the conversation.
+    pub id: Uuid,
+    /// Username of the other participant.
+    pub other_username: String,
+    /// Preview of the most recent message, if any was sent.
+    pub last_message: Option<LastMessagePreview>,
+    /// Number of messages from the other participant that the user has not read.
+    pub unread_count: i64,
+    /// Timestamp of the most recent message, or of the conversation creation (RFC3339).
+    pub last_message_at: String,
+    /// Timestamp when the conversation was created (RFC3339).
+    pub created_at: String,
+}
+
+/// Preview of the most recent message in a conversation.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastMessagePreview {
+    /// Unique identifier for the message.
+    pub id: Uuid,
+    /// The beginning of the message content.
+    pub preview: String,
+    /// The user who sent the message.
+    pub user_sent: String,
+    /// Timestamp when the message was sent (RFC3339).
+    pub sent_at: String,
+}
@@ -41,4 +41,6 @@ pub struct ChatItem {
     pub user_sent: String,
     /// Timestamp when the message was sent.
     pub sent_at: String,
+    /// Whether the other participant has read the message.
+    pub read_by_partner: bool,
 }
@@ -1,14 +1,79 @@
 //! Create new chat request and response types.
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use super::codes::{ChatCode, ChatCodeFormat};
+
+/// Maximum number of characters of a chat code label.
+pub const MAX_LABEL_LENGTH: usize = 64;
+
+/// Optional request payload for creating a chat code.
+///
+/// Codes created without a body get the default time to live of the user's
+/// tier, a single use, no label and the numeric format, and start
+/// conversations without approval.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsPostRequest {
+    /// Number of seconds until the code expires.
+    pub ttl_seconds: Option<i64>,
+    /// Number of times the code can be redeemed.
+    pub max_uses: Option<i32>,
+    /// Label reminding the user who they shared the code with.
+    pub label: Option<String>,
+    /// Whether to create a numeric code or an invite code.
+    #[serde(default)]
+    pub format: ChatCodeFormat,
+    /// Whether redemptions wait for the user's approval before starting a conversation.
+    #[serde(default)]
+    pub requires_approval: bool,
+}
+
+impl ApiChatsPostRequest {
+    /// Validates the request, apart from the limits of the user's tier.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the label is at most [`MAX_LABEL_LENGTH`] characters long
+    /// - `Err(String)` with a descriptive error message otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(label) = &self.label
+            && label.trim().chars().count() > MAX_LABEL_LENGTH
+        {
+            return Err(format!(
+                "Label must be at most {MAX_LABEL_LENGTH} characters"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the trimmed label, if it isn't blank.
+    pub fn label(&self) -> Option<&str> {
+        self.label
+            .as_deref()
+            .map(str::trim)
+            .filter(|label| !label.is_empty())
+    }
+}
 
 /// Response payload for successful chat creation.
 ///
 /// Contains the chat code and a success message for the newly created chat.
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ApiChatsPostResponse {
     /// Success message for chat creation.
     pub message: String,
-    /// The unique code for the created chat.
-    pub code: u16,
+    /// The unique code for the created chat, a number or an invite code.
+    pub code: ChatCode,
+    /// Shareable link redeeming the code, if invite links are enabled.
+    pub invite_url: Option<String>,
+    /// The code's label, if any.
+    pub label: Option<String>,
+    /// Number of times the code can be redeemed.
+    pub max_uses: i32,
+    /// Whether redemptions wait for the user's approval.
+    pub requires_approval: bool,
+    /// Timestamp when the code expires (RFC3339).
+    pub expires_at: String,
 }
@@ -0,0 +1,2 @@
+/// Advance read marker endpoint types.
+pub mod post;
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Request payload for advancing the user's read marker in a conversation.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsReadsPostRequest {
+    /// Conversation that the message belongs to.
+    pub conversation_id: Uuid,
+    /// The last message the user has read.
+    pub message_id: Uuid,
+}
+
+/// Response payload for advancing the user's read marker.
+///
+/// Contains the marker after the update, which stays unchanged when the
+/// given message is older than the one already read.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsReadsPostResponse {
+    /// The last message the user has read.
+    pub last_read_message_id: Option<Uuid>,
+    /// Timestamp of the last message the user has read.
+    pub last_read_at: String,
+}
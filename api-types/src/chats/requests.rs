@@ -0,0 +1,4 @@
+/// List chat requests endpoint types.
+pub mod get;
+/// Accept or decline chat request endpoint types.
+pub mod post;
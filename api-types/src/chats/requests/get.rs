@@ -0,0 +1,29 @@
+//! List chat requests response types.
+
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Response payload for listing the authenticated user's pending chat requests.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsRequestsGetResponse {
+    /// Requests to chat with the user, waiting for their decision, oldest first.
+    pub incoming: Vec<ChatRequestItem>,
+    /// Requests the user sent, waiting for the other user's decision, oldest first.
+    pub outgoing: Vec<ChatRequestItem>,
+}
+
+/// Represents a single pending chat request in the response.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatRequestItem {
+    /// Unique identifier of the request.
+    pub id: Uuid,
+    /// Username of the other user: the requester of incoming requests, the
+    /// code owner of outgoing ones.
+    pub username: String,
+    /// Label of the redeemed code, only shown to its owner.
+    pub label: Option<String>,
+    /// Timestamp when the request was sent (RFC3339).
+    pub created_at: String,
+}
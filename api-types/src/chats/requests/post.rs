@@ -0,0 +1,24 @@
+//! Accept or decline chat request types.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Request payload for deciding on a pending chat request.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsRequestsPostRequest {
+    /// The request to decide on.
+    pub request_id: Uuid,
+    /// Whether to accept the request and start the conversation, or decline it.
+    pub accept: bool,
+}
+
+/// Response payload for a decided chat request.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiChatsRequestsPostResponse {
+    /// Success message for the decision.
+    pub message: String,
+    /// The conversation with the requester, when the request was accepted.
+    pub conversation_id: Option<Uuid>,
+}
@@ -1,12 +1,224 @@
 //! WebSocket connection types.
+//!
+//! Every frame exchanged over `/api/chats/ws` is a JSON object wrapped in a
+//! [`WsEnvelope`], which carries the protocol version `v` next to the frame's
+//! `type` tag, e.g. `{"v":1,"type":"send","clientId":"abc","content":"Hi"}`.
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Version of the WebSocket protocol spoken by the server.
+pub const WS_PROTOCOL_VERSION: u16 = 1;
+
 /// Query parameters for WebSocket connections.
+///
+/// Clients that cannot set headers on the handshake request may also pass
+/// their access token as `access_token`; it is read by the auth middleware.
 #[derive(Deserialize)]
 pub struct ApiChatsWsQuery {
-    /// The chat code to connect to
+    /// Conversation to subscribe to right away. Client frames that omit
+    /// `conversationId` target this conversation.
     #[serde(rename = "chatId")]
     pub chat_id: Option<Uuid>,
+    /// Replay messages of `chat_id` sent after this point before streaming live events.
+    ///
+    /// Either the ID of the last message the client has seen, or an RFC3339
+    /// timestamp.
+    pub since: Option<String>,
+}
+
+/// Versioned wrapper around a WebSocket frame.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WsEnvelope<F> {
+    /// Protocol version the frame was written against.
+    pub v: u16,
+    /// The frame itself, flattened next to the version.
+    #[serde(flatten)]
+    pub frame: F,
+}
+
+impl<F> WsEnvelope<F> {
+    /// Wraps a frame using the current [`WS_PROTOCOL_VERSION`].
+    pub fn new(frame: F) -> Self {
+        Self {
+            v: WS_PROTOCOL_VERSION,
+            frame,
+        }
+    }
+}
+
+/// Frames sent from the client to the server.
+#[derive(Deserialize, Debug)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum WsClientFrame {
+    /// Subscribes the socket to a conversation.
+    Subscribe {
+        /// Client-supplied correlation ID, echoed back in the matching `subscribed` or `error`.
+        client_id: Option<String>,
+        /// The conversation to subscribe to.
+        conversation_id: Uuid,
+        /// Replay messages sent after this message ID or RFC3339 timestamp first.
+        since: Option<String>,
+    },
+    /// Unsubscribes the socket from a conversation.
+    Unsubscribe {
+        /// Client-supplied correlation ID, echoed back in the matching `unsubscribed`.
+        client_id: Option<String>,
+        /// The conversation to unsubscribe from.
+        conversation_id: Uuid,
+    },
+    /// Sends a new message to a conversation.
+    Send {
+        /// Client-supplied correlation ID, echoed back in the matching `ack` or `error`.
+        client_id: Option<String>,
+        /// Target conversation. Defaults to the socket's `chatId`.
+        conversation_id: Option<Uuid>,
+        /// The message content.
+        content: String,
+    },
+    /// Marks every message up to and including the given one as read.
+    Read {
+        /// Client-supplied correlation ID, echoed back in an `error` if the frame fails.
+        client_id: Option<String>,
+        /// Target conversation. Defaults to the socket's `chatId`.
+        conversation_id: Option<Uuid>,
+        /// The last message the user has read.
+        message_id: Uuid,
+    },
+    /// Signals that the user started typing.
+    ///
+    /// The indicator expires on the server unless it is repeated, so clients
+    /// should resend it every few seconds while the user keeps typing.
+    TypingStart {
+        /// Target conversation. Defaults to the socket's `chatId`.
+        conversation_id: Option<Uuid>,
+    },
+    /// Signals that the user stopped typing.
+    TypingStop {
+        /// Target conversation. Defaults to the socket's `chatId`.
+        conversation_id: Option<Uuid>,
+    },
+}
+
+/// Frames sent from the server to the client.
+#[derive(Serialize, Debug)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum WsServerFrame {
+    /// Confirms a subscription to a conversation.
+    Subscribed {
+        /// Correlation ID from the `subscribe` frame.
+        client_id: Option<String>,
+        /// The subscribed conversation.
+        conversation_id: Uuid,
+    },
+    /// Confirms that the socket no longer receives events of a conversation.
+    Unsubscribed {
+        /// Correlation ID from the `unsubscribe` frame.
+        client_id: Option<String>,
+        /// The unsubscribed conversation.
+        conversation_id: Uuid,
+    },
+    /// Confirms that a `send` frame was persisted.
+    Ack {
+        /// Correlation ID from the `send` frame.
+        client_id: Option<String>,
+        /// Conversation the message was sent to.
+        conversation_id: Uuid,
+        /// ID of the persisted message.
+        message_id: Uuid,
+        /// Timestamp when the message was sent (RFC3339).
+        sent_at: String,
+    },
+    /// A new message was posted to the conversation.
+    Message {
+        /// Conversation the message belongs to.
+        conversation_id: Uuid,
+        /// ID of the message.
+        message_id: Uuid,
+        /// Username of the sender.
+        user_sent: String,
+        /// The message content.
+        content: String,
+        /// Timestamp when the message was sent (RFC3339).
+        sent_at: String,
+    },
+    /// An existing message was edited.
+    Edited {
+        /// Conversation the message belongs to.
+        conversation_id: Uuid,
+        /// ID of the edited message.
+        message_id: Uuid,
+        /// The new message content.
+        content: String,
+        /// Timestamp when the message was edited (RFC3339).
+        edited_at: String,
+    },
+    /// A message was deleted.
+    Deleted {
+        /// Conversation the message belonged to.
+        conversation_id: Uuid,
+        /// ID of the deleted message.
+        message_id: Uuid,
+    },
+    /// A participant's read marker moved forward.
+    Read {
+        /// Conversation the read marker belongs to.
+        conversation_id: Uuid,
+        /// Username of the participant who read the messages.
+        user_read: String,
+        /// The last message the participant has read.
+        message_id: Uuid,
+        /// Timestamp of the last message the participant has read (RFC3339).
+        read_at: String,
+    },
+    /// All messages missed since the `since` query parameter were replayed;
+    /// every following frame is live.
+    ReplayComplete {
+        /// Conversation the messages were replayed from.
+        conversation_id: Uuid,
+        /// Number of replayed `message` frames.
+        count: u64,
+    },
+    /// The other participant started typing.
+    TypingStart {
+        /// Conversation the participant is typing in.
+        conversation_id: Uuid,
+        /// Username of the participant who is typing.
+        user_typing: String,
+    },
+    /// The other participant stopped typing, or their typing indicator expired.
+    TypingStop {
+        /// Conversation the participant was typing in.
+        conversation_id: Uuid,
+        /// Username of the participant who was typing.
+        user_typing: String,
+    },
+    /// The other participant came online or went offline.
+    Presence {
+        /// Conversation shared with the participant.
+        conversation_id: Uuid,
+        /// Username of the participant.
+        username: String,
+        /// Whether the participant has at least one open WebSocket.
+        online: bool,
+        /// Timestamp when the participant's last WebSocket closed (RFC3339).
+        last_seen_at: Option<String>,
+    },
+    /// A client frame could not be processed.
+    Error {
+        /// Correlation ID of the failed frame, when it could be read.
+        client_id: Option<String>,
+        /// Conversation the failed frame targeted, when known.
+        conversation_id: Option<Uuid>,
+        /// Human-readable description of the error.
+        message: String,
+    },
 }
@@ -5,3 +5,15 @@ pub mod get;
 
 /// User update endpoint types.
 pub mod patch;
+
+/// API key management endpoint types.
+pub mod keys;
+
+/// Linked identity provider account endpoint types.
+pub mod identities;
+
+/// Two-factor authentication enrollment endpoint types.
+pub mod two_factor;
+
+/// Verification email resend endpoint types.
+pub mod verify_email;
@@ -14,6 +14,10 @@ pub struct UsersMeResponseInternal {
     pub username: String,
     /// The user's optional biography/description.
     pub bio: Option<String>,
+    /// Whether the user verified their email address.
+    pub email_verified: bool,
+    /// New email address waiting for verification.
+    pub pending_email: Option<String>,
     /// Timestamp when the user account was created.
     pub created_at: OffsetDateTime,
     /// Timestamp when the user account was last updated.
@@ -28,6 +32,11 @@ pub struct UsersMeResponse {
     pub username: String,
     /// The user's optional biography/description.
     pub bio: Option<String>,
+    /// Whether the user verified their email address.
+    pub email_verified: bool,
+    /// New email address waiting for verification, replacing `email` once confirmed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_email: Option<String>,
     /// Timestamp when the user account was created.
     pub created_at: String,
     /// Timestamp when the user account was last updated.
@@ -0,0 +1,8 @@
+/// List linked identities endpoint types.
+pub mod get;
+
+/// Link identity endpoint types.
+pub mod post;
+
+/// Unlink identity endpoint types.
+pub mod delete;
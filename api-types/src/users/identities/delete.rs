@@ -0,0 +1,18 @@
+//! Identity unlinking request and response types.
+
+use serde::{Deserialize, Serialize};
+
+/// Request payload for unlinking an identity provider account.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsersIdentitiesDeleteRequest {
+    /// ID of the link to remove.
+    pub identity_id: i64,
+}
+
+/// Response payload for unlinking an identity provider account.
+#[derive(Serialize)]
+pub struct ApiUsersIdentitiesDeleteResponse {
+    /// Success message for the operation.
+    pub message: String,
+}
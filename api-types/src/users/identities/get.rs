@@ -0,0 +1,28 @@
+//! Linked identity listing response types.
+
+use serde::Serialize;
+
+/// An identity provider account linked to the user.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsersIdentity {
+    /// Unique ID of the link.
+    pub id: i64,
+    /// ID of the provider, if it is still configured.
+    pub provider: Option<String>,
+    /// Issuer URL of the provider.
+    pub issuer: String,
+    /// Email address the provider reported.
+    pub email: Option<String>,
+    /// When the identity was linked (RFC3339).
+    pub created_at: String,
+    /// When the identity was last used to log in (RFC3339).
+    pub last_login_at: Option<String>,
+}
+
+/// Response payload listing the user's linked identities.
+#[derive(Serialize)]
+pub struct ApiUsersIdentitiesGetResponse {
+    /// The linked identities, oldest first.
+    pub identities: Vec<ApiUsersIdentity>,
+}
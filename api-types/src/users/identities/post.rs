@@ -0,0 +1,18 @@
+//! Identity linking request and response types.
+
+use serde::{Deserialize, Serialize};
+
+/// Request payload for linking an identity provider account.
+#[derive(Deserialize)]
+pub struct ApiUsersIdentitiesPostRequest {
+    /// ID of the provider to link.
+    pub provider: String,
+}
+
+/// Response payload for linking an identity provider account.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsersIdentitiesPostResponse {
+    /// URL to send the browser to, where the user logs into the provider.
+    pub authorize_url: String,
+}
@@ -0,0 +1,8 @@
+/// List API keys endpoint types.
+pub mod get;
+
+/// Create API key endpoint types.
+pub mod post;
+
+/// Revoke API key endpoint types.
+pub mod delete;
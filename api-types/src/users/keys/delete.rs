@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Request payload for revoking an API key.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsersKeysDeleteRequest {
+    /// The API key to revoke.
+    pub key_id: Uuid,
+}
+
+/// Response payload for revoking an API key.
+#[derive(Serialize)]
+pub struct ApiUsersKeysDeleteResponse {
+    /// Success message for the operation.
+    pub message: String,
+}
@@ -0,0 +1,31 @@
+//! List API keys response types.
+
+use serde::Serialize;
+use utils::scopes::Scope;
+use uuid::Uuid;
+
+/// Response payload for listing the authenticated user's API keys.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsersKeysGetResponse {
+    /// API keys, most recently created first.
+    pub keys: Vec<ApiKeyItem>,
+}
+
+/// Represents a single API key in the response. The key itself is never returned again.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyItem {
+    /// Unique identifier for the API key.
+    pub id: Uuid,
+    /// Name given to the API key.
+    pub name: String,
+    /// First characters of the key, to tell keys apart.
+    pub prefix: String,
+    /// Scopes granted to the API key.
+    pub scopes: Vec<Scope>,
+    /// Timestamp when the API key was created (RFC3339).
+    pub created_at: String,
+    /// Timestamp when the API key was last used (RFC3339), if ever.
+    pub last_used_at: Option<String>,
+}
@@ -0,0 +1,60 @@
+//! Create API key request and response types.
+
+use serde::{Deserialize, Serialize};
+use utils::scopes::Scope;
+use uuid::Uuid;
+
+/// Maximum number of characters in an API key name.
+pub const MAX_KEY_NAME_LENGTH: usize = 64;
+
+/// Request payload for creating an API key.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsersKeysPostRequest {
+    /// Name of the API key, e.g. the bot or integration using it.
+    pub name: String,
+    /// Scopes granted to the API key. At least one is required.
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiUsersKeysPostRequest {
+    /// Validates the API key request.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the request is valid
+    /// - `Err(String)` with an error message if validation fails
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Name is required".to_string());
+        }
+        if name.chars().count() > MAX_KEY_NAME_LENGTH {
+            return Err(format!(
+                "Name must be at most {MAX_KEY_NAME_LENGTH} characters"
+            ));
+        }
+        if self.scopes.is_empty() {
+            return Err("At least one scope is required".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Response payload for a created API key.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsersKeysPostResponse {
+    /// Unique identifier for the API key.
+    pub id: Uuid,
+    /// Name given to the API key.
+    pub name: String,
+    /// The API key. It is only shown once and cannot be retrieved again.
+    pub key: String,
+    /// First characters of the key, to tell keys apart.
+    pub prefix: String,
+    /// Scopes granted to the API key.
+    pub scopes: Vec<Scope>,
+    /// Timestamp when the API key was created (RFC3339).
+    pub created_at: String,
+}
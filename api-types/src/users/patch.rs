@@ -18,6 +18,9 @@ pub struct UsersUpdateRequest {
     pub password: String,
     /// Optional new password to update the user's password.
     pub new_password: Option<String>,
+    /// Current TOTP code, required to change the email or password when
+    /// two-factor authentication is enabled.
+    pub totp_code: Option<String>,
 }
 
 #[derive(serde::Serialize)]
@@ -25,4 +28,8 @@ pub struct UsersUpdateResponse {
     /// List of fields that were updated.
     #[serde(rename = "updatedFields")]
     pub updated_fields: Vec<String>,
+    /// New email address waiting for verification. The current address stays
+    /// in use until the link sent to the new one is opened.
+    #[serde(rename = "pendingEmail", skip_serializing_if = "Option::is_none")]
+    pub pending_email: Option<String>,
 }
@@ -0,0 +1,8 @@
+/// Start enrollment endpoint types.
+pub mod post;
+
+/// Confirm enrollment endpoint types.
+pub mod confirm;
+
+/// Disable two-factor authentication endpoint types.
+pub mod delete;
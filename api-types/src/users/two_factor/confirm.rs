@@ -0,0 +1,17 @@
+//! Two-factor enrollment confirmation request and response types.
+
+use serde::{Deserialize, Serialize};
+
+/// Request payload for confirming two-factor enrollment.
+#[derive(Deserialize)]
+pub struct ApiUsersTwoFactorConfirmRequest {
+    /// The current TOTP code from the authenticator app.
+    pub code: String,
+}
+
+/// Response payload for confirming two-factor enrollment.
+#[derive(Serialize)]
+pub struct ApiUsersTwoFactorConfirmResponse {
+    /// Success message for the operation.
+    pub message: String,
+}
@@ -0,0 +1,20 @@
+//! Disable two-factor authentication request and response types.
+
+use serde::{Deserialize, Serialize};
+
+/// Request payload for disabling two-factor authentication.
+#[derive(Deserialize)]
+pub struct ApiUsersTwoFactorDeleteRequest {
+    /// The user's current password.
+    pub password: String,
+    /// A TOTP code from the authenticator app, or one of the recovery codes.
+    /// Not needed to cancel an enrollment that was never confirmed.
+    pub code: Option<String>,
+}
+
+/// Response payload for disabling two-factor authentication.
+#[derive(Serialize)]
+pub struct ApiUsersTwoFactorDeleteResponse {
+    /// Success message for the operation.
+    pub message: String,
+}
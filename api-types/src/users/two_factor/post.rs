@@ -0,0 +1,18 @@
+//! Two-factor enrollment response types.
+
+use serde::Serialize;
+
+/// Response payload for starting two-factor enrollment.
+///
+/// Two-factor authentication is only enabled once a first code is confirmed
+/// at `/api/users/2fa/confirm`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsersTwoFactorPostResponse {
+    /// The base32 encoded TOTP secret, for manual entry in the authenticator app.
+    pub secret: String,
+    /// The `otpauth://` URI of the secret, usually shown as a QR code.
+    pub otpauth_uri: String,
+    /// Single-use recovery codes. They are only shown once.
+    pub recovery_codes: Vec<String>,
+}
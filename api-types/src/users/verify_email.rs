@@ -0,0 +1,14 @@
+//! Verification email resend response types.
+
+use serde::Serialize;
+
+/// Response payload for resending the verification email.
+#[derive(Serialize)]
+pub struct ApiUsersVerifyEmailResponse {
+    /// Whether the email was sent.
+    pub ok: bool,
+    /// A human-readable message describing the result.
+    pub message: String,
+    /// The address the email was sent to.
+    pub email: String,
+}
@@ -1,47 +1,249 @@
 //! Authentication middleware for protected routes.
 //!
-//! This middleware validates JWT tokens from cookies and prevents
-//! unauthorized access to protected endpoints.
+//! This middleware validates JWT tokens from the `Authorization` header or
+//! cookies, checks that the session they belong to was not revoked, and
+//! prevents unauthorized access to protected endpoints. It also accepts API
+//! keys, whose scopes handlers check through [`Access`].
 
 use axum::body::Body;
+use axum::extract::{FromRequestParts, Query, State};
 use axum::http::Request;
 use axum::http::StatusCode;
+use axum::http::header::{AUTHORIZATION, UPGRADE};
+use axum::http::request::Parts;
 use axum::middleware::Next;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
 use axum_extra::extract::CookieJar;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use utils::errors::error_response;
+use utils::scopes::Scope;
+use utils::tokens::{API_KEY_PREFIX, hash_token};
+use uuid::Uuid;
 
-/// Authentication middleware that validates JWT tokens from cookies.
+/// Query parameter carrying the access token on WebSocket upgrades, for
+/// clients that cannot set headers on the handshake request (e.g. browsers).
+pub const ACCESS_TOKEN_PARAM: &str = "access_token";
+
+/// ID of the session the current request is authenticated with.
+///
+/// Inserted into the request extensions by [`auth_middleware`], next to the
+/// user ID.
+///
+/// Also usable as an extractor by handlers that must not be reachable with an
+/// API key, such as session and key management: requests authenticated with
+/// an API key are rejected with `403 FORBIDDEN`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionId(pub Uuid);
+
+impl<S: Send + Sync> FromRequestParts<S> for SessionId {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<SessionId>().copied().ok_or_else(|| {
+            error_response(
+                StatusCode::FORBIDDEN,
+                "This endpoint requires a signed-in session, API keys cannot use it.",
+            )
+        })
+    }
+}
+
+/// What the current request is allowed to do.
+///
+/// Inserted into the request extensions by [`auth_middleware`], next to the
+/// user ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Authenticated with a session; every scope is granted.
+    Session,
+    /// Authenticated with an API key, limited to its scopes.
+    ApiKey {
+        /// ID of the API key.
+        key_id: Uuid,
+        /// Scopes granted to the API key.
+        scopes: Vec<Scope>,
+    },
+}
+
+impl Access {
+    /// Returns whether the request may use the given scope.
+    pub fn allows(&self, scope: Scope) -> bool {
+        match self {
+            Access::Session => true,
+            Access::ApiKey { scopes, .. } => scopes.contains(&scope),
+        }
+    }
+
+    /// Ensures the request may use the given scope.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the scope is granted
+    /// - `Err(Response)` - A `403 FORBIDDEN` response otherwise
+    #[allow(clippy::result_large_err)]
+    pub fn require(&self, scope: Scope) -> Result<(), Response> {
+        if self.allows(scope) {
+            return Ok(());
+        }
+
+        tracing::info!(%scope, "API key lacks required scope");
+        Err(error_response(
+            StatusCode::FORBIDDEN,
+            format!("This API key lacks the {scope} scope."),
+        ))
+    }
+}
+
+/// Extracts the access token from the request.
+///
+/// The token is looked up, in order, in the `Authorization: Bearer` header, the
+/// `session_token` cookie and, for WebSocket upgrades only, the `access_token`
+/// query parameter. The query parameter is limited to upgrades because URLs
+/// end up in logs and browser history.
+///
+/// An `Authorization` header with another scheme, such as the Basic auth some
+/// proxies add, is ignored rather than taken as a missing token.
+fn extract_token(req: &Request<Body>, cookies: &CookieJar) -> Option<String> {
+    let bearer = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty());
+    if let Some(token) = bearer {
+        return Some(token.to_string());
+    }
+
+    if let Some(cookie) = cookies.get(utils::cookies::ACCESS_COOKIE) {
+        return Some(cookie.value().to_string());
+    }
+
+    let is_websocket = req
+        .headers()
+        .get(UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    if !is_websocket {
+        return None;
+    }
+
+    Query::<HashMap<String, String>>::try_from_uri(req.uri())
+        .ok()
+        .and_then(|Query(mut params)| params.remove(ACCESS_TOKEN_PARAM))
+}
+
+/// Looks up an API key, recording its use at most once a minute.
+///
+/// # Returns
+///
+/// - `Ok(Some((user_id, access)))` if the key exists
+/// - `Ok(None)` if the key is unknown
+/// - `Err(sqlx::Error)` if the database operation fails
+async fn authenticate_api_key(
+    pool: &PgPool,
+    key: &str,
+) -> Result<Option<(i64, Access)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        WITH key AS (
+            SELECT id, user_id, scopes, last_used_at
+            FROM api_keys
+            WHERE key_hash = $1
+        ), touched AS (
+            UPDATE api_keys k
+            SET last_used_at = NOW()
+            FROM key
+            WHERE k.id = key.id
+              AND (key.last_used_at IS NULL OR key.last_used_at < NOW() - INTERVAL '1 minute')
+        )
+        SELECT id AS "id!", user_id AS "user_id!", scopes AS "scopes!"
+        FROM key
+        "#,
+        hash_token(key)
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| {
+        let scopes = row
+            .scopes
+            .iter()
+            .filter_map(|scope| {
+                scope
+                    .parse::<Scope>()
+                    .inspect_err(|e| tracing::warn!(key_id = %row.id, "{e}"))
+                    .ok()
+            })
+            .collect();
+
+        (
+            row.user_id,
+            Access::ApiKey {
+                key_id: row.id,
+                scopes,
+            },
+        )
+    }))
+}
+
+/// Authentication middleware that validates JWT access tokens and API keys.
 ///
 /// This middleware:
-/// 1. Extracts the `auth_token` cookie from the request
+/// 1. Extracts the access token from the `Authorization: Bearer` header, the
+///    `session_token` cookie or, on WebSocket upgrades, the `access_token` query parameter
 /// 2. Decodes and validates the JWT token
-/// 3. Stores the claims in request extensions for handler access
-/// 4. Returns 401 Unauthorized if the token is missing or invalid
+/// 3. Checks that the session named by the `jti` claim is neither revoked nor expired
+/// 4. Stores the user ID, [`SessionId`] and [`Access::Session`] in request extensions for handler access
+/// 5. Returns 401 Unauthorized if the token is missing or invalid, or the session is revoked
+///
+/// Tokens starting with `ck_` are API keys instead. For those, the user ID and
+/// [`Access::ApiKey`] with the key's scopes are stored, but no [`SessionId`].
 ///
 /// # Example
 ///
 /// ```rust,no_run
-/// use axum::{Router, middleware};
-/// use middleware::auth::auth_middleware;
+/// use axum::{Router, middleware, routing::get};
+/// use ::middleware::auth::auth_middleware;
+/// # async fn protected_handler() {}
+/// # let pool: sqlx::PgPool = unimplemented!();
 ///
-/// let app = Router::new()
+/// let app: Router = Router::new()
 ///     .route("/protected", get(protected_handler))
-///     .layer(middleware::from_fn(auth_middleware));
+///     .layer(middleware::from_fn_with_state(pool, auth_middleware));
 /// ```
-#[tracing::instrument(skip(cookies, req, next))]
+#[tracing::instrument(skip(pool, cookies, req, next))]
 pub async fn auth_middleware(
+    State(pool): State<PgPool>,
     cookies: CookieJar,
     mut req: Request<Body>,
     next: Next,
 ) -> Result<impl IntoResponse, StatusCode> {
-    // Extract the auth token from cookie
-    let token = cookies
-        .get("session_token")
-        .map(|c| c.value().to_string())
-        .ok_or_else(|| {
-            tracing::warn!("No auth_token cookie found");
-            StatusCode::UNAUTHORIZED
-        })?;
+    // Extract the auth token from the header, cookie or query
+    let token = extract_token(&req, &cookies).ok_or_else(|| {
+        tracing::warn!("No access token found");
+        StatusCode::UNAUTHORIZED
+    })?;
+
+    if token.starts_with(API_KEY_PREFIX) {
+        let (uid, access) = authenticate_api_key(&pool, &token)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = ?e, "Failed to look up API key.");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .ok_or_else(|| {
+                tracing::warn!("Unknown API key");
+                StatusCode::UNAUTHORIZED
+            })?;
+
+        req.extensions_mut().insert(uid);
+        req.extensions_mut().insert(access);
+
+        tracing::debug!("Auth middleware passed with API key");
+        return Ok(next.run(req).await);
+    }
 
     // Decode and validate the JWT
     let claims = utils::jwt::verify_jwt(&token).map_err(|e| {
@@ -49,12 +251,52 @@ pub async fn auth_middleware(
         StatusCode::UNAUTHORIZED
     })?;
     let uid = claims.sub.parse::<i64>().map_err(|e| {
-        tracing::warn!(error = ?e, "Invalid user ID in JWT claims cookie.");
+        tracing::warn!(error = ?e, "Invalid user ID in JWT claims.");
         StatusCode::BAD_REQUEST
     })?;
+    let session_id = Uuid::parse_str(&claims.jti).map_err(|e| {
+        tracing::warn!(error = ?e, "Invalid session ID in JWT claims.");
+        StatusCode::UNAUTHORIZED
+    })?;
+
+    // Check the session is still active, refreshing its last use at most once a minute
+    let active = sqlx::query_scalar!(
+        r#"
+        WITH session AS (
+            SELECT id, last_used_at
+            FROM sessions
+            WHERE id = $1
+              AND user_id = $2
+              AND revoked_at IS NULL
+              AND expires_at > NOW()
+        ), touched AS (
+            UPDATE sessions s
+            SET last_used_at = NOW()
+            FROM session
+            WHERE s.id = session.id
+              AND session.last_used_at < NOW() - INTERVAL '1 minute'
+        )
+        SELECT EXISTS(SELECT 1 FROM session) AS "active!"
+        "#,
+        session_id,
+        uid
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| {
+        tracing::error!(error = ?e, "Failed to look up session.");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if !active {
+        tracing::warn!(user_id = uid, %session_id, "Session is revoked or expired");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
 
     // Store claims in request extensions so handlers can access it
     req.extensions_mut().insert(uid);
+    req.extensions_mut().insert(SessionId(session_id));
+    req.extensions_mut().insert(Access::Session);
 
     tracing::debug!("Auth middleware passed");
     Ok(next.run(req).await)
@@ -1,9 +1,10 @@
 //! Middleware utilities for the application.
 //!
 //! This module provides middleware for request processing, including
-//! authentication verification and JWT token validation.
+//! authentication verification, JWT token validation, session revocation checks
+//! and API key scopes.
 
 /// JWT authentication middleware for protecting routes.
 pub mod auth;
 
-pub use auth::auth_middleware;
+pub use auth::{Access, SessionId, auth_middleware};
@@ -0,0 +1,25 @@
+-- Include the message ID, conversation and sender username in insert notifications
+CREATE OR REPLACE FUNCTION notify_message_insert()
+RETURNS TRIGGER AS $$
+DECLARE
+    notification json;
+BEGIN
+    -- Build the notification payload
+    notification = json_build_object(
+        'id', NEW.id,
+        'conversation_id', NEW.conversation_id,
+        'user_id', NEW.user_sent_id,
+        'username', (SELECT username FROM users WHERE id = NEW.user_sent_id),
+        'content', NEW.content,
+        'sent_at', NEW.sent_at
+    );
+
+    -- Send notification to channel named after the conversation_id
+    PERFORM pg_notify(
+        'conversation_' || NEW.conversation_id::text,
+        notification::text
+    );
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
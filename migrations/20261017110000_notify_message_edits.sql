@@ -0,0 +1,85 @@
+-- Tag insert notifications with their kind, so they can be told apart from edits and deletions
+CREATE OR REPLACE FUNCTION notify_message_insert()
+RETURNS TRIGGER AS $$
+DECLARE
+    notification json;
+BEGIN
+    -- Build the notification payload
+    notification = json_build_object(
+        'kind', 'message',
+        'id', NEW.id,
+        'conversation_id', NEW.conversation_id,
+        'user_id', NEW.user_sent_id,
+        'username', (SELECT username FROM users WHERE id = NEW.user_sent_id),
+        'content', NEW.content,
+        'sent_at', NEW.sent_at
+    );
+
+    -- Send notification to channel named after the conversation_id
+    PERFORM pg_notify(
+        'conversation_' || NEW.conversation_id::text,
+        notification::text
+    );
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create a function that sends a notification when a message is edited
+CREATE OR REPLACE FUNCTION notify_message_update()
+RETURNS TRIGGER AS $$
+DECLARE
+    notification json;
+BEGIN
+    notification = json_build_object(
+        'kind', 'edited',
+        'id', NEW.id,
+        'conversation_id', NEW.conversation_id,
+        'user_id', NEW.user_sent_id,
+        'content', NEW.content,
+        'edited_at', NEW.edited_at
+    );
+
+    PERFORM pg_notify(
+        'conversation_' || NEW.conversation_id::text,
+        notification::text
+    );
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create a function that sends a notification when a message is deleted
+CREATE OR REPLACE FUNCTION notify_message_delete()
+RETURNS TRIGGER AS $$
+DECLARE
+    notification json;
+BEGIN
+    notification = json_build_object(
+        'kind', 'deleted',
+        'id', OLD.id,
+        'conversation_id', OLD.conversation_id,
+        'user_id', OLD.user_sent_id
+    );
+
+    PERFORM pg_notify(
+        'conversation_' || OLD.conversation_id::text,
+        notification::text
+    );
+
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create a trigger that fires after a message's content is edited
+CREATE TRIGGER message_update_trigger
+    AFTER UPDATE ON messages
+    FOR EACH ROW
+    WHEN (OLD.content IS DISTINCT FROM NEW.content OR OLD.edited_at IS DISTINCT FROM NEW.edited_at)
+    EXECUTE FUNCTION notify_message_update();
+
+-- Create a trigger that fires after each message deletion
+CREATE TRIGGER message_delete_trigger
+    AFTER DELETE ON messages
+    FOR EACH ROW
+    EXECUTE FUNCTION notify_message_delete();
@@ -0,0 +1,44 @@
+-- Create conversation_reads table to store each participant's read marker
+CREATE TABLE conversation_reads (
+    conversation_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    last_read_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (conversation_id, user_id)
+);
+
+-- Index for finding all read markers of a user
+CREATE INDEX idx_conversation_reads_user ON conversation_reads(user_id);
+
+-- Trigger function to bump last_message_at and the sender's read marker on insert
+CREATE OR REPLACE FUNCTION touch_conversation_on_message()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE conversations
+    SET last_message_at = GREATEST(last_message_at, NEW.sent_at)
+    WHERE id = NEW.conversation_id;
+
+    -- Sending a message implies having read everything before it
+    INSERT INTO conversation_reads (conversation_id, user_id, last_read_at)
+    VALUES (NEW.conversation_id, NEW.user_sent_id, NEW.sent_at)
+    ON CONFLICT (conversation_id, user_id)
+    DO UPDATE SET last_read_at = GREATEST(conversation_reads.last_read_at, EXCLUDED.last_read_at);
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create a trigger that fires after each message insert
+CREATE TRIGGER message_touch_conversation_trigger
+    AFTER INSERT ON messages
+    FOR EACH ROW
+    EXECUTE FUNCTION touch_conversation_on_message();
+
+-- Backfill last_message_at for existing conversations
+UPDATE conversations
+SET last_message_at = latest.sent_at
+FROM (
+    SELECT conversation_id, MAX(sent_at) AS sent_at
+    FROM messages
+    GROUP BY conversation_id
+) AS latest
+WHERE conversations.id = latest.conversation_id;
@@ -0,0 +1,59 @@
+-- Track which message each participant read last
+ALTER TABLE conversation_reads
+ADD COLUMN last_read_message_id UUID REFERENCES messages(id) ON DELETE SET NULL;
+
+-- Sending a message moves the sender's read marker to that message
+CREATE OR REPLACE FUNCTION touch_conversation_on_message()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE conversations
+    SET last_message_at = GREATEST(last_message_at, NEW.sent_at)
+    WHERE id = NEW.conversation_id;
+
+    -- Sending a message implies having read everything before it
+    INSERT INTO conversation_reads (conversation_id, user_id, last_read_message_id, last_read_at)
+    VALUES (NEW.conversation_id, NEW.user_sent_id, NEW.id, NEW.sent_at)
+    ON CONFLICT (conversation_id, user_id)
+    DO UPDATE SET
+        last_read_message_id = EXCLUDED.last_read_message_id,
+        last_read_at = EXCLUDED.last_read_at
+    WHERE conversation_reads.last_read_at <= EXCLUDED.last_read_at;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create a function that sends a notification when a read marker moves
+CREATE OR REPLACE FUNCTION notify_conversation_read()
+RETURNS TRIGGER AS $$
+DECLARE
+    notification json;
+BEGIN
+    IF NEW.last_read_message_id IS NULL
+        OR (TG_OP = 'UPDATE' AND OLD.last_read_message_id IS NOT DISTINCT FROM NEW.last_read_message_id) THEN
+        RETURN NEW;
+    END IF;
+
+    notification = json_build_object(
+        'kind', 'read',
+        'conversation_id', NEW.conversation_id,
+        'user_id', NEW.user_id,
+        'username', (SELECT username FROM users WHERE id = NEW.user_id),
+        'message_id', NEW.last_read_message_id,
+        'read_at', NEW.last_read_at
+    );
+
+    PERFORM pg_notify(
+        'conversation_' || NEW.conversation_id::text,
+        notification::text
+    );
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Create a trigger that fires after each read marker change
+CREATE TRIGGER conversation_read_trigger
+    AFTER INSERT OR UPDATE ON conversation_reads
+    FOR EACH ROW
+    EXECUTE FUNCTION notify_conversation_read();
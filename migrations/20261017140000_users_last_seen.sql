@@ -0,0 +1,3 @@
+-- Record when a user's last WebSocket connection closed
+ALTER TABLE users
+ADD COLUMN last_seen_at TIMESTAMPTZ;
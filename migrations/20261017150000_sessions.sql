@@ -0,0 +1,15 @@
+-- Create sessions table: every issued JWT carries the ID of its session as `jti`,
+-- so that sessions can be listed and revoked before the token expires
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_agent TEXT,
+    ip_address TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ
+);
+
+-- Index for listing and revoking a user's sessions
+CREATE INDEX idx_sessions_user ON sessions(user_id);
@@ -0,0 +1,15 @@
+-- Create refresh_tokens table: each session is a family of refresh tokens,
+-- where every refresh rotates the current token into a new one
+CREATE TABLE refresh_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    session_id UUID NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    -- SHA-256 digest of the token, the token itself is never stored
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set once the token was exchanged for a new one; presenting it again
+    -- means it was stolen, and revokes the whole session
+    rotated_at TIMESTAMPTZ
+);
+
+-- Index for cleaning up the tokens of a session
+CREATE INDEX idx_refresh_tokens_session ON refresh_tokens(session_id);
@@ -0,0 +1,16 @@
+-- Create api_keys table: long-lived, scoped credentials for bots and integrations
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    -- First characters of the key, shown so users can tell their keys apart
+    prefix TEXT NOT NULL,
+    -- SHA-256 digest of the key, the key itself is never stored
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ
+);
+
+-- Index for listing a user's keys
+CREATE INDEX idx_api_keys_user ON api_keys(user_id);
@@ -0,0 +1,34 @@
+-- Two-factor authentication with TOTP (RFC 6238)
+ALTER TABLE users
+    -- Base32 encoded shared secret, set at enrollment
+    ADD COLUMN totp_secret TEXT,
+    -- Set once the first code was confirmed; 2FA is only enforced from then on
+    ADD COLUMN totp_enabled_at TIMESTAMPTZ,
+    -- Time step of the last accepted code, so that a code cannot be used twice
+    ADD COLUMN totp_last_step BIGINT;
+
+-- Single-use recovery codes, for when the authenticator is lost
+CREATE TABLE recovery_codes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- SHA-256 digest of the normalized code, the code itself is never stored
+    code_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_recovery_codes_user ON recovery_codes(user_id);
+
+-- Logins that passed the password check and wait for a second factor
+CREATE TABLE mfa_challenges (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- SHA-256 digest of the token handed to the client
+    token_hash TEXT NOT NULL UNIQUE,
+    -- Number of wrong codes entered; the challenge is dropped after too many
+    attempts INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_mfa_challenges_user ON mfa_challenges(user_id);
@@ -0,0 +1,13 @@
+-- Create password_resets table: single-use tokens emailed to users who forgot their password
+CREATE TABLE password_resets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- SHA-256 digest of the token, the token itself is never stored
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+-- Index for invalidating a user's outstanding tokens
+CREATE INDEX idx_password_resets_user ON password_resets(user_id);
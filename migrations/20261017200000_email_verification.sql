@@ -0,0 +1,20 @@
+-- Track whether users confirmed their email address.
+-- Existing accounts predate verification and are trusted.
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMPTZ;
+UPDATE users SET email_verified_at = created_at;
+
+-- Create email_verifications table: single-use tokens confirming an address,
+-- either the one registered with or the one a user is changing to
+CREATE TABLE email_verifications (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Address being verified; replaces users.email once confirmed if it differs
+    email TEXT NOT NULL,
+    -- SHA-256 digest of the token, the token itself is never stored
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Index for replacing a user's outstanding tokens
+CREATE INDEX idx_email_verifications_user ON email_verifications(user_id);
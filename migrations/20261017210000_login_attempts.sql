@@ -0,0 +1,20 @@
+-- Create login_attempts table: audit log of logins, also used to back off
+-- password guessing per account and per IP address
+CREATE TABLE login_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    -- Set when the identifier matched an account
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    -- Lowercased username or email address the login was attempted with
+    identifier TEXT,
+    ip_address TEXT,
+    user_agent TEXT,
+    outcome TEXT NOT NULL CHECK (
+        outcome IN ('failed', 'locked', 'mfa_required', 'mfa_failed', 'succeeded', 'password_reset')
+    ),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Indexes for counting recent failures per account, identifier and IP address
+CREATE INDEX idx_login_attempts_user ON login_attempts(user_id, created_at);
+CREATE INDEX idx_login_attempts_identifier ON login_attempts(identifier, created_at);
+CREATE INDEX idx_login_attempts_ip ON login_attempts(ip_address, created_at);
@@ -0,0 +1,44 @@
+-- Create user_identities table: accounts at OpenID Connect providers linked to users
+CREATE TABLE user_identities (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Issuer URL of the provider; subjects are only unique per issuer
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    -- Email address the provider reported when the identity was last used
+    email TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_login_at TIMESTAMPTZ,
+    UNIQUE (issuer, subject)
+);
+
+-- Index for listing a user's identities
+CREATE INDEX idx_user_identities_user ON user_identities(user_id);
+
+-- Create oidc_logins table: logins waiting for the provider to redirect back
+CREATE TABLE oidc_logins (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- Configured provider ID
+    provider TEXT NOT NULL,
+    -- SHA-256 digest of the state, which is also kept in the browser's cookie
+    state_hash TEXT NOT NULL UNIQUE,
+    pkce_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    -- Set when a signed-in user links the identity to their account
+    link_user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Create oidc_signups table: new identities waiting for their user to choose a username
+CREATE TABLE oidc_signups (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT NOT NULL,
+    email_verified BOOLEAN NOT NULL,
+    -- SHA-256 digest of the token handed to the client
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
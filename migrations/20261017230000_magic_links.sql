@@ -0,0 +1,15 @@
+-- Create magic_links table: single-use passwordless login links emailed to users
+CREATE TABLE magic_links (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- SHA-256 digest of the token, the token itself is never stored
+    token_hash TEXT NOT NULL UNIQUE,
+    -- SHA-256 digest of the nonce kept in the requesting browser's cookie
+    nonce_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+-- Index for invalidating a user's outstanding links
+CREATE INDEX idx_magic_links_user ON magic_links(user_id);
@@ -0,0 +1,14 @@
+-- Chat codes expire, can be redeemed a limited number of times and carry an optional label.
+-- Existing codes get the default lifetime from now on.
+ALTER TABLE chat_codes
+ADD COLUMN label TEXT CHECK (char_length(label) <= 64),
+ADD COLUMN max_uses INTEGER NOT NULL DEFAULT 1 CHECK (max_uses > 0),
+ADD COLUMN uses INTEGER NOT NULL DEFAULT 0 CHECK (uses >= 0 AND uses <= max_uses),
+ADD COLUMN expires_at TIMESTAMPTZ NOT NULL DEFAULT NOW() + INTERVAL '24 hours';
+
+-- Index for the sweeper purging expired codes
+CREATE INDEX idx_chat_codes_expires_at ON chat_codes(expires_at);
+
+-- Tier of the user, selecting the limits that apply to them, such as how many chat codes they can have
+ALTER TABLE users
+ADD COLUMN tier TEXT NOT NULL DEFAULT 'standard';
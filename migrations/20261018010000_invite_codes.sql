@@ -0,0 +1,6 @@
+-- Chat codes are either a 5-digit number or a high-entropy Crockford base32 invite code
+ALTER TABLE chat_codes
+ALTER COLUMN code DROP NOT NULL,
+-- Canonical form: 15 symbols and the check symbol, without hyphens
+ADD COLUMN invite_code TEXT UNIQUE,
+ADD CONSTRAINT chat_codes_one_code CHECK ((code IS NULL) <> (invite_code IS NULL));
@@ -0,0 +1,43 @@
+-- Create chat_code_attempts table: audit log of chat code redemptions, also
+-- used to back off code guessing per user and per IP address
+CREATE TABLE chat_code_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Set when the code was found
+    chat_code_id BIGINT REFERENCES chat_codes(id) ON DELETE SET NULL,
+    ip_address TEXT,
+    user_agent TEXT,
+    outcome TEXT NOT NULL CHECK (
+        outcome IN ('failed', 'locked', 'flagged', 'redeemed', 'requested')
+    ),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Indexes for counting recent failures per user and IP address
+CREATE INDEX idx_chat_code_attempts_user ON chat_code_attempts(user_id, created_at);
+CREATE INDEX idx_chat_code_attempts_ip ON chat_code_attempts(ip_address, created_at);
+
+-- Codes requiring approval create chat requests instead of conversations
+ALTER TABLE chat_codes
+ADD COLUMN requires_approval BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Create chat_requests table: redemptions waiting for the code owner's approval
+CREATE TABLE chat_requests (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- Owner of the redeemed code, who accepts or declines the request
+    owner_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    requester_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Label of the redeemed code, kept once the code is spent or deleted
+    label TEXT,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'accepted', 'declined')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    decided_at TIMESTAMPTZ
+);
+
+-- Index for listing a user's requests
+CREATE INDEX idx_chat_requests_owner ON chat_requests(owner_id, status);
+CREATE INDEX idx_chat_requests_requester ON chat_requests(requester_id, status);
+
+-- A user can have a single pending request to another
+CREATE UNIQUE INDEX idx_chat_requests_pending ON chat_requests(owner_id, requester_id)
+WHERE status = 'pending';
@@ -0,0 +1,37 @@
+-- Sending a message already moves the sender's read marker, which the message
+-- notification implies: don't send a second, `read` notification for it
+CREATE OR REPLACE FUNCTION notify_conversation_read()
+RETURNS TRIGGER AS $$
+DECLARE
+    notification json;
+BEGIN
+    IF NEW.last_read_message_id IS NULL
+        OR (TG_OP = 'UPDATE' AND OLD.last_read_message_id IS NOT DISTINCT FROM NEW.last_read_message_id) THEN
+        RETURN NEW;
+    END IF;
+
+    -- Markers moved to the reader's own message, by sending it
+    IF EXISTS (
+        SELECT 1 FROM messages
+        WHERE id = NEW.last_read_message_id AND user_sent_id = NEW.user_id
+    ) THEN
+        RETURN NEW;
+    END IF;
+
+    notification = json_build_object(
+        'kind', 'read',
+        'conversation_id', NEW.conversation_id,
+        'user_id', NEW.user_id,
+        'username', (SELECT username FROM users WHERE id = NEW.user_id),
+        'message_id', NEW.last_read_message_id,
+        'read_at', NEW.last_read_at
+    );
+
+    PERFORM pg_notify(
+        'conversation_' || NEW.conversation_id::text,
+        notification::text
+    );
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,28 @@
+-- Conversations store their participants ordered by ID, so that a pair of
+-- users has a single row. Redeeming a code directly used to compare the IDs as
+-- text, which stored pairs such as (10, 9) reversed, and let accepting a chat
+-- request create a second conversation for them.
+
+-- Move the messages of reversed duplicates into the ordered conversation
+UPDATE messages m
+SET conversation_id = o.id
+FROM conversations r
+JOIN conversations o ON o.user_id_1 = r.user_id_2 AND o.user_id_2 = r.user_id_1
+WHERE m.conversation_id = r.id AND r.user_id_1 > r.user_id_2;
+
+UPDATE conversations o
+SET last_message_at = GREATEST(o.last_message_at, r.last_message_at)
+FROM conversations r
+WHERE o.user_id_1 = r.user_id_2 AND o.user_id_2 = r.user_id_1 AND r.user_id_1 > r.user_id_2;
+
+DELETE FROM conversations r
+USING conversations o
+WHERE o.user_id_1 = r.user_id_2 AND o.user_id_2 = r.user_id_1 AND r.user_id_1 > r.user_id_2;
+
+-- Put the remaining reversed pairs in order
+UPDATE conversations
+SET user_id_1 = user_id_2, user_id_2 = user_id_1
+WHERE user_id_1 > user_id_2;
+
+ALTER TABLE conversations
+ADD CONSTRAINT conversations_ordered_users CHECK (user_id_1 < user_id_2);
@@ -0,0 +1,233 @@
+//! Chat code redemption tracking and brute-force protection.
+//!
+//! Every chat code redemption is written to the `chat_code_attempts` audit
+//! log. Recent failures (unknown, expired, mistyped codes and invalid invite
+//! links) are counted from it to slow down code guessing:
+//! - per user, failures beyond the first few put redemptions on a cooldown
+//!   growing exponentially with every further failure
+//! - per IP address, too many failures in a short window block the address
+//!
+//! IP addresses are the client's as resolved by [`SessionMeta`], so that
+//! a forged `X-Forwarded-For` header can't spread failures over made-up
+//! addresses or pin them on someone else's.
+//!
+//! Users and IP addresses failing often enough to look like they are
+//! enumerating codes are flagged in the audit log, with a warning.
+
+use crate::sessions::SessionMeta;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::Response;
+use sqlx::{Connection, PgConnection};
+use time::{Duration, OffsetDateTime};
+use utils::errors::error_response;
+
+/// Failures allowed before redemptions go on a cooldown.
+const FREE_FAILURES: i64 = 5;
+
+/// Cooldown after the first failure beyond [`FREE_FAILURES`], doubled with every further failure.
+const BASE_COOLDOWN: Duration = Duration::minutes(1);
+
+/// Longest cooldown of a user.
+const MAX_COOLDOWN: Duration = Duration::hours(1);
+
+/// How long failures count towards a user's cooldown and enumeration flags.
+const FAILURE_WINDOW: Duration = Duration::hours(24);
+
+/// Window in which failures from an IP address are counted for blocking it.
+const IP_WINDOW: Duration = Duration::minutes(15);
+
+/// Failures from an IP address within [`IP_WINDOW`] after which it is blocked.
+const MAX_IP_FAILURES: i64 = 30;
+
+/// Failures of a user within [`FAILURE_WINDOW`] after which they are flagged.
+const ENUMERATION_USER_FAILURES: i64 = 20;
+
+/// Failures from an IP address within [`FAILURE_WINDOW`] after which it is flagged.
+const ENUMERATION_IP_FAILURES: i64 = 50;
+
+/// Outcome of a chat code redemption, as stored in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// Unknown, expired or mistyped code, or invalid invite link.
+    Failed,
+    /// Refused without looking the code up because of a cooldown.
+    Locked,
+    /// The user or IP address crossed an enumeration threshold.
+    Flagged,
+    /// A conversation was started.
+    Redeemed,
+    /// A chat request was sent to the code's owner for approval.
+    Requested,
+}
+
+impl Outcome {
+    /// Returns the value stored in the `outcome` column.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Failed => "failed",
+            Outcome::Locked => "locked",
+            Outcome::Flagged => "flagged",
+            Outcome::Redeemed => "redeemed",
+            Outcome::Requested => "requested",
+        }
+    }
+}
+
+/// Writes a redemption to the audit log.
+///
+/// Takes a connection so that it can run inside the caller's transaction.
+/// Failures to write are logged and otherwise ignored; the write is made in a
+/// savepoint so that a failure doesn't abort the caller's transaction.
+pub(crate) async fn record(
+    conn: &mut PgConnection,
+    user_id: i64,
+    chat_code_id: Option<i64>,
+    meta: &SessionMeta,
+    outcome: Outcome,
+) {
+    let result: Result<(), sqlx::Error> = async {
+        let mut savepoint = conn.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO chat_code_attempts (user_id, chat_code_id, ip_address, user_agent, outcome)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            user_id,
+            chat_code_id,
+            meta.ip_address(),
+            meta.user_agent(),
+            outcome.as_str()
+        )
+        .execute(&mut *savepoint)
+        .await?;
+        savepoint.commit().await
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!(error = ?e, outcome = outcome.as_str(), "Failed to record chat code attempt.");
+    }
+}
+
+/// Writes a failed redemption to the audit log, and flags the user or IP
+/// address once they cross an enumeration threshold.
+///
+/// A user or IP address is flagged at most once per [`FAILURE_WINDOW`].
+pub(crate) async fn record_failure(conn: &mut PgConnection, user_id: i64, meta: &SessionMeta) {
+    record(conn, user_id, None, meta, Outcome::Failed).await;
+
+    let failures = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM chat_code_attempts
+             WHERE user_id = $1 AND outcome = 'failed' AND created_at > $3) AS "user_failures!",
+            (SELECT COUNT(*) FROM chat_code_attempts
+             WHERE ip_address = $2 AND outcome = 'failed' AND created_at > $3) AS "ip_failures!",
+            EXISTS (SELECT 1 FROM chat_code_attempts
+                    WHERE (user_id = $1 OR ip_address = $2)
+                      AND outcome = 'flagged' AND created_at > $3) AS "flagged!"
+        "#,
+        user_id,
+        meta.ip_address(),
+        OffsetDateTime::now_utc() - FAILURE_WINDOW
+    )
+    .fetch_one(&mut *conn)
+    .await;
+
+    let failures = match failures {
+        Ok(failures) => failures,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to count failed chat code attempts.");
+            return;
+        }
+    };
+
+    if failures.flagged
+        || (failures.user_failures < ENUMERATION_USER_FAILURES
+            && failures.ip_failures < ENUMERATION_IP_FAILURES)
+    {
+        return;
+    }
+
+    tracing::warn!(
+        user_id,
+        ip_address = meta.ip_address(),
+        user_failures = failures.user_failures,
+        ip_failures = failures.ip_failures,
+        "Possible chat code enumeration"
+    );
+    record(conn, user_id, None, meta, Outcome::Flagged).await;
+}
+
+/// Returns how long the user, or the IP address, must wait before redeeming
+/// chat codes again because of too many recent failures.
+///
+/// Callers hold a lock on the user until the attempt is recorded, so that
+/// concurrent submissions see each other's failures.
+///
+/// # Returns
+///
+/// - `Ok(Some(Duration))` if redemptions are refused
+/// - `Ok(None)` if the code may be looked up
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn retry_after(
+    conn: &mut PgConnection,
+    user_id: i64,
+    meta: &SessionMeta,
+) -> Result<Option<Duration>, sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+
+    let failures = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM chat_code_attempts
+             WHERE user_id = $1 AND outcome = 'failed' AND created_at > $3) AS "user_count!",
+            (SELECT MAX(created_at) FROM chat_code_attempts
+             WHERE user_id = $1 AND outcome = 'failed' AND created_at > $3) AS user_latest,
+            (SELECT COUNT(*) FROM chat_code_attempts
+             WHERE ip_address = $2 AND outcome = 'failed' AND created_at > $4) AS "ip_count!",
+            (SELECT MIN(created_at) FROM chat_code_attempts
+             WHERE ip_address = $2 AND outcome = 'failed' AND created_at > $4) AS ip_oldest
+        "#,
+        user_id,
+        meta.ip_address(),
+        now - FAILURE_WINDOW,
+        now - IP_WINDOW
+    )
+    .fetch_one(conn)
+    .await?;
+
+    let user_retry_after = failures
+        .user_latest
+        .filter(|_| failures.user_count >= FREE_FAILURES)
+        .map(|latest| latest + cooldown(failures.user_count) - now);
+    let ip_retry_after = failures
+        .ip_oldest
+        .filter(|_| failures.ip_count >= MAX_IP_FAILURES)
+        .map(|oldest| oldest + IP_WINDOW - now);
+
+    Ok(user_retry_after
+        .into_iter()
+        .chain(ip_retry_after)
+        .max()
+        .filter(|retry_after| retry_after.is_positive()))
+}
+
+/// Returns the cooldown after the given number of failures.
+fn cooldown(failures: i64) -> Duration {
+    let doublings = (failures - FREE_FAILURES).clamp(0, 16) as u32;
+    (BASE_COOLDOWN * 2_i32.pow(doublings)).min(MAX_COOLDOWN)
+}
+
+/// Builds the `429 TOO MANY REQUESTS` response for a cooldown, with a `Retry-After` header.
+pub(crate) fn too_many_attempts(retry_after: Duration) -> Response {
+    let seconds = retry_after.whole_seconds().max(1);
+    let mut resp = error_response(
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("Too many invalid chat codes. Try again in {seconds} seconds."),
+    );
+    resp.headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(seconds));
+    resp
+}
@@ -0,0 +1,249 @@
+//! Chat code limits and expiry.
+//!
+//! Chat codes expire after a time to live chosen at creation, and can be
+//! redeemed a limited number of times. How many codes a user can have at once,
+//! and how long and how often they can be used, depend on the user's tier
+//! (the `tier` column of `users`), configured with `CHAT_CODE_TIERS`.
+//!
+//! Codes are either 5-digit numbers, easy to read out, or high-entropy invite
+//! codes (see [`utils::invites`]). Either can also be shared as a signed invite
+//! link when `INVITE_LINK_SECRET` is set.
+//!
+//! A background sweeper purges expired codes.
+
+use crate::setup::public_url;
+use api_types::chats::codes::ChatCode;
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use utils::invites::{format_invite_code, parse_invite_code, sign_invite_token};
+
+/// Tier of users whose tier isn't configured, when `CHAT_CODE_TIERS` is not set.
+const DEFAULT_TIER: &str = "standard";
+
+/// Default number of active codes a user can have at once.
+const DEFAULT_MAX_CODES: i64 = 5;
+
+/// Default maximum number of uses of a code.
+const DEFAULT_MAX_USES: i32 = 10;
+
+/// Default time to live of a code created without one.
+const DEFAULT_TTL: Duration = Duration::hours(24);
+
+/// Default maximum time to live of a code.
+const DEFAULT_MAX_TTL: Duration = Duration::days(7);
+
+/// Shortest time to live of a code.
+pub(crate) const MIN_TTL: Duration = Duration::minutes(1);
+
+/// How often expired codes are purged.
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Number of codes generated before giving up when they are all taken.
+pub(crate) const MAX_GENERATION_ATTEMPTS: usize = 5;
+
+/// The chat code limits of a user tier.
+#[derive(Debug, Clone)]
+pub(crate) struct ChatCodeTier {
+    /// Name of the tier, as stored in `users.tier`.
+    pub name: String,
+    /// Number of active codes a user can have at once.
+    pub max_codes: i64,
+    /// Maximum number of uses of a code.
+    pub max_uses: i32,
+    /// Time to live of a code created without one.
+    pub default_ttl: Duration,
+    /// Maximum time to live of a code.
+    pub max_ttl: Duration,
+}
+
+impl ChatCodeTier {
+    /// Reads the tier's limits from its `CHAT_CODE_TIER_<TIER>_*` environment
+    /// variables, each falling back to the default.
+    fn from_env(name: &str) -> Result<Self, String> {
+        let prefix = format!("CHAT_CODE_TIER_{}_", name.to_uppercase().replace('-', "_"));
+        fn number<T: std::str::FromStr + PartialOrd + Default>(
+            name: String,
+            default: T,
+        ) -> Result<T, String> {
+            match env::var(&name) {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|value| *value > T::default())
+                    .ok_or_else(|| format!("{name} must be a positive integer, got {value:?}")),
+                Err(_) => Ok(default),
+            }
+        }
+
+        let max_codes = number(format!("{prefix}MAX_CODES"), DEFAULT_MAX_CODES)?;
+        let max_uses = number(format!("{prefix}MAX_USES"), DEFAULT_MAX_USES)?;
+        let max_ttl = Duration::seconds(number(
+            format!("{prefix}MAX_TTL"),
+            DEFAULT_MAX_TTL.whole_seconds(),
+        )?);
+        let default_ttl = Duration::seconds(number(
+            format!("{prefix}DEFAULT_TTL"),
+            DEFAULT_TTL.whole_seconds().min(max_ttl.whole_seconds()),
+        )?);
+
+        if max_ttl < MIN_TTL || default_ttl < MIN_TTL || default_ttl > max_ttl {
+            return Err(format!(
+                "{prefix}DEFAULT_TTL and {prefix}MAX_TTL must be at least {} seconds, \
+                 and the default at most the maximum",
+                MIN_TTL.whole_seconds()
+            ));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            max_codes,
+            max_uses,
+            default_ttl,
+            max_ttl,
+        })
+    }
+}
+
+/// The chat code limits of every user tier.
+#[derive(Debug, Clone)]
+pub(crate) struct ChatCodePolicies(Arc<Vec<ChatCodeTier>>);
+
+impl ChatCodePolicies {
+    /// Reads the tiers from the `CHAT_CODE_TIERS` environment variable, a comma
+    /// separated list of tier names, and their `CHAT_CODE_TIER_<TIER>_*` limits.
+    ///
+    /// Without `CHAT_CODE_TIERS`, every user gets the `standard` tier.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(ChatCodePolicies)` with the configured tiers
+    /// - `Err(String)` describing the invalid configuration
+    pub(crate) fn from_env() -> Result<Self, String> {
+        let names = env::var("CHAT_CODE_TIERS").unwrap_or_else(|_| DEFAULT_TIER.into());
+        let tiers = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(ChatCodeTier::from_env)
+            .collect::<Result<Vec<_>, _>>()?;
+        if tiers.is_empty() {
+            return Err("CHAT_CODE_TIERS doesn't list any tier".to_string());
+        }
+        Ok(Self(Arc::new(tiers)))
+    }
+
+    /// Returns the limits of a tier, or of the first configured tier for users
+    /// whose tier isn't configured.
+    pub(crate) fn tier(&self, name: &str) -> &ChatCodeTier {
+        self.0
+            .iter()
+            .find(|tier| tier.name == name)
+            .unwrap_or_else(|| {
+                tracing::warn!(
+                    tier = name,
+                    "Unknown user tier, using the default chat code limits"
+                );
+                &self.0[0]
+            })
+    }
+
+    /// Returns the limits of the user's tier.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(&ChatCodeTier)` with the limits
+    /// - `Err(sqlx::Error)` if the database operation fails
+    pub(crate) async fn for_user(
+        &self,
+        pool: &PgPool,
+        user_id: i64,
+    ) -> Result<&ChatCodeTier, sqlx::Error> {
+        let tier = sqlx::query_scalar!("SELECT tier FROM users WHERE id = $1", user_id)
+            .fetch_one(pool)
+            .await?;
+        Ok(self.tier(&tier))
+    }
+}
+
+/// A chat code as stored in `chat_codes`: exactly one of the columns is set.
+#[derive(Debug, Clone)]
+pub(crate) struct StoredCode {
+    /// The 5-digit numeric code.
+    pub code: Option<i32>,
+    /// The invite code, in canonical form.
+    pub invite_code: Option<String>,
+}
+
+impl StoredCode {
+    /// Parses a code sent by a client.
+    ///
+    /// # Returns
+    ///
+    /// - `Some(StoredCode)` with the column to look the code up by
+    /// - `None` if an invite code is malformed or its check symbol doesn't match
+    pub(crate) fn parse(code: &ChatCode) -> Option<Self> {
+        match code {
+            ChatCode::Numeric(code) => Some(Self {
+                code: Some(*code as i32),
+                invite_code: None,
+            }),
+            ChatCode::Invite(code) => Some(Self {
+                code: None,
+                invite_code: Some(parse_invite_code(code)?),
+            }),
+        }
+    }
+
+    /// Returns the code as shown to clients, invite codes grouped with hyphens.
+    pub(crate) fn to_api(&self) -> ChatCode {
+        match (&self.code, &self.invite_code) {
+            (_, Some(invite_code)) => ChatCode::Invite(format_invite_code(invite_code)),
+            (code, None) => ChatCode::Numeric(code.unwrap_or_default() as u16),
+        }
+    }
+}
+
+/// Builds the shareable invite link of a chat code.
+///
+/// # Returns
+///
+/// - `Some(String)` with the link, valid until the code expires
+/// - `None` if invite links are disabled
+pub(crate) fn invite_url(code_id: i64, expires_at: OffsetDateTime) -> Option<String> {
+    let token = sign_invite_token(code_id, expires_at)?;
+    Some(format!("{}/invite?token={}", public_url(), token))
+}
+
+/// Deletes expired chat codes.
+///
+/// # Returns
+///
+/// - `Ok(u64)` with the number of deleted codes
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn purge_expired(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM chat_codes WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Starts the background task purging expired chat codes every [`SWEEP_INTERVAL`].
+///
+/// Expired codes are ignored everywhere, so the sweeper only keeps the table
+/// small and frees their numbers for new codes.
+pub(crate) fn spawn_sweeper(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_expired(&pool).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "Purged expired chat codes"),
+                Err(e) => tracing::error!(error = ?e, "Failed to purge expired chat codes"),
+            }
+        }
+    });
+}
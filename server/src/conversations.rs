@@ -0,0 +1,62 @@
+//! Conversation lookup and creation.
+//!
+//! A conversation stores its two participants ordered by ID, so that a pair of
+//! users has a single row. Every query goes through these helpers so that the
+//! pair is always ordered the same way.
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Finds the conversation between two users.
+///
+/// # Returns
+///
+/// - `Ok(Some(Uuid))` with the conversation ID if they have one
+/// - `Ok(None)` if they don't
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn find_conversation(
+    conn: &mut PgConnection,
+    user_id: i64,
+    other_user_id: i64,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT id FROM conversations
+        WHERE user_id_1 = LEAST($1::BIGINT, $2::BIGINT)
+          AND user_id_2 = GREATEST($1::BIGINT, $2::BIGINT)
+        "#,
+        user_id,
+        other_user_id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Creates the conversation between two users, unless they already have one.
+///
+/// # Returns
+///
+/// - `Ok(Some(Uuid))` with the ID of the created conversation
+/// - `Ok(None)` if the users already have a conversation
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn create_conversation(
+    conn: &mut PgConnection,
+    user_id: i64,
+    other_user_id: i64,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO conversations (user_id_1, user_id_2)
+        VALUES (LEAST($1::BIGINT, $2::BIGINT), GREATEST($1::BIGINT, $2::BIGINT))
+        ON CONFLICT (user_id_1, user_id_2) DO NOTHING
+        RETURNING id
+        "#,
+        user_id,
+        other_user_id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+#[cfg(test)]
+mod tests;
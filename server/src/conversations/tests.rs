@@ -0,0 +1,154 @@
+//! Conversations started by redeeming codes, directly and through approval,
+//! between users whose IDs have a different number of digits.
+
+use crate::routes::chats::post::api_chats_post;
+use crate::routes::chats::requests::post::api_chats_requests_post;
+use crate::verification::UnverifiedLimits;
+use api_types::chats::codes::ChatCode;
+use api_types::chats::codes::post::ApiChatsCodesPostRequest;
+use api_types::chats::requests::post::ApiChatsRequestsPostRequest;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use middleware::Access;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+/// Owner of the codes, whose ID sorts after the redeemer's when compared as text.
+const OWNER: i64 = 9;
+
+/// User redeeming the codes.
+const REDEEMER: i64 = 10;
+
+/// Creates a verified user with the given ID.
+async fn create_user(pool: &PgPool, id: i64) {
+    sqlx::query!(
+        r#"
+        INSERT INTO users (id, username, email, password_hash, email_verified_at)
+        VALUES ($1, $2, $3, 'unused', NOW())
+        "#,
+        id,
+        format!("user{id}"),
+        format!("user{id}@example.com")
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// Creates a numeric chat code owned by [`OWNER`].
+async fn create_code(pool: &PgPool, code: i32, requires_approval: bool) {
+    sqlx::query!(
+        "INSERT INTO chat_codes (code, user_id, requires_approval) VALUES ($1, $2, $3)",
+        code,
+        OWNER,
+        requires_approval
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// Redeems a code as [`REDEEMER`].
+async fn redeem(pool: &PgPool, code: u16) -> Response {
+    api_chats_post(
+        Extension(REDEEMER),
+        Extension(Access::Session),
+        State(pool.clone()),
+        State(UnverifiedLimits::from_env().unwrap()),
+        ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))),
+        HeaderMap::new(),
+        Json(ApiChatsCodesPostRequest {
+            code: Some(ChatCode::Numeric(code)),
+            token: None,
+        }),
+    )
+    .await
+    .into_response()
+}
+
+/// Accepts a chat request as [`OWNER`].
+async fn accept(pool: &PgPool, request_id: Uuid) -> Response {
+    api_chats_requests_post(
+        Extension(OWNER),
+        Extension(Access::Session),
+        State(pool.clone()),
+        Json(ApiChatsRequestsPostRequest {
+            request_id,
+            accept: true,
+        }),
+    )
+    .await
+    .into_response()
+}
+
+/// Reads a JSON response body.
+async fn json(resp: Response) -> serde_json::Value {
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+/// Returns the participants of every conversation.
+async fn conversations(pool: &PgPool) -> Vec<(i64, i64)> {
+    sqlx::query!("SELECT user_id_1, user_id_2 FROM conversations")
+        .fetch_all(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.user_id_1, row.user_id_2))
+        .collect()
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn approval_finds_conversation_started_directly(pool: PgPool) {
+    create_user(&pool, OWNER).await;
+    create_user(&pool, REDEEMER).await;
+    create_code(&pool, 10001, false).await;
+    create_code(&pool, 10002, true).await;
+
+    assert_eq!(redeem(&pool, 10001).await.status(), StatusCode::CREATED);
+    assert_eq!(redeem(&pool, 10002).await.status(), StatusCode::CONFLICT);
+    assert_eq!(conversations(&pool).await, vec![(OWNER, REDEEMER)]);
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn direct_redemption_finds_conversation_started_through_approval(pool: PgPool) {
+    create_user(&pool, OWNER).await;
+    create_user(&pool, REDEEMER).await;
+    create_code(&pool, 10001, true).await;
+    create_code(&pool, 10002, false).await;
+
+    let resp = redeem(&pool, 10001).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let request_id = json(resp).await["request_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(
+        accept(&pool, request_id).await.status(),
+        StatusCode::CREATED
+    );
+
+    assert_eq!(redeem(&pool, 10002).await.status(), StatusCode::CONFLICT);
+    assert_eq!(conversations(&pool).await, vec![(OWNER, REDEEMER)]);
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn rejects_conversations_out_of_order(pool: PgPool) {
+    create_user(&pool, OWNER).await;
+    create_user(&pool, REDEEMER).await;
+
+    let result = sqlx::query!(
+        "INSERT INTO conversations (user_id_1, user_id_2) VALUES ($1, $2)",
+        REDEEMER,
+        OWNER
+    )
+    .execute(&pool)
+    .await;
+    assert!(result.is_err());
+}
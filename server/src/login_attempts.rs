@@ -0,0 +1,257 @@
+//! Login attempt tracking and brute-force protection.
+//!
+//! Every login attempt is written to the `login_attempts` audit log. Recent
+//! failures, wrong passwords as well as wrong second factors, are counted from
+//! it to slow down guessing:
+//! - per account, failures beyond the first few lock the account out for an
+//!   exponentially growing time, until a successful login
+//! - per IP address, too many failures in a short window block the address
+//!
+//! Failures are counted per identifier (the username or email address typed
+//! in) as well as per account, so that unknown identifiers are locked out
+//! exactly like existing accounts and the responses don't reveal which exist.
+
+use crate::sessions::SessionMeta;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::Response;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use utils::errors::error_response;
+
+/// Failures allowed before an account is locked out.
+const FREE_FAILURES: i64 = 5;
+
+/// Lockout after the first failure beyond [`FREE_FAILURES`], doubled with every further failure.
+const BASE_LOCKOUT: Duration = Duration::minutes(1);
+
+/// Longest lockout of an account.
+const MAX_LOCKOUT: Duration = Duration::hours(1);
+
+/// How long failures count towards an account's lockout.
+const FAILURE_WINDOW: Duration = Duration::hours(24);
+
+/// Window in which failures from an IP address are counted.
+const IP_WINDOW: Duration = Duration::minutes(15);
+
+/// Failures from an IP address within [`IP_WINDOW`] after which it is blocked.
+const MAX_IP_FAILURES: i64 = 30;
+
+/// Outcome of a login attempt, as stored in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// Unknown identifier or wrong password.
+    Failed,
+    /// Refused without checking the password because of a lockout.
+    Locked,
+    /// Correct password, waiting for the second factor.
+    MfaRequired,
+    /// Wrong second factor.
+    MfaFailed,
+    /// A session was issued.
+    Succeeded,
+    /// The password was reset, which lifts the lockout.
+    PasswordReset,
+}
+
+impl Outcome {
+    /// Returns the value stored in the `outcome` column.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Failed => "failed",
+            Outcome::Locked => "locked",
+            Outcome::MfaRequired => "mfa_required",
+            Outcome::MfaFailed => "mfa_failed",
+            Outcome::Succeeded => "succeeded",
+            Outcome::PasswordReset => "password_reset",
+        }
+    }
+}
+
+/// Normalizes the username or email address a login was attempted with.
+pub(crate) fn normalize_identifier(person: &str) -> String {
+    person.trim().to_lowercase()
+}
+
+/// Writes a login attempt to the audit log.
+///
+/// Failures to write are logged and otherwise ignored, so that logins keep
+/// working if the audit log can't be written.
+pub(crate) async fn record(
+    pool: &PgPool,
+    user_id: Option<i64>,
+    identifier: Option<&str>,
+    meta: &SessionMeta,
+    outcome: Outcome,
+) {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO login_attempts (user_id, identifier, ip_address, user_agent, outcome)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        identifier,
+        meta.ip_address(),
+        meta.user_agent(),
+        outcome.as_str()
+    )
+    .execute(pool)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!(error = ?e, outcome = outcome.as_str(), "Failed to record login attempt.");
+    }
+}
+
+/// Returns how long the IP address is blocked for because of too many recent failures.
+///
+/// # Returns
+///
+/// - `Ok(Some(Duration))` if the address is blocked
+/// - `Ok(None)` if it may log in
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn ip_retry_after(
+    pool: &PgPool,
+    ip_address: Option<&str>,
+) -> Result<Option<Duration>, sqlx::Error> {
+    let Some(ip_address) = ip_address else {
+        return Ok(None);
+    };
+    let now = OffsetDateTime::now_utc();
+
+    let failures = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!", MIN(created_at) AS oldest
+        FROM login_attempts
+        WHERE ip_address = $1 AND outcome IN ('failed', 'mfa_failed') AND created_at > $2
+        "#,
+        ip_address,
+        now - IP_WINDOW
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if failures.count < MAX_IP_FAILURES {
+        return Ok(None);
+    }
+    Ok(failures
+        .oldest
+        .map(|oldest| oldest + IP_WINDOW - now)
+        .filter(|retry_after| retry_after.is_positive()))
+}
+
+/// Returns how long logins with the identifier, or into the account, are locked out for.
+///
+/// Wrong passwords and wrong second factors are counted since the last
+/// successful login or password reset of the account, and within
+/// [`FAILURE_WINDOW`]. A correct password alone doesn't lift the lockout, so
+/// that the second factor can't be guessed by logging in again and again.
+///
+/// # Returns
+///
+/// - `Ok(Some(Duration))` if logins are locked out
+/// - `Ok(None)` if the password may be checked
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn account_retry_after(
+    pool: &PgPool,
+    identifier: &str,
+    user_id: Option<i64>,
+) -> Result<Option<Duration>, sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+
+    let failures = sqlx::query!(
+        r#"
+        WITH last_reset AS (
+            SELECT MAX(created_at) AS at
+            FROM login_attempts
+            WHERE user_id = $2 AND outcome IN ('succeeded', 'password_reset')
+        )
+        SELECT COUNT(*) AS "count!", MAX(la.created_at) AS latest
+        FROM login_attempts la, last_reset
+        WHERE la.outcome IN ('failed', 'mfa_failed')
+          AND (la.identifier = $1 OR la.user_id = $2)
+          AND la.created_at > $3
+          AND la.created_at > COALESCE(last_reset.at, '-infinity')
+        "#,
+        identifier,
+        user_id,
+        now - FAILURE_WINDOW
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if failures.count < FREE_FAILURES {
+        return Ok(None);
+    }
+    Ok(failures
+        .latest
+        .map(|latest| latest + lockout(failures.count) - now)
+        .filter(|retry_after| retry_after.is_positive()))
+}
+
+/// Returns the lockout after the given number of consecutive failures.
+fn lockout(failures: i64) -> Duration {
+    let doublings = (failures - FREE_FAILURES).clamp(0, 16) as u32;
+    (BASE_LOCKOUT * 2_i32.pow(doublings)).min(MAX_LOCKOUT)
+}
+
+/// Failed attempts to log into an account since its last successful login.
+pub(crate) struct FailedAttempts {
+    /// Number of failed attempts.
+    pub count: i64,
+    /// When the latest one happened.
+    pub last_at: OffsetDateTime,
+    /// IP address of the latest one.
+    pub last_ip_address: Option<String>,
+}
+
+/// Returns the failed attempts to log into the account since its last successful login.
+///
+/// Must be called before the current login is recorded as successful.
+///
+/// # Returns
+///
+/// - `Ok(Some(FailedAttempts))` if there were any
+/// - `Ok(None)` otherwise
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn failed_since_last_login(
+    pool: &PgPool,
+    user_id: i64,
+) -> Result<Option<FailedAttempts>, sqlx::Error> {
+    let attempts = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!",
+               MAX(created_at) AS last_at,
+               (ARRAY_AGG(ip_address ORDER BY created_at DESC))[1] AS last_ip_address
+        FROM login_attempts
+        WHERE user_id = $1
+          AND outcome IN ('failed', 'locked', 'mfa_failed')
+          AND created_at > COALESCE(
+              (SELECT MAX(created_at) FROM login_attempts
+               WHERE user_id = $1 AND outcome = 'succeeded'),
+              '-infinity'
+          )
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(attempts.last_at.map(|last_at| FailedAttempts {
+        count: attempts.count,
+        last_at,
+        last_ip_address: attempts.last_ip_address,
+    }))
+}
+
+/// Builds the `429 TOO MANY REQUESTS` response for a lockout, with a `Retry-After` header.
+pub(crate) fn too_many_attempts(retry_after: Duration) -> Response {
+    let seconds = retry_after.whole_seconds().max(1);
+    let mut resp = error_response(
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("Too many failed login attempts. Try again in {seconds} seconds."),
+    );
+    resp.headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(seconds));
+    resp
+}
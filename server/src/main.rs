@@ -4,35 +4,105 @@ mod routes;
 /// Setup utilities for logging and database connections.
 mod setup;
 
+/// Chat code redemption tracking and brute-force protection.
+mod chat_code_attempts;
+
+/// Chat code limits per user tier, and the sweeper purging expired codes.
+mod chat_codes;
+
+/// Conversation lookup and creation, with their participants in order.
+mod conversations;
+
+/// Login attempt tracking and brute-force protection.
+mod login_attempts;
+
+/// Outgoing email delivery.
+mod mailer;
+
+/// Shared PostgreSQL LISTEN/NOTIFY hub for real-time delivery.
+mod notifications;
+
+/// OpenID Connect single sign-on.
+mod oidc;
+
+/// Online presence tracking for WebSocket users.
+mod presence;
+
+/// Session issuance, refresh and revocation.
+mod sessions;
+
+/// Shared application state.
+mod state;
+
+/// Two-factor authentication with TOTP codes and recovery codes.
+mod two_factor;
+
+/// Email address verification.
+mod verification;
+
+use crate::notifications::NotificationHub;
+use crate::presence::Presence;
 use crate::routes::auth::login::api_auth_login_post;
+use crate::routes::auth::logout::api_auth_logout_post;
+use crate::routes::auth::magic_link::consume::api_auth_magic_link_consume_post;
+use crate::routes::auth::magic_link::send::api_auth_magic_link_post;
+use crate::routes::auth::oidc::callback::api_auth_oidc_callback_get;
+use crate::routes::auth::oidc::providers::api_auth_oidc_providers_get;
+use crate::routes::auth::oidc::signup::api_auth_oidc_signup_post;
+use crate::routes::auth::oidc::start::api_auth_oidc_start_get;
+use crate::routes::auth::password::forgot::api_auth_password_forgot_post;
+use crate::routes::auth::password::reset::api_auth_password_reset_post;
+use crate::routes::auth::refresh::api_auth_refresh_post;
 use crate::routes::auth::register::api_auth_register_post;
+use crate::routes::auth::sessions::delete::api_auth_sessions_delete;
+use crate::routes::auth::sessions::get::api_auth_sessions_get;
+use crate::routes::auth::two_factor::api_auth_two_factor_post;
+use crate::routes::auth::verify_email::api_auth_verify_email_post;
 use crate::routes::chats::codes::delete::api_chats_codes_delete;
+use crate::routes::chats::codes::get::api_chats_codes_get;
 use crate::routes::chats::codes::post::api_chats_codes_post;
+use crate::routes::chats::codes::qr::api_chats_codes_qr_get;
+use crate::routes::chats::get::api_chats_get;
 use crate::routes::chats::messages::delete::api_chats_messages_delete;
 use crate::routes::chats::messages::get::api_chats_messages_get;
 use crate::routes::chats::messages::patch::api_chats_messages_patch;
 use crate::routes::chats::post::api_chats_post;
+use crate::routes::chats::reads::post::api_chats_reads_post;
+use crate::routes::chats::requests::get::api_chats_requests_get;
+use crate::routes::chats::requests::post::api_chats_requests_post;
 use crate::routes::chats::ws::api_chats_ws;
 use crate::routes::users::get::api_users_get;
+use crate::routes::users::identities::delete::api_users_identities_delete;
+use crate::routes::users::identities::get::api_users_identities_get;
+use crate::routes::users::identities::post::api_users_identities_post;
+use crate::routes::users::keys::delete::api_users_keys_delete;
+use crate::routes::users::keys::get::api_users_keys_get;
+use crate::routes::users::keys::post::api_users_keys_post;
 use crate::routes::users::patch::api_users_patch;
+use crate::routes::users::two_factor::confirm::api_users_two_factor_confirm_post;
+use crate::routes::users::two_factor::delete::api_users_two_factor_delete;
+use crate::routes::users::two_factor::post::api_users_two_factor_post;
+use crate::routes::users::verify_email::api_users_verify_email_post;
+use crate::routes::well_known::jwks::well_known_jwks_get;
 use crate::setup::{init_logging, setup_db};
+use crate::state::AppState;
 use ::middleware::auth_middleware;
 use axum::middleware;
 use axum::routing::{any, post};
 use axum::{Router, routing::get};
-use sqlx::PgPool;
 use std::env;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tower_governor::GovernorLayer;
 use tower_governor::governor::GovernorConfigBuilder;
-use tower_governor::key_extractor::{KeyExtractor, SmartIpKeyExtractor};
+use tower_governor::key_extractor::{KeyExtractor, PeerIpKeyExtractor};
+use utils::proxies::client_ip;
 
 #[tokio::main]
 async fn main() {
     init_logging();
     #[cfg(debug_assertions)]
-    if let Err(_) = dotenvy::dotenv() {
+    if dotenvy::dotenv().is_err() {
         tracing::warn!("Failed to load .env file. Continuing without it.");
     }
 
@@ -48,15 +118,97 @@ async fn main() {
         std::process::exit(1);
     }
 
-    if let Err(e) = env::var("JWT_SECRET_KEY") {
-        println!(
-            "An error occurred while trying to retrive JWT_SECRET_KEY env variable: {}",
-            e
-        );
+    if let Err(e) = utils::jwt::init_keyring() {
+        tracing::error!(error = %e, "Invalid JWT key configuration. Exiting.");
+        std::process::exit(1);
+    }
+
+    if let Err(e) = utils::hashing::init_password_hashing() {
+        tracing::error!(error = %e, "Invalid password hashing configuration. Exiting.");
+        std::process::exit(1);
+    }
+
+    if let Err(e) = utils::encryption::init_totp_encryption() {
+        tracing::error!(error = %e, "Invalid TOTP encryption configuration. Exiting.");
         std::process::exit(1);
     }
 
-    let app = create_router(setup_db().await).into_make_service_with_connect_info::<SocketAddr>();
+    if let Err(e) = utils::password_policy::init_password_policy() {
+        tracing::error!(error = %e, "Invalid password policy configuration. Exiting.");
+        std::process::exit(1);
+    }
+
+    if let Err(e) = utils::invites::init_invite_links() {
+        tracing::error!(error = %e, "Invalid invite link configuration. Exiting.");
+        std::process::exit(1);
+    }
+
+    if let Err(e) = utils::proxies::init_trusted_proxies() {
+        tracing::error!(error = %e, "Invalid trusted proxy configuration. Exiting.");
+        std::process::exit(1);
+    }
+
+    let mailer = match mailer::from_env() {
+        Ok(mailer) => mailer,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to configure the mailer. Exiting.");
+            std::process::exit(1);
+        }
+    };
+
+    let unverified_limits = match verification::UnverifiedLimits::from_env() {
+        Ok(limits) => limits,
+        Err(e) => {
+            tracing::error!(error = %e, "Invalid UNVERIFIED_LIMITS. Exiting.");
+            std::process::exit(1);
+        }
+    };
+
+    let oidc = match oidc::OidcProviders::from_env() {
+        Ok(providers) => providers,
+        Err(e) => {
+            tracing::error!(error = %e, "Invalid OpenID Connect configuration. Exiting.");
+            std::process::exit(1);
+        }
+    };
+
+    let chat_code_policies = match chat_codes::ChatCodePolicies::from_env() {
+        Ok(policies) => policies,
+        Err(e) => {
+            tracing::error!(error = %e, "Invalid chat code tier configuration. Exiting.");
+            std::process::exit(1);
+        }
+    };
+
+    let pool = setup_db().await;
+    match two_factor::encrypt_stored_secrets(&pool).await {
+        Ok(0) => {}
+        Ok(encrypted) => tracing::info!(encrypted, "Encrypted stored TOTP secrets"),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to encrypt stored TOTP secrets. Exiting.");
+            std::process::exit(1);
+        }
+    }
+    chat_codes::spawn_sweeper(pool.clone());
+    sessions::spawn_sweeper(pool.clone());
+    let hub = match NotificationHub::start(&pool).await {
+        Ok(hub) => hub,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to start the notification hub. Exiting.");
+            std::process::exit(1);
+        }
+    };
+
+    let app = create_router(AppState {
+        pool,
+        hub,
+        presence: Presence::default(),
+        mailer,
+        unverified_limits,
+        oidc,
+        chat_code_policies,
+    })
+    .into_make_service_with_connect_info::<SocketAddr>();
 
     let listener = match tokio::net::TcpListener::bind(&addr).await {
         Ok(listener) => listener,
@@ -77,7 +229,7 @@ async fn main() {
 }
 
 #[inline(always)]
-fn create_router(pool: PgPool) -> Router {
+fn create_router(state: AppState) -> Router {
     let mut rate_limit_config = GovernorConfigBuilder::default();
     rate_limit_config.per_second(1).burst_size(20);
 
@@ -91,41 +243,119 @@ fn create_router(pool: PgPool) -> Router {
     // Health check route (no auth required)
     let health_routes = Router::new().route("/api/health", get(|| async { "ok :)" }));
 
+    // Published signing keys (no auth required)
+    let well_known_routes = Router::new().route("/.well-known/jwks.json", get(well_known_jwks_get));
+
     // Authentication routes (no auth required)
     let auth_routes = Router::new()
         .route("/api/auth/register", post(api_auth_register_post))
-        .route("/api/auth/login", post(api_auth_login_post));
+        .route("/api/auth/login", post(api_auth_login_post))
+        .route("/api/auth/login/2fa", post(api_auth_two_factor_post))
+        .route("/api/auth/email/verify", post(api_auth_verify_email_post))
+        .route("/api/auth/refresh", post(api_auth_refresh_post))
+        .route("/api/auth/magic-link", post(api_auth_magic_link_post))
+        .route(
+            "/api/auth/magic-link/consume",
+            post(api_auth_magic_link_consume_post),
+        )
+        .route("/api/auth/oidc/providers", get(api_auth_oidc_providers_get))
+        .route(
+            "/api/auth/oidc/{provider}/start",
+            get(api_auth_oidc_start_get),
+        )
+        .route(
+            "/api/auth/oidc/{provider}/callback",
+            get(api_auth_oidc_callback_get),
+        )
+        .route("/api/auth/oidc/signup", post(api_auth_oidc_signup_post))
+        .route(
+            "/api/auth/password/forgot",
+            post(api_auth_password_forgot_post),
+        )
+        .route(
+            "/api/auth/password/reset",
+            post(api_auth_password_reset_post),
+        );
+
+    // Protected authentication routes (auth required)
+    let protected_auth_routes = Router::new()
+        .route("/api/auth/logout", post(api_auth_logout_post))
+        .route(
+            "/api/auth/sessions",
+            get(api_auth_sessions_get).delete(api_auth_sessions_delete),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.pool.clone(),
+            auth_middleware,
+        ));
 
     // Protected user routes (auth required)
     let protected_users_routes = Router::new()
         .route("/api/users", get(api_users_get).patch(api_users_patch))
-        .layer(middleware::from_fn(auth_middleware));
+        .route(
+            "/api/users/keys",
+            get(api_users_keys_get)
+                .post(api_users_keys_post)
+                .delete(api_users_keys_delete),
+        )
+        .route(
+            "/api/users/2fa",
+            post(api_users_two_factor_post).delete(api_users_two_factor_delete),
+        )
+        .route(
+            "/api/users/2fa/confirm",
+            post(api_users_two_factor_confirm_post),
+        )
+        .route("/api/users/email/verify", post(api_users_verify_email_post))
+        .route(
+            "/api/users/identities",
+            get(api_users_identities_get)
+                .post(api_users_identities_post)
+                .delete(api_users_identities_delete),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.pool.clone(),
+            auth_middleware,
+        ));
 
     // Protected chat routes (auth required)
     let protected_chat_routes = Router::new()
         .route(
             "/api/chats",
-            post(api_chats_post), // Submit the chat code
+            get(api_chats_get).post(api_chats_post), // List conversations / submit the chat code
         )
         .route(
             "/api/chats/codes",
-            post(api_chats_codes_post).delete(api_chats_codes_delete),
+            get(api_chats_codes_get)
+                .post(api_chats_codes_post)
+                .delete(api_chats_codes_delete),
         )
+        .route("/api/chats/codes/{code}/qr", get(api_chats_codes_qr_get))
         .route(
             "/api/chats/messages",
             get(api_chats_messages_get)
                 .delete(api_chats_messages_delete)
                 .patch(api_chats_messages_patch),
         )
+        .route("/api/chats/reads", post(api_chats_reads_post))
+        .route(
+            "/api/chats/requests",
+            get(api_chats_requests_get).post(api_chats_requests_post),
+        )
         .route("/api/chats/ws", any(api_chats_ws))
-        .layer(middleware::from_fn(auth_middleware));
+        .layer(middleware::from_fn_with_state(
+            state.pool.clone(),
+            auth_middleware,
+        ));
 
     Router::new()
         .merge(health_routes)
+        .merge(well_known_routes)
         .merge(auth_routes)
+        .merge(protected_auth_routes)
         .merge(protected_users_routes)
         .merge(protected_chat_routes)
-        .with_state(pool)
+        .with_state(state)
         .layer(rate_limit_layer)
 }
 
@@ -149,7 +379,8 @@ impl KeyExtractor for IpRouteKeyExtractor {
         &self,
         req: &axum::http::Request<T>,
     ) -> Result<Self::Key, tower_governor::GovernorError> {
-        let ip = SmartIpKeyExtractor.extract(req)?;
+        let peer = PeerIpKeyExtractor.extract(req)?;
+        let ip = client_ip(req.headers(), peer);
         Ok(IpRouteKey {
             ip,
             path: req.uri().path().to_owned(),
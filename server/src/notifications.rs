@@ -0,0 +1,369 @@
+//! Process-wide PostgreSQL notification hub.
+//!
+//! A single [`PgListener`] connection is shared by every WebSocket. Sockets
+//! register a [`Subscriber`] and subscribe it to channels; the hub issues
+//! `LISTEN` for a channel while at least one subscriber needs it, and fans
+//! each notification out to the subscribers' bounded queues.
+//!
+//! A subscriber whose queue is full is dropped from the hub, which closes its
+//! receiver so that the owning socket can disconnect instead of stalling
+//! delivery for everyone else.
+
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+
+/// Number of notifications buffered per subscriber before it is considered too slow.
+const SUBSCRIBER_CAPACITY: usize = 64;
+
+/// A notification received on one of the subscribed channels.
+#[derive(Clone, Debug)]
+pub(crate) struct Notification {
+    /// Channel the notification was sent to.
+    pub channel: Arc<str>,
+    /// Raw payload of the notification.
+    pub payload: Arc<str>,
+}
+
+/// Handle to the shared notification hub. Cheap to clone.
+#[derive(Clone)]
+pub(crate) struct NotificationHub {
+    inner: Arc<HubInner>,
+}
+
+struct HubInner {
+    registry: Mutex<Registry>,
+    commands: mpsc::UnboundedSender<Command>,
+    next_id: AtomicU64,
+}
+
+/// Bookkeeping of which subscriber listens to which channel.
+#[derive(Default)]
+struct Registry {
+    subscribers: HashMap<u64, SubscriberEntry>,
+    channels: HashMap<String, HashSet<u64>>,
+}
+
+struct SubscriberEntry {
+    sender: mpsc::Sender<Notification>,
+    channels: HashSet<String>,
+}
+
+/// Errors returned when subscribing to a channel.
+#[derive(Debug)]
+pub(crate) enum SubscribeError {
+    /// The subscriber was dropped for falling behind, or the hub has shut down.
+    Closed,
+    /// The shared listener failed to `LISTEN` to the channel.
+    Listen(sqlx::Error),
+}
+
+impl std::fmt::Display for SubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubscribeError::Closed => write!(f, "subscriber is closed"),
+            SubscribeError::Listen(e) => write!(f, "failed to listen to channel: {}", e),
+        }
+    }
+}
+
+/// Requests handled by the listener task.
+enum Command {
+    /// Make sure the channel is listened to, then acknowledge.
+    Listen(String, oneshot::Sender<Result<(), sqlx::Error>>),
+    /// Stop listening to the channel if nobody subscribes to it anymore.
+    Unlisten(String),
+    /// Dispatch every notification sent so far, then acknowledge.
+    Sync(oneshot::Sender<()>),
+}
+
+impl NotificationHub {
+    /// Connects the shared listener and spawns the task driving it.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(NotificationHub)` once the listener connection is established
+    /// - `Err(sqlx::Error)` if the listener could not connect
+    pub(crate) async fn start(pool: &PgPool) -> Result<Self, sqlx::Error> {
+        let listener = PgListener::connect_with(pool).await?;
+        let (commands, command_rx) = mpsc::unbounded_channel();
+
+        let inner = Arc::new(HubInner {
+            registry: Mutex::new(Registry::default()),
+            commands,
+            next_id: AtomicU64::new(0),
+        });
+
+        tokio::spawn(run_listener(listener, command_rx, inner.clone()));
+
+        Ok(Self { inner })
+    }
+
+    /// Registers a new subscriber.
+    ///
+    /// # Returns
+    ///
+    /// The subscriber handle used to manage channels, and the receiving end of
+    /// its queue. The receiver yields `None` once the subscriber was dropped
+    /// for falling behind, or once the handle itself is dropped.
+    pub(crate) fn subscriber(&self) -> (Subscriber, mpsc::Receiver<Notification>) {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_CAPACITY);
+
+        self.inner.registry().subscribers.insert(
+            id,
+            SubscriberEntry {
+                sender,
+                channels: HashSet::new(),
+            },
+        );
+
+        let subscriber = Subscriber {
+            id,
+            hub: self.clone(),
+        };
+        (subscriber, receiver)
+    }
+}
+
+impl HubInner {
+    fn registry(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Registry {
+    /// Removes a subscriber from every channel it listens to.
+    ///
+    /// # Returns
+    ///
+    /// The channels that no longer have any subscriber.
+    fn remove_subscriber(&mut self, id: u64) -> Vec<String> {
+        let Some(entry) = self.subscribers.remove(&id) else {
+            return Vec::new();
+        };
+
+        entry
+            .channels
+            .into_iter()
+            .filter(|channel| self.remove_from_channel(channel, id))
+            .collect()
+    }
+
+    /// Removes a subscriber from a single channel.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the channel no longer has any subscriber.
+    fn remove_from_channel(&mut self, channel: &str, id: u64) -> bool {
+        let Some(ids) = self.channels.get_mut(channel) else {
+            return false;
+        };
+        ids.remove(&id);
+        if ids.is_empty() {
+            self.channels.remove(channel);
+            return true;
+        }
+        false
+    }
+}
+
+/// A registered consumer of notifications.
+///
+/// Dropping the subscriber unsubscribes it from all of its channels.
+pub(crate) struct Subscriber {
+    id: u64,
+    hub: NotificationHub,
+}
+
+impl Subscriber {
+    /// Subscribes to a channel.
+    ///
+    /// Resolves once the shared listener is listening to the channel, so any
+    /// notification sent afterwards is guaranteed to be delivered.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` once the channel is listened to
+    /// - `Err(SubscribeError)` if `LISTEN` failed or the subscriber is closed
+    pub(crate) async fn subscribe(&self, channel: &str) -> Result<(), SubscribeError> {
+        {
+            let mut registry = self.hub.inner.registry();
+            let Some(entry) = registry.subscribers.get_mut(&self.id) else {
+                return Err(SubscribeError::Closed);
+            };
+            if !entry.channels.insert(channel.to_string()) {
+                return Ok(());
+            }
+            registry
+                .channels
+                .entry(channel.to_string())
+                .or_default()
+                .insert(self.id);
+        }
+
+        let (ack, ack_rx) = oneshot::channel();
+        self.hub
+            .inner
+            .commands
+            .send(Command::Listen(channel.to_string(), ack))
+            .map_err(|_| SubscribeError::Closed)?;
+
+        let result = ack_rx.await.map_err(|_| SubscribeError::Closed)?;
+        if result.is_err() {
+            self.unsubscribe(channel);
+        }
+        result.map_err(SubscribeError::Listen)
+    }
+
+    /// Waits until every notification sent before the call was queued to
+    /// the subscribers of its channel.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` once the notifications are queued
+    /// - `Err(SubscribeError::Closed)` if the hub has shut down
+    pub(crate) async fn sync(&self) -> Result<(), SubscribeError> {
+        let (ack, ack_rx) = oneshot::channel();
+        self.hub
+            .inner
+            .commands
+            .send(Command::Sync(ack))
+            .map_err(|_| SubscribeError::Closed)?;
+        ack_rx.await.map_err(|_| SubscribeError::Closed)
+    }
+
+    /// Unsubscribes from a channel.
+    pub(crate) fn unsubscribe(&self, channel: &str) {
+        let emptied = {
+            let mut registry = self.hub.inner.registry();
+            let removed = registry
+                .subscribers
+                .get_mut(&self.id)
+                .is_some_and(|entry| entry.channels.remove(channel));
+            removed && registry.remove_from_channel(channel, self.id)
+        };
+
+        if emptied {
+            let _ = self
+                .hub
+                .inner
+                .commands
+                .send(Command::Unlisten(channel.to_string()));
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let emptied = self.hub.inner.registry().remove_subscriber(self.id);
+        for channel in emptied {
+            let _ = self.hub.inner.commands.send(Command::Unlisten(channel));
+        }
+    }
+}
+
+/// Drives the shared listener: applies `LISTEN`/`UNLISTEN` requests and fans
+/// notifications out to subscribers.
+#[tracing::instrument(skip_all)]
+async fn run_listener(
+    mut listener: PgListener,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    inner: Arc<HubInner>,
+) {
+    let mut listening = HashSet::new();
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => {
+                match command {
+                    Some(Command::Listen(channel, ack)) => {
+                        let result = if listening.contains(&channel) {
+                            Ok(())
+                        } else {
+                            listener.listen(&channel).await.map(|_| {
+                                listening.insert(channel.clone());
+                            })
+                        };
+                        if let Err(e) = &result {
+                            tracing::error!(error = ?e, channel, "Failed to listen to channel");
+                        }
+                        let _ = ack.send(result);
+                    }
+                    Some(Command::Unlisten(channel)) => {
+                        // The channel may have gained a new subscriber since the request was sent
+                        if inner.registry().channels.contains_key(&channel) {
+                            continue;
+                        }
+                        if listening.remove(&channel)
+                            && let Err(e) = listener.unlisten(&channel).await
+                        {
+                            tracing::error!(error = ?e, channel, "Failed to unlisten from channel");
+                        }
+                    }
+                    Some(Command::Sync(ack)) => {
+                        // Notifications sent before the round trip arrive ahead of
+                        // its response, and are buffered by the listener meanwhile
+                        if let Err(e) = sqlx::query("SELECT 1").execute(&mut listener).await {
+                            tracing::error!(error = ?e, "Failed to sync notification listener");
+                        }
+                        while let Some(notification) = listener.next_buffered() {
+                            dispatch(&inner, notification.channel(), notification.payload());
+                        }
+                        let _ = ack.send(());
+                    }
+                    None => break,
+                }
+            }
+
+            notification = listener.recv() => {
+                match notification {
+                    Ok(notification) => dispatch(&inner, notification.channel(), notification.payload()),
+                    Err(e) => {
+                        tracing::error!(error = ?e, "Notification listener error, retrying");
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Delivers a notification to every subscriber of its channel, dropping
+/// subscribers whose queue is full or closed.
+fn dispatch(inner: &HubInner, channel: &str, payload: &str) {
+    let notification = Notification {
+        channel: channel.into(),
+        payload: payload.into(),
+    };
+
+    let mut registry = inner.registry();
+    let Some(ids) = registry.channels.get(channel) else {
+        return;
+    };
+
+    let dropped: Vec<u64> = ids
+        .iter()
+        .filter(|id| {
+            registry
+                .subscribers
+                .get(id)
+                .is_some_and(|entry| entry.sender.try_send(notification.clone()).is_err())
+        })
+        .copied()
+        .collect();
+
+    for id in dropped {
+        tracing::warn!(
+            subscriber = id,
+            channel,
+            "Dropping slow notification subscriber"
+        );
+        for emptied in registry.remove_subscriber(id) {
+            let _ = inner.commands.send(Command::Unlisten(emptied));
+        }
+    }
+}
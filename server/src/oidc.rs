@@ -0,0 +1,471 @@
+//! OpenID Connect single sign-on.
+//!
+//! Users can log in through the providers configured with `OIDC_PROVIDERS`,
+//! using the authorization code flow with PKCE. Starting a login stores the
+//! PKCE verifier and nonce under the hash of a random `state`, which is also
+//! kept in a cookie, so that only the browser that started a login can finish it.
+//!
+//! Provider accounts are linked to users through the `user_identities` table,
+//! keyed by the provider's issuer and the account's subject.
+
+use crate::setup::public_url;
+use axum::http::StatusCode;
+use axum::response::Response;
+use openidconnect::core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata};
+use openidconnect::{
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, EndpointMaybeSet, EndpointNotSet,
+    EndpointSet, IssuerUrl, Nonce, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+    TokenResponse, reqwest,
+};
+use sqlx::{PgConnection, PgPool};
+use std::env;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use utils::cookies::OIDC_STATE_DURATION;
+use utils::errors::error_response;
+use utils::tokens::{generate_token, hash_token};
+
+/// Scopes requested besides `openid` when `OIDC_<ID>_SCOPES` is not set.
+const DEFAULT_SCOPES: &str = "email profile";
+
+/// How long a new user has to pick a username after logging in with a provider.
+pub(crate) const SIGNUP_DURATION: time::Duration = time::Duration::minutes(30);
+
+/// Client configured from a provider's discovery document.
+type DiscoveredClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+/// A configured OpenID Connect provider.
+pub(crate) struct OidcProvider {
+    /// ID used in URLs, e.g. `corp` in `/api/auth/oidc/corp/start`.
+    pub id: String,
+    /// Name shown to users.
+    pub name: String,
+    issuer_url: IssuerUrl,
+    client_id: ClientId,
+    client_secret: Option<ClientSecret>,
+    scopes: Vec<String>,
+    /// Discovered on first use, so that an unreachable provider doesn't keep the server from starting.
+    client: OnceCell<DiscoveredClient>,
+}
+
+impl OidcProvider {
+    /// Reads the provider's configuration from its `OIDC_<ID>_*` environment variables.
+    fn from_env(id: &str) -> Result<Self, String> {
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(format!(
+                "Invalid provider ID {id:?}, use lowercase letters, digits and dashes"
+            ));
+        }
+
+        let prefix = format!("OIDC_{}_", id.to_uppercase().replace('-', "_"));
+        let var = |name: &str| env::var(format!("{prefix}{name}")).ok();
+        let required = |name: &str| {
+            var(name).ok_or_else(|| format!("{prefix}{name} must be set for provider {id}"))
+        };
+
+        let issuer_url = IssuerUrl::new(required("ISSUER")?)
+            .map_err(|e| format!("Invalid {prefix}ISSUER: {e}"))?;
+
+        Ok(Self {
+            id: id.to_string(),
+            name: var("NAME").unwrap_or_else(|| id.to_string()),
+            issuer_url,
+            client_id: ClientId::new(required("CLIENT_ID")?),
+            client_secret: var("CLIENT_SECRET").map(ClientSecret::new),
+            scopes: var("SCOPES")
+                .unwrap_or_else(|| DEFAULT_SCOPES.to_string())
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            client: OnceCell::new(),
+        })
+    }
+
+    /// Returns the issuer URL identities of this provider are stored with.
+    pub(crate) fn issuer(&self) -> &str {
+        self.issuer_url.as_str()
+    }
+
+    /// Returns the URL the provider redirects back to.
+    fn redirect_url(&self) -> String {
+        format!("{}/api/auth/oidc/{}/callback", public_url(), self.id)
+    }
+
+    /// Returns the client, discovering the provider's endpoints and keys on first use.
+    async fn client(&self, http: &reqwest::Client) -> Result<&DiscoveredClient, OidcError> {
+        self.client
+            .get_or_try_init(|| async {
+                let metadata = CoreProviderMetadata::discover_async(self.issuer_url.clone(), http)
+                    .await
+                    .map_err(|e| OidcError::Provider(format!("Discovery failed: {e}")))?;
+                let redirect_url = RedirectUrl::new(self.redirect_url())
+                    .map_err(|e| OidcError::Provider(format!("Invalid redirect URL: {e}")))?;
+
+                Ok(CoreClient::from_provider_metadata(
+                    metadata,
+                    self.client_id.clone(),
+                    self.client_secret.clone(),
+                )
+                .set_redirect_uri(redirect_url))
+            })
+            .await
+    }
+}
+
+/// The configured OpenID Connect providers.
+#[derive(Clone)]
+pub(crate) struct OidcProviders {
+    providers: Arc<Vec<OidcProvider>>,
+    http: reqwest::Client,
+}
+
+impl OidcProviders {
+    /// Reads the providers listed in the `OIDC_PROVIDERS` environment variable,
+    /// a comma separated list of provider IDs. Each provider is configured with
+    /// `OIDC_<ID>_ISSUER`, `OIDC_<ID>_CLIENT_ID`, and optionally
+    /// `OIDC_<ID>_CLIENT_SECRET`, `OIDC_<ID>_NAME` and `OIDC_<ID>_SCOPES`.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(OidcProviders)` with the configured providers, possibly none
+    /// - `Err(String)` describing the invalid configuration
+    pub(crate) fn from_env() -> Result<Self, String> {
+        let providers = env::var("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(OidcProvider::from_env)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Following redirects would expose the token endpoint to SSRF
+        let http = reqwest::ClientBuilder::new()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| format!("Failed to build the HTTP client: {e}"))?;
+
+        Ok(Self {
+            providers: Arc::new(providers),
+            http,
+        })
+    }
+
+    /// Returns the configured providers.
+    pub(crate) fn list(&self) -> &[OidcProvider] {
+        &self.providers
+    }
+
+    /// Returns the provider with the given ID.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(&OidcProvider)` if it is configured
+    /// - `Err(OidcError::UnknownProvider)` otherwise
+    pub(crate) fn get(&self, id: &str) -> Result<&OidcProvider, OidcError> {
+        self.providers
+            .iter()
+            .find(|provider| provider.id == id)
+            .ok_or(OidcError::UnknownProvider)
+    }
+}
+
+/// Errors returned by OpenID Connect logins.
+#[derive(Debug)]
+pub(crate) enum OidcError {
+    /// No provider is configured with the ID.
+    UnknownProvider,
+    /// The state is unknown or expired, or doesn't match the browser's cookie.
+    InvalidState,
+    /// The provider could not be reached, or returned something invalid.
+    Provider(String),
+    /// A database operation failed.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for OidcError {
+    fn from(e: sqlx::Error) -> Self {
+        OidcError::Database(e)
+    }
+}
+
+impl OidcError {
+    /// Builds the error response for the error.
+    pub(crate) fn into_response(self) -> Response {
+        match self {
+            OidcError::UnknownProvider => {
+                error_response(StatusCode::NOT_FOUND, "Unknown identity provider")
+            }
+            OidcError::InvalidState => error_response(
+                StatusCode::BAD_REQUEST,
+                "This login is invalid or has expired, please try again",
+            ),
+            OidcError::Provider(e) => {
+                tracing::error!(error = %e, "OpenID Connect provider error.");
+                error_response(
+                    StatusCode::BAD_GATEWAY,
+                    "The identity provider could not complete the login",
+                )
+            }
+            OidcError::Database(e) => {
+                tracing::error!(error = ?e, "Database error during OpenID Connect login.");
+                error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "An error occurred on our end while logging you in",
+                )
+            }
+        }
+    }
+}
+
+/// A login sent to a provider.
+pub(crate) struct StartedLogin {
+    /// URL of the provider's authorization endpoint to send the browser to.
+    pub authorize_url: String,
+    /// State to keep in the browser's cookie.
+    pub state: String,
+}
+
+/// Starts a login with the provider.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `providers` - The configured providers
+/// * `provider` - The provider to log in with
+/// * `link_user_id` - The signed-in user to link the identity to, if any
+///
+/// # Returns
+///
+/// - `Ok(StartedLogin)` with the URL to redirect to
+/// - `Err(OidcError)` if the provider could not be discovered or the login stored
+pub(crate) async fn start_login(
+    pool: &PgPool,
+    providers: &OidcProviders,
+    provider: &OidcProvider,
+    link_user_id: Option<i64>,
+) -> Result<StartedLogin, OidcError> {
+    let client = provider.client(&providers.http).await?;
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let (authorize_url, state, nonce) = client
+        .authorize_url(
+            CoreAuthenticationFlow::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .add_scopes(provider.scopes.iter().cloned().map(Scope::new))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+
+    let expires_at = time::OffsetDateTime::now_utc() + OIDC_STATE_DURATION;
+    sqlx::query!(
+        r#"
+        INSERT INTO oidc_logins (provider, state_hash, pkce_verifier, nonce, link_user_id, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        provider.id,
+        hash_token(state.secret()),
+        pkce_verifier.secret(),
+        nonce.secret(),
+        link_user_id,
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(StartedLogin {
+        authorize_url: authorize_url.to_string(),
+        state: state.secret().clone(),
+    })
+}
+
+/// An account at a provider, as reported by its verified ID token.
+pub(crate) struct ProviderIdentity {
+    /// Issuer URL of the provider.
+    pub issuer: String,
+    /// The account's stable identifier at the provider.
+    pub subject: String,
+    /// Email address of the account, if shared.
+    pub email: Option<String>,
+    /// Whether the provider verified the email address.
+    pub email_verified: bool,
+    /// Username suggested by the provider, if any.
+    pub preferred_username: Option<String>,
+}
+
+/// A login the provider redirected back from.
+pub(crate) struct FinishedLogin {
+    /// The identity that logged in.
+    pub identity: ProviderIdentity,
+    /// The signed-in user who started the login to link the identity to, if any.
+    pub link_user_id: Option<i64>,
+}
+
+/// Finishes a login the provider redirected back from.
+///
+/// Redeems the stored login (which can only be used once), exchanges the
+/// authorization code with the PKCE verifier, and verifies the ID token's
+/// signature, audience, expiry and nonce.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `providers` - The configured providers
+/// * `provider` - The provider that redirected back
+/// * `code` - The authorization code from the redirect
+/// * `state` - The state from the redirect, already matched against the cookie
+///
+/// # Returns
+///
+/// - `Ok(FinishedLogin)` with the provider's identity
+/// - `Err(OidcError)` if the login is invalid or the provider refused it
+pub(crate) async fn finish_login(
+    pool: &PgPool,
+    providers: &OidcProviders,
+    provider: &OidcProvider,
+    code: String,
+    state: &str,
+) -> Result<FinishedLogin, OidcError> {
+    let Some(login) = sqlx::query!(
+        r#"
+        DELETE FROM oidc_logins
+        WHERE state_hash = $1 AND provider = $2 AND expires_at > NOW()
+        RETURNING pkce_verifier, nonce, link_user_id
+        "#,
+        hash_token(state),
+        provider.id
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Err(OidcError::InvalidState);
+    };
+
+    let client = provider.client(&providers.http).await?;
+    let token_response = client
+        .exchange_code(AuthorizationCode::new(code))
+        .map_err(|e| OidcError::Provider(format!("No token endpoint: {e}")))?
+        .set_pkce_verifier(PkceCodeVerifier::new(login.pkce_verifier))
+        .request_async(&providers.http)
+        .await
+        .map_err(|e| OidcError::Provider(format!("Code exchange failed: {e}")))?;
+
+    let id_token = token_response
+        .id_token()
+        .ok_or_else(|| OidcError::Provider("No ID token returned".to_string()))?;
+    let claims = id_token
+        .claims(&client.id_token_verifier(), &Nonce::new(login.nonce))
+        .map_err(|e| OidcError::Provider(format!("Invalid ID token: {e}")))?;
+
+    Ok(FinishedLogin {
+        identity: ProviderIdentity {
+            issuer: provider.issuer().to_string(),
+            subject: claims.subject().to_string(),
+            email: claims.email().map(|email| email.to_string()),
+            email_verified: claims.email_verified().unwrap_or(false),
+            preferred_username: claims
+                .preferred_username()
+                .map(|username| username.to_string()),
+        },
+        link_user_id: login.link_user_id,
+    })
+}
+
+/// Suggests a username for a new user from their provider identity, shortened
+/// to `max_length` characters of the ones usually allowed in usernames.
+pub(crate) fn suggest_username(identity: &ProviderIdentity, max_length: usize) -> String {
+    let source = identity
+        .preferred_username
+        .as_deref()
+        .or_else(|| identity.email.as_deref()?.split('@').next())
+        .unwrap_or_default();
+
+    source
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .take(max_length)
+        .collect()
+}
+
+/// Links a provider identity to a user, unless it is already linked.
+///
+/// Takes a connection so that it can run inside the caller's transaction.
+///
+/// # Returns
+///
+/// - `Ok(i64)` with the user the identity is linked to, which is another
+///   user if it was linked to them before
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn link_identity(
+    conn: &mut PgConnection,
+    user_id: i64,
+    identity: &ProviderIdentity,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        WITH inserted AS (
+            INSERT INTO user_identities (user_id, issuer, subject, email, last_login_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (issuer, subject) DO NOTHING
+            RETURNING user_id
+        )
+        SELECT user_id AS "user_id!" FROM inserted
+        UNION ALL
+        SELECT user_id FROM user_identities WHERE issuer = $2 AND subject = $3
+        LIMIT 1
+        "#,
+        user_id,
+        identity.issuer,
+        identity.subject,
+        identity.email
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Stores a pending signup for an identity that isn't linked to any user yet,
+/// with the email address the provider shared.
+///
+/// # Returns
+///
+/// - `Ok(String)` with the token the new user signs up with
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn create_signup(
+    pool: &PgPool,
+    identity: &ProviderIdentity,
+    email: &str,
+) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let expires_at = time::OffsetDateTime::now_utc() + SIGNUP_DURATION;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO oidc_signups (issuer, subject, email, email_verified, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        identity.issuer,
+        identity.subject,
+        email,
+        identity.email_verified,
+        hash_token(&token),
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests;
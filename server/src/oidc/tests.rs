@@ -0,0 +1,547 @@
+//! Single sign-on logins against a mock identity provider.
+//!
+//! The mock provider serves discovery, its signing keys and a token endpoint
+//! on a random local port. Each test grants an authorization code for the
+//! claims it needs, then drives the start, callback and signup handlers the
+//! way a browser would.
+
+use super::*;
+use crate::mailer::{OutboxMailer, SharedMailer};
+use crate::routes::auth::oidc::callback::api_auth_oidc_callback_get;
+use crate::routes::auth::oidc::signup::api_auth_oidc_signup_post;
+use crate::routes::auth::oidc::start::api_auth_oidc_start_get;
+use api_types::auth::oidc::callback::ApiAuthOidcCallbackQuery;
+use api_types::auth::oidc::signup::ApiAuthOidcSignupRequest;
+use axum::extract::{ConnectInfo, Form, Path, Query, State};
+use axum::http::header::{COOKIE, LOCATION, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use axum_extra::extract::CookieJar;
+use axum_extra::extract::cookie::Cookie;
+use openidconnect::core::{
+    CoreIdToken, CoreIdTokenClaims, CoreIdTokenFields, CoreJsonWebKeySet, CoreJwsSigningAlgorithm,
+    CoreResponseType, CoreRsaPrivateSigningKey, CoreSubjectIdentifierType, CoreTokenResponse,
+    CoreTokenType,
+};
+use openidconnect::url::{Url, form_urlencoded};
+use openidconnect::{
+    AccessToken, Audience, AuthUrl, EmptyAdditionalClaims, EmptyAdditionalProviderMetadata,
+    EmptyExtraTokenFields, EndUserEmail, JsonWebKeyId, JsonWebKeySetUrl, PrivateSigningKey,
+    ResponseTypes, StandardClaims, SubjectIdentifier, TokenUrl,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Mutex, Once};
+use utils::cookies::OIDC_STATE_COOKIE;
+
+/// Signing key of the mock provider, only ever used by these tests.
+const ISSUER_KEY: &str = include_str!("test_issuer_key.pem");
+
+/// Client ID the server is registered with at the mock provider.
+const CLIENT_ID: &str = "chat";
+
+/// Address the test requests come from.
+const CLIENT_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 40000);
+
+/// An account at the mock provider, as its ID token describes it.
+#[derive(Clone)]
+struct Account {
+    subject: &'static str,
+    email: Option<&'static str>,
+    email_verified: bool,
+}
+
+/// An authorization code handed out by the mock provider.
+struct Grant {
+    account: Account,
+    nonce: String,
+    code_challenge: String,
+}
+
+/// The mock identity provider.
+struct MockIssuer {
+    url: String,
+    key: CoreRsaPrivateSigningKey,
+    grants: Mutex<HashMap<String, Grant>>,
+}
+
+impl MockIssuer {
+    /// Starts the provider on a random local port.
+    async fn start() -> Arc<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = Arc::new(Self {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            key: CoreRsaPrivateSigningKey::from_pem(
+                ISSUER_KEY,
+                Some(JsonWebKeyId::new("test".to_string())),
+            )
+            .unwrap(),
+            grants: Mutex::new(HashMap::new()),
+        });
+
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(issuer.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        issuer
+    }
+
+    /// Returns the server's configuration of this provider.
+    fn providers(&self) -> OidcProviders {
+        OidcProviders {
+            providers: Arc::new(vec![OidcProvider {
+                id: "mock".to_string(),
+                name: "Mock".to_string(),
+                issuer_url: IssuerUrl::new(self.url.clone()).unwrap(),
+                client_id: ClientId::new(CLIENT_ID.to_string()),
+                client_secret: Some(ClientSecret::new("secret".to_string())),
+                scopes: vec!["email".to_string()],
+                client: OnceCell::new(),
+            }]),
+            http: reqwest::ClientBuilder::new()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap(),
+        }
+    }
+
+    /// Hands out an authorization code for the account, as if they logged in
+    /// after being sent to the authorization URL.
+    fn grant(&self, authorize_url: &Url, account: Account) -> String {
+        self.grant_with_nonce(authorize_url, account, &query_param(authorize_url, "nonce"))
+    }
+
+    /// Like [`MockIssuer::grant`], with the ID token carrying the given nonce.
+    fn grant_with_nonce(&self, authorize_url: &Url, account: Account, nonce: &str) -> String {
+        let code = generate_token();
+        self.grants.lock().unwrap().insert(
+            code.clone(),
+            Grant {
+                account,
+                nonce: nonce.to_string(),
+                code_challenge: query_param(authorize_url, "code_challenge"),
+            },
+        );
+        code
+    }
+}
+
+/// Serves the discovery document.
+async fn discovery(State(issuer): State<Arc<MockIssuer>>) -> impl IntoResponse {
+    let metadata = CoreProviderMetadata::new(
+        IssuerUrl::new(issuer.url.clone()).unwrap(),
+        AuthUrl::new(format!("{}/authorize", issuer.url)).unwrap(),
+        JsonWebKeySetUrl::new(format!("{}/jwks", issuer.url)).unwrap(),
+        vec![ResponseTypes::new(vec![CoreResponseType::Code])],
+        vec![CoreSubjectIdentifierType::Public],
+        vec![CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256],
+        EmptyAdditionalProviderMetadata {},
+    )
+    .set_token_endpoint(Some(
+        TokenUrl::new(format!("{}/token", issuer.url)).unwrap(),
+    ));
+    Json(metadata)
+}
+
+/// Serves the signing keys.
+async fn jwks(State(issuer): State<Arc<MockIssuer>>) -> impl IntoResponse {
+    Json(CoreJsonWebKeySet::new(vec![
+        issuer.key.as_verification_key(),
+    ]))
+}
+
+/// Exchanges an authorization code for an ID token, checking the PKCE verifier.
+async fn token(
+    State(issuer): State<Arc<MockIssuer>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> axum::response::Response {
+    let grant = form
+        .get("code")
+        .and_then(|code| issuer.grants.lock().unwrap().remove(code));
+    let verified = grant.as_ref().is_some_and(|grant| {
+        form.get("code_verifier").is_some_and(|verifier| {
+            PkceCodeChallenge::from_code_verifier_sha256(&PkceCodeVerifier::new(verifier.clone()))
+                .as_str()
+                == grant.code_challenge
+        })
+    });
+    let Some(grant) = grant.filter(|_| verified) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid_grant" })),
+        )
+            .into_response();
+    };
+
+    let now = chrono::Utc::now();
+    let claims = CoreIdTokenClaims::new(
+        IssuerUrl::new(issuer.url.clone()).unwrap(),
+        vec![Audience::new(CLIENT_ID.to_string())],
+        now + chrono::Duration::minutes(5),
+        now,
+        StandardClaims::new(SubjectIdentifier::new(grant.account.subject.to_string()))
+            .set_email(
+                grant
+                    .account
+                    .email
+                    .map(|email| EndUserEmail::new(email.to_string())),
+            )
+            .set_email_verified(Some(grant.account.email_verified)),
+        EmptyAdditionalClaims {},
+    )
+    .set_nonce(Some(Nonce::new(grant.nonce)));
+    let id_token = CoreIdToken::new(
+        claims,
+        &issuer.key,
+        CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+        None,
+        None,
+    )
+    .unwrap();
+
+    Json(CoreTokenResponse::new(
+        AccessToken::new(generate_token()),
+        CoreTokenType::Bearer,
+        CoreIdTokenFields::new(Some(id_token), EmptyExtraTokenFields {}),
+    ))
+    .into_response()
+}
+
+/// Returns a query parameter of the URL.
+fn query_param(url: &Url, name: &str) -> String {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default()
+}
+
+/// Sets the JWT secret sessions are signed with.
+fn init_env() {
+    static INIT: Once = Once::new();
+    // SAFETY: the variable is only read through `std::env`, which synchronizes
+    // access to the environment, and set once before any session is issued
+    INIT.call_once(|| unsafe { env::set_var("JWT_SECRET_KEY", "oidc-test-secret") });
+}
+
+/// A login started in the browser.
+struct Browser {
+    state_cookie: String,
+    authorize_url: Url,
+}
+
+/// Starts a login, as a browser following the start endpoint would.
+async fn start(pool: &PgPool, providers: &OidcProviders) -> Browser {
+    let resp = api_auth_oidc_start_get(
+        State(pool.clone()),
+        State(providers.clone()),
+        Path("mock".to_string()),
+    )
+    .await
+    .into_response();
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    let authorize_url = Url::parse(resp.headers()[LOCATION].to_str().unwrap()).unwrap();
+    let state_cookie = cookie(&resp, OIDC_STATE_COOKIE).expect("state cookie");
+    assert_eq!(query_param(&authorize_url, "state"), state_cookie);
+    Browser {
+        state_cookie,
+        authorize_url,
+    }
+}
+
+/// Calls the redirect endpoint with the code, the login's state and the given state cookie.
+async fn callback(
+    pool: &PgPool,
+    providers: &OidcProviders,
+    browser: &Browser,
+    state_cookie: &str,
+    code: String,
+) -> axum::response::Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        COOKIE,
+        HeaderValue::from_str(&format!("{OIDC_STATE_COOKIE}={state_cookie}")).unwrap(),
+    );
+
+    api_auth_oidc_callback_get(
+        State(pool.clone()),
+        State(providers.clone()),
+        ConnectInfo(SocketAddr::from(CLIENT_ADDR)),
+        headers.clone(),
+        CookieJar::from_headers(&headers),
+        Path("mock".to_string()),
+        Query(ApiAuthOidcCallbackQuery {
+            code: Some(code),
+            state: Some(query_param(&browser.authorize_url, "state")),
+            error: None,
+            error_description: None,
+        }),
+    )
+    .await
+    .into_response()
+}
+
+/// Returns the value of a cookie the response sets.
+fn cookie(resp: &axum::response::Response, name: &str) -> Option<String> {
+    resp.headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| Cookie::parse(value.to_str().ok()?.to_string()).ok())
+        .find(|cookie| cookie.name() == name && !cookie.value().is_empty())
+        .map(|cookie| cookie.value().to_string())
+}
+
+/// Creates a user, with their email address verified or not.
+async fn create_user(pool: &PgPool, username: &str, email: &str, email_verified: bool) -> i64 {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO users (username, email, password_hash, bio, email_verified_at)
+        VALUES ($1, $2, 'unused', '', CASE WHEN $3 THEN NOW() END)
+        RETURNING id
+        "#,
+        username,
+        email,
+        email_verified
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+/// Returns the user the mock provider's account is linked to, if any.
+async fn linked_user(pool: &PgPool, issuer: &MockIssuer, subject: &str) -> Option<i64> {
+    sqlx::query_scalar!(
+        "SELECT user_id FROM user_identities WHERE issuer = $1 AND subject = $2",
+        issuer.url,
+        subject
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+/// Returns the number of sessions of the user.
+async fn session_count(pool: &PgPool, user_id: i64) -> i64 {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM sessions WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+const ALICE: Account = Account {
+    subject: "alice-sub",
+    email: Some("alice@example.com"),
+    email_verified: true,
+};
+
+#[sqlx::test(migrations = "../migrations")]
+async fn logs_in_linked_identity(pool: PgPool) {
+    init_env();
+    let issuer = MockIssuer::start().await;
+    let providers = issuer.providers();
+    let user_id = create_user(&pool, "alice", "alice@example.com", true).await;
+    sqlx::query!(
+        "INSERT INTO user_identities (user_id, issuer, subject) VALUES ($1, $2, $3)",
+        user_id,
+        issuer.url,
+        ALICE.subject
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let browser = start(&pool, &providers).await;
+    let code = issuer.grant(&browser.authorize_url, ALICE);
+    let resp = callback(&pool, &providers, &browser, &browser.state_cookie, code).await;
+
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers()[LOCATION], format!("{}/", public_url()));
+    assert!(cookie(&resp, "session_token").is_some());
+    assert!(cookie(&resp, "refresh_token").is_some());
+    assert_eq!(session_count(&pool, user_id).await, 1);
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn reports_failed_logins_since_last_login(pool: PgPool) {
+    init_env();
+    let issuer = MockIssuer::start().await;
+    let providers = issuer.providers();
+    let user_id = create_user(&pool, "alice", "alice@example.com", true).await;
+    sqlx::query!(
+        "INSERT INTO user_identities (user_id, issuer, subject) VALUES ($1, $2, $3)",
+        user_id,
+        issuer.url,
+        ALICE.subject
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    for _ in 0..2 {
+        sqlx::query!(
+            r#"
+            INSERT INTO login_attempts (user_id, identifier, ip_address, outcome)
+            VALUES ($1, 'alice', '203.0.113.7', 'failed')
+            "#,
+            user_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let browser = start(&pool, &providers).await;
+    let code = issuer.grant(&browser.authorize_url, ALICE);
+    let resp = callback(&pool, &providers, &browser, &browser.state_cookie, code).await;
+
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let location = resp.headers()[LOCATION].to_str().unwrap();
+    let fragment = location
+        .strip_prefix(&format!("{}/#", public_url()))
+        .unwrap();
+    assert!(fragment.starts_with("failedAttempts=2&lastAttemptAt="));
+    assert!(fragment.ends_with("&lastIpAddress=203.0.113.7"));
+
+    // The notice is only shown once
+    let browser = start(&pool, &providers).await;
+    let code = issuer.grant(&browser.authorize_url, ALICE);
+    let resp = callback(&pool, &providers, &browser, &browser.state_cookie, code).await;
+    assert_eq!(resp.headers()[LOCATION], format!("{}/", public_url()));
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn rejects_state_not_matching_cookie(pool: PgPool) {
+    init_env();
+    let issuer = MockIssuer::start().await;
+    let providers = issuer.providers();
+
+    // Another browser's login
+    let victim = start(&pool, &providers).await;
+    let attacker = start(&pool, &providers).await;
+    let code = issuer.grant(&victim.authorize_url, ALICE);
+    let resp = callback(&pool, &providers, &victim, &attacker.state_cookie, code).await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(cookie(&resp, "session_token").is_none());
+    assert_eq!(linked_user(&pool, &issuer, ALICE.subject).await, None);
+
+    // The login wasn't redeemed, so its own browser can still finish it
+    let code = issuer.grant(&victim.authorize_url, ALICE);
+    let resp = callback(&pool, &providers, &victim, &victim.state_cookie, code).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn rejects_id_token_with_other_nonce(pool: PgPool) {
+    init_env();
+    let issuer = MockIssuer::start().await;
+    let providers = issuer.providers();
+    let user_id = create_user(&pool, "alice", "alice@example.com", true).await;
+
+    let browser = start(&pool, &providers).await;
+    let code = issuer.grant_with_nonce(&browser.authorize_url, ALICE, "replayed-nonce");
+    let resp = callback(&pool, &providers, &browser, &browser.state_cookie, code).await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    assert!(cookie(&resp, "session_token").is_none());
+    assert_eq!(linked_user(&pool, &issuer, ALICE.subject).await, None);
+    assert_eq!(session_count(&pool, user_id).await, 0);
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn links_user_by_verified_email(pool: PgPool) {
+    init_env();
+    let issuer = MockIssuer::start().await;
+    let providers = issuer.providers();
+    let user_id = create_user(&pool, "alice", "alice@example.com", true).await;
+
+    let browser = start(&pool, &providers).await;
+    let code = issuer.grant(&browser.authorize_url, ALICE);
+    let resp = callback(&pool, &providers, &browser, &browser.state_cookie, code).await;
+
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers()[LOCATION], format!("{}/", public_url()));
+    assert!(cookie(&resp, "session_token").is_some());
+    assert_eq!(
+        linked_user(&pool, &issuer, ALICE.subject).await,
+        Some(user_id)
+    );
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn refuses_linking_by_unverified_email(pool: PgPool) {
+    init_env();
+    let issuer = MockIssuer::start().await;
+    let providers = issuer.providers();
+    create_user(&pool, "alice", "alice@example.com", true).await;
+
+    let browser = start(&pool, &providers).await;
+    let account = Account {
+        email_verified: false,
+        ..ALICE
+    };
+    let code = issuer.grant(&browser.authorize_url, account);
+    let resp = callback(&pool, &providers, &browser, &browser.state_cookie, code).await;
+
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert!(cookie(&resp, "session_token").is_none());
+    assert_eq!(linked_user(&pool, &issuer, ALICE.subject).await, None);
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn signs_up_new_user(pool: PgPool) {
+    init_env();
+    let issuer = MockIssuer::start().await;
+    let providers = issuer.providers();
+
+    let browser = start(&pool, &providers).await;
+    let code = issuer.grant(&browser.authorize_url, ALICE);
+    let resp = callback(&pool, &providers, &browser, &browser.state_cookie, code).await;
+
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let location = Url::parse(resp.headers()[LOCATION].to_str().unwrap()).unwrap();
+    assert_eq!(location.path(), "/signup/sso");
+    let fragment: HashMap<_, _> = form_urlencoded::parse(location.fragment().unwrap().as_bytes())
+        .into_owned()
+        .collect();
+    assert_eq!(fragment["username"], "alice");
+
+    let mailer: SharedMailer = Arc::new(OutboxMailer::new(None));
+    let resp = api_auth_oidc_signup_post(
+        State(pool.clone()),
+        State(mailer),
+        ConnectInfo(SocketAddr::from(CLIENT_ADDR)),
+        HeaderMap::new(),
+        Json(ApiAuthOidcSignupRequest {
+            signup_token: fragment["signupToken"].clone(),
+            username: "alice".to_string(),
+            return_tokens: false,
+        }),
+    )
+    .await
+    .into_response();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert!(cookie(&resp, "session_token").is_some());
+
+    let user = sqlx::query!(
+        r#"
+        SELECT id, email, email_verified_at IS NOT NULL AS "email_verified!"
+        FROM users WHERE username = 'alice'
+        "#
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(user.email, "alice@example.com");
+    assert!(user.email_verified);
+    assert_eq!(
+        linked_user(&pool, &issuer, ALICE.subject).await,
+        Some(user.id)
+    );
+}
@@ -0,0 +1,107 @@
+//! Online presence tracking.
+//!
+//! Counts the open WebSockets of every user in this process. A user is
+//! online while at least one of their sockets is open; the transitions are
+//! broadcast to the channels of every conversation the user is part of, and
+//! `users.last_seen_at` is recorded when the last socket closes.
+
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Connected socket counts per user. Cheap to clone.
+#[derive(Clone, Default)]
+pub(crate) struct Presence {
+    connections: Arc<Mutex<HashMap<i64, usize>>>,
+}
+
+impl Presence {
+    /// Records a newly opened socket for the user.
+    ///
+    /// # Returns
+    ///
+    /// `true` if this is the user's first socket, i.e. the user came online.
+    pub(crate) fn connect(&self, user_id: i64) -> bool {
+        let mut connections = self.connections();
+        let count = connections.entry(user_id).or_insert(0);
+        *count += 1;
+        *count == 1
+    }
+
+    /// Records a closed socket for the user.
+    ///
+    /// # Returns
+    ///
+    /// `true` if this was the user's last socket, i.e. the user went offline.
+    pub(crate) fn disconnect(&self, user_id: i64) -> bool {
+        let mut connections = self.connections();
+        let Some(count) = connections.get_mut(&user_id) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            connections.remove(&user_id);
+            return true;
+        }
+        false
+    }
+
+    /// Checks whether the user has at least one open socket.
+    pub(crate) fn is_online(&self, user_id: i64) -> bool {
+        self.connections().contains_key(&user_id)
+    }
+
+    fn connections(&self) -> std::sync::MutexGuard<'_, HashMap<i64, usize>> {
+        self.connections.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Broadcasts a presence change to every conversation the user is part of.
+///
+/// When the user goes offline, `users.last_seen_at` is recorded first so the
+/// notification carries it.
+///
+/// # Returns
+///
+/// - `Ok(())` once the notifications were sent
+/// - `Err(sqlx::Error)` if a database operation fails
+#[tracing::instrument(skip(pool))]
+pub(crate) async fn broadcast_presence(
+    pool: &PgPool,
+    user_id: i64,
+    online: bool,
+) -> Result<(), sqlx::Error> {
+    if !online {
+        sqlx::query!(
+            "UPDATE users SET last_seen_at = NOW() WHERE id = $1",
+            user_id
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    sqlx::query!(
+        r#"
+        SELECT pg_notify(
+            'conversation_' || c.id::TEXT,
+            json_build_object(
+                'kind', 'presence',
+                'conversation_id', c.id,
+                'user_id', u.id,
+                'username', u.username,
+                'online', $2::BOOLEAN,
+                'last_seen_at', u.last_seen_at
+            )::TEXT
+        )
+        FROM conversations c
+        JOIN users u ON u.id = $1
+        WHERE c.user_id_1 = $1 OR c.user_id_2 = $1
+        "#,
+        user_id,
+        online
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -10,3 +10,6 @@ pub mod chats;
 
 /// User management routes (profile, settings, etc.).
 pub mod users;
+
+/// Well-known discovery routes (published signing keys).
+pub mod well_known;
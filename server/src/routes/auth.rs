@@ -4,5 +4,21 @@
 
 /// User login endpoint handler.
 pub mod login;
+/// User logout endpoint handler.
+pub mod logout;
+/// Passwordless magic link login endpoint handlers.
+pub mod magic_link;
+/// OpenID Connect single sign-on endpoint handlers.
+pub mod oidc;
+/// Forgotten password and reset endpoint handlers.
+pub mod password;
+/// Session refresh endpoint handler.
+pub mod refresh;
 /// User registration endpoint handler.
 pub mod register;
+/// Session listing and revocation endpoint handlers.
+pub mod sessions;
+/// Two-factor login step endpoint handler.
+pub mod two_factor;
+/// Email verification endpoint handler.
+pub mod verify_email;
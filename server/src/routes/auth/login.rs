@@ -3,43 +3,71 @@
 //! Handles user authentication with password verification
 //! and JWT token generation.
 
-use api_types::auth::login::{ApiAuthLoginRequest, ApiAuthLoginResponse};
+use crate::login_attempts::{
+    self, Outcome, account_retry_after, failed_since_last_login, ip_retry_after,
+    normalize_identifier, too_many_attempts,
+};
+use crate::sessions::{SessionMeta, issue_session};
+use crate::two_factor::create_challenge;
+use api_types::auth::login::{ApiAuthLoginRequest, ApiAuthLoginResponse, ApiLoginSecurityNotice};
 use axum::Json;
-use axum::extract::State;
-use axum::http::StatusCode;
-use axum::http::header::SET_COOKIE;
-use axum::response::IntoResponse;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use sqlx::PgPool;
 use sqlx::prelude::FromRow;
-use utils::cookies::create_auth_cookie;
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use time::format_description::well_known::Rfc3339;
 use utils::errors::error_response;
-use utils::hashing;
+use utils::hashing::{self, Verification};
+
+/// Hash checked for unknown users, so that they take as long to reject as wrong passwords.
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    hashing::hash_password("dummy password").expect("Hashing the dummy password failed")
+});
 
 #[derive(FromRow)]
 struct UserRecord {
     id: i64,
     password_hash: String,
+    totp_enabled: bool,
 }
 
 /// Handles user login requests.
 ///
 /// This endpoint:
 /// 1. Validates the login request (username/email and password not empty)
-/// 2. Queries the database for a user with the provided username or email
-/// 3. Verifies the password against the stored hash
-/// 4. Generates a JWT token for the authenticated user
-/// 5. Sets a session cookie with the JWT token
+/// 2. Refuses the attempt if the IP address failed too often recently
+/// 3. Queries the database for a user with the provided username or email
+/// 4. Refuses the attempt if the account (or the identifier, for unknown users)
+///    is locked out after repeated failures
+/// 5. Verifies the password against the stored hash, and rehashes it if the
+///    hash was made with outdated Argon2 parameters or pepper
+/// 6. If the user enabled two-factor authentication, returns an `mfaToken`
+///    to exchange at `/api/auth/login/2fa` instead, and stops here
+/// 7. Starts a new session and issues its access and refresh tokens
+/// 8. Sets the access and refresh token cookies, or returns the tokens in the
+///    body if the client asked for them with `returnTokens`, along with a
+///    notice about failed attempts since the previous login
+///
+/// Every attempt is written to the login audit log. Unknown users and wrong
+/// passwords get the same response, and lockouts apply to both alike.
 ///
 /// # Arguments
 ///
 /// * `pool` - The PostgreSQL connection pool
+/// * `addr` - The client's socket address, recorded with the session
+/// * `headers` - The request headers, whose user agent is recorded with the session
 /// * `req` - The login request containing person and password
 ///
 /// # Returns
 ///
-/// - `200 OK` with user details and session cookie on success
+/// - `200 OK` with user details and session cookies (or tokens) on success, or
+///   with an `mfaToken` if a second factor is required
 /// - `400 BAD REQUEST` if validation fails
 /// - `401 UNAUTHORIZED` if credentials are invalid
+/// - `429 TOO MANY REQUESTS` with a `Retry-After` header after too many failed attempts
 /// - `500 INTERNAL SERVER ERROR` if any server-side operation fails
 ///
 /// # Example Request
@@ -48,12 +76,15 @@ struct UserRecord {
 /// {
 ///   "person": "john_doe",
 ///   "password": "SecurePass123",
-///   "is_email": false
+///   "isEmail": false,
+///   "returnTokens": false
 /// }
 /// ```
-#[tracing::instrument(skip(pool, req))]
+#[tracing::instrument(skip(pool, headers, req))]
 pub async fn api_auth_login_post(
     State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(req): Json<ApiAuthLoginRequest>,
 ) -> impl IntoResponse {
     if let Err(e) = req.validate() {
@@ -68,13 +99,36 @@ pub async fn api_auth_login_post(
         person,
         password,
         is_email,
+        return_tokens,
     } = req;
 
+    let meta = SessionMeta::from_request(&headers, addr);
+    let identifier = normalize_identifier(&person);
+
+    match ip_retry_after(&pool, meta.ip_address()).await {
+        Ok(None) => {}
+        Ok(Some(retry_after)) => {
+            tracing::warn!(
+                ip_address = meta.ip_address(),
+                "Login attempt from blocked IP address"
+            );
+            login_attempts::record(&pool, None, Some(&identifier), &meta, Outcome::Locked).await;
+            return too_many_attempts(retry_after);
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to count failed logins of IP address.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while logging you in",
+            );
+        }
+    }
+
     // Query user based on whether it's email or username
     let user = sqlx::query_as!(
         UserRecord,
         r#"
-        SELECT id, password_hash
+        SELECT id, password_hash, totp_enabled_at IS NOT NULL AS "totp_enabled!"
         FROM users
         WHERE (email = $1 AND $2) OR (username = $1 AND NOT $2)
         "#,
@@ -85,11 +139,7 @@ pub async fn api_auth_login_post(
     .await;
 
     let user = match user {
-        Ok(Some(user)) => user,
-        Ok(None) => {
-            tracing::info!(person, is_email, "Login attempt with non-existent user");
-            return error_response(StatusCode::UNAUTHORIZED, "Invalid credentials".to_string());
-        }
+        Ok(user) => user,
         Err(e) => {
             tracing::error!(error = ?e, "Failed to query user from database.");
             return error_response(
@@ -98,15 +148,52 @@ pub async fn api_auth_login_post(
             );
         }
     };
+    let user_id = user.as_ref().map(|user| user.id);
+
+    // Don't check passwords while locked out, whether or not the user exists
+    match account_retry_after(&pool, &identifier, user_id).await {
+        Ok(None) => {}
+        Ok(Some(retry_after)) => {
+            tracing::warn!(user_id, "Login attempt while locked out");
+            login_attempts::record(&pool, user_id, Some(&identifier), &meta, Outcome::Locked).await;
+            return too_many_attempts(retry_after);
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to count failed logins of account.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while logging you in",
+            );
+        }
+    }
+
+    let Some(user) = user else {
+        tracing::info!(person, is_email, "Login attempt with non-existent user");
+        let _ = hashing::verify_password(&password, &DUMMY_HASH);
+        login_attempts::record(&pool, None, Some(&identifier), &meta, Outcome::Failed).await;
+        return error_response(StatusCode::UNAUTHORIZED, "Invalid credentials");
+    };
 
     // Verify password
     match hashing::verify_password(&password, &user.password_hash) {
-        Ok(true) => {
+        Ok(Verification::Match) => {
             tracing::info!(user_id = user.id, "Password verification successful");
         }
-        Ok(false) => {
+        Ok(Verification::MatchOutdated) => {
+            tracing::info!(user_id = user.id, "Password verification successful");
+            rehash_password(&pool, user.id, &password, &user.password_hash).await;
+        }
+        Ok(Verification::Mismatch) => {
             tracing::info!(user_id = user.id, "Login attempt with invalid password");
-            return error_response(StatusCode::UNAUTHORIZED, "Invalid credentials".to_string());
+            login_attempts::record(
+                &pool,
+                Some(user.id),
+                Some(&identifier),
+                &meta,
+                Outcome::Failed,
+            )
+            .await;
+            return error_response(StatusCode::UNAUTHORIZED, "Invalid credentials");
         }
         Err(e) => {
             tracing::error!(error = ?e, "Failed to verify password.");
@@ -117,21 +204,182 @@ pub async fn api_auth_login_post(
         }
     }
 
-    // Generate JWT token and cookie
-    let cookie = match create_auth_cookie(user.id) {
-        Ok(c) => c,
+    // Hold the session back until the second factor is verified
+    if user.totp_enabled {
+        return require_second_factor(&pool, user.id, Some(&identifier), &meta).await;
+    }
+
+    complete_login(&pool, user.id, Some(&identifier), meta, return_tokens).await
+}
+
+/// Replaces a password hash made with outdated parameters or pepper, while
+/// the password is at hand.
+///
+/// The hash is only replaced if it didn't change in the meantime. Failures are
+/// logged and otherwise ignored, the next login tries again.
+async fn rehash_password(pool: &PgPool, user_id: i64, password: &str, old_hash: &str) {
+    let new_hash = match hashing::hash_password(password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to rehash password.");
+            return;
+        }
+    };
+
+    let result = sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE id = $2 AND password_hash = $3",
+        new_hash,
+        user_id,
+        old_hash
+    )
+    .execute(pool)
+    .await;
+
+    match result {
+        Ok(_) => tracing::info!(user_id, "Upgraded password hash"),
+        Err(e) => tracing::error!(error = ?e, user_id, "Failed to store rehashed password."),
+    }
+}
+
+/// Builds the login response asking for the second factor, for a user who
+/// passed every other login check.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_id` - The user logging in
+/// * `identifier` - The normalized username or email address used, if known
+/// * `meta` - The client logging in
+///
+/// # Returns
+///
+/// `200 OK` with an `mfaToken` to exchange at `/api/auth/login/2fa`, or an error response.
+pub(crate) async fn require_second_factor(
+    pool: &PgPool,
+    user_id: i64,
+    identifier: Option<&str>,
+    meta: &SessionMeta,
+) -> Response {
+    let mfa_token = match create_challenge(pool, user_id).await {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to create two-factor challenge.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while logging you in",
+            );
+        }
+    };
+
+    tracing::info!(user_id, "Two-factor code required");
+    login_attempts::record(pool, Some(user_id), identifier, meta, Outcome::MfaRequired).await;
+    let resp = ApiAuthLoginResponse {
+        ok: true,
+        message: "Two-factor code required".to_string(),
+        id: None,
+        tokens: None,
+        mfa_token: Some(mfa_token),
+        security_notice: None,
+    };
+    (StatusCode::OK, Json(resp)).into_response()
+}
+
+/// Starts a session for a user who passed every login check and builds the login response.
+///
+/// The response carries a security notice if someone failed to log into the
+/// account since its previous successful login.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_id` - The user logging in
+/// * `identifier` - The normalized username or email address used, if known
+/// * `meta` - The client the session is issued to
+/// * `return_tokens` - Whether to return the tokens in the body instead of setting cookies
+///
+/// # Returns
+///
+/// `200 OK` with user details and session cookies (or tokens), or an error response.
+pub(crate) async fn complete_login(
+    pool: &PgPool,
+    user_id: i64,
+    identifier: Option<&str>,
+    meta: SessionMeta,
+    return_tokens: bool,
+) -> Response {
+    // Look at the failures before this login becomes the latest success
+    let security_notice = security_notice(pool, user_id).await;
+    login_attempts::record(pool, Some(user_id), identifier, &meta, Outcome::Succeeded).await;
+
+    // Start a session and generate its tokens
+    let tokens = match issue_session(pool, user_id, meta).await {
+        Ok(tokens) => tokens,
         Err(resp) => return resp,
     };
 
-    tracing::debug!("Setting session cookie for user.");
+    tracing::debug!("Setting session cookies for user.");
+
+    if return_tokens {
+        let resp = ApiAuthLoginResponse {
+            ok: true,
+            message: "Login successful".to_string(),
+            id: Some(user_id),
+            tokens: Some(tokens.into()),
+            mfa_token: None,
+            security_notice,
+        };
+        return (StatusCode::OK, Json(resp)).into_response();
+    }
 
     let resp = ApiAuthLoginResponse {
         ok: true,
         message: "Login successful".to_string(),
-        id: Some(user.id),
+        id: Some(user_id),
+        tokens: None,
+        mfa_token: None,
+        security_notice,
     };
     let mut resp = (StatusCode::OK, Json(resp)).into_response();
-    resp.headers_mut().insert(SET_COOKIE, cookie);
+    if let Err(resp) = tokens.set_cookies(&mut resp) {
+        return resp;
+    }
 
     resp
 }
+
+/// Builds the notice about the failed attempts to log into the account since
+/// its previous successful login.
+///
+/// Must be called before the current login is recorded as successful.
+///
+/// # Returns
+///
+/// The notice if there were failed attempts, `None` otherwise or if they
+/// could not be counted.
+pub(crate) async fn security_notice(pool: &PgPool, user_id: i64) -> Option<ApiLoginSecurityNotice> {
+    let failed = match failed_since_last_login(pool, user_id).await {
+        Ok(failed) => failed?,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to count failed logins since the last login.");
+            return None;
+        }
+    };
+
+    Some(ApiLoginSecurityNotice {
+        message: match failed.count {
+            1 => "There was 1 failed attempt to log into your account since your last login. \
+                  If it wasn't you, consider changing your password."
+                .to_string(),
+            count => format!(
+                "There were {count} failed attempts to log into your account since your last \
+                 login. If they weren't you, consider changing your password."
+            ),
+        },
+        failed_attempts: failed.count,
+        last_attempt_at: failed
+            .last_at
+            .format(&Rfc3339)
+            .unwrap_or("Wasn't able to format timestamp".to_string()),
+        last_ip_address: failed.last_ip_address,
+    })
+}
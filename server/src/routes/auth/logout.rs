@@ -0,0 +1,68 @@
+//! User logout endpoint handler.
+//!
+//! Handles ending the current session and clearing the session cookies.
+
+use crate::sessions::clear_cookies;
+use api_types::auth::logout::ApiAuthLogoutResponse;
+use axum::Json;
+use axum::extract::{Extension, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::errors::error_response;
+
+/// Handles user logout requests.
+///
+/// This endpoint:
+/// 1. Revokes the session the request is authenticated with, so its access
+///    and refresh tokens are rejected from now on even if they were copied elsewhere
+/// 2. Clears the session cookies
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `session_id` - The session the request is authenticated with
+///
+/// # Returns
+///
+/// - `200 OK` with the cookies cleared on success
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, session_id))]
+pub async fn api_auth_logout_post(
+    State(pool): State<PgPool>,
+    Extension(user_id): Extension<i64>,
+    session_id: SessionId,
+) -> impl IntoResponse {
+    if let Err(e) = sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id.0,
+        user_id
+    )
+    .execute(&pool)
+    .await
+    {
+        tracing::error!(error = ?e, "Failed to revoke session on logout.");
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "An error occurred on our end while logging out",
+        );
+    }
+
+    tracing::info!(user_id, session_id = %session_id.0, "User logged out");
+
+    let resp = ApiAuthLogoutResponse {
+        ok: true,
+        message: "Logout successful".to_string(),
+    };
+    let mut resp = (StatusCode::OK, Json(resp)).into_response();
+    clear_cookies(&mut resp);
+
+    resp
+}
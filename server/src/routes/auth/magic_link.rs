@@ -0,0 +1,5 @@
+/// Magic link request endpoint handler.
+pub mod send;
+
+/// Magic link login endpoint handler.
+pub mod consume;
@@ -0,0 +1,110 @@
+//! Magic link login endpoint handler.
+//!
+//! Handles logging in with the token from a magic login link.
+
+use crate::routes::auth::login::{complete_login, require_second_factor};
+use crate::sessions::SessionMeta;
+use api_types::auth::magic_link::consume::ApiAuthMagicLinkConsumeRequest;
+use axum::Json;
+use axum::extract::{ConnectInfo, State};
+use axum::http::header::SET_COOKIE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use utils::cookies::{MAGIC_LINK_COOKIE, build_magic_link_removal_cookie};
+use utils::errors::error_response;
+use utils::tokens::hash_token;
+
+/// Handles magic link login requests.
+///
+/// This endpoint:
+/// 1. Marks the link as used, if it is valid, unused, unexpired, and the
+///    request carries the nonce cookie of the browser that asked for it
+/// 2. If the user enabled two-factor authentication, returns an `mfaToken`
+///    to exchange at `/api/auth/login/2fa` instead, and stops here
+/// 3. Starts a new session and sets the session cookies, or returns the
+///    tokens in the body if the client asked for them with `returnTokens`
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `addr` - The client's socket address, recorded with the session
+/// * `headers` - The request headers, whose user agent is recorded with the session
+/// * `cookies` - The request cookies, holding the nonce cookie
+/// * `req` - The request containing the token from the link
+///
+/// # Returns
+///
+/// - `200 OK` with user details and session cookies (or tokens) on success, or
+///   with an `mfaToken` if a second factor is required
+/// - `400 BAD REQUEST` if the link is invalid, expired, already used, or
+///   opened in another browser
+/// - `500 INTERNAL SERVER ERROR` if any server-side operation fails
+#[tracing::instrument(skip(pool, headers, cookies, req))]
+pub async fn api_auth_magic_link_consume_post(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Json(req): Json<ApiAuthMagicLinkConsumeRequest>,
+) -> impl IntoResponse {
+    let invalid = || {
+        error_response(
+            StatusCode::BAD_REQUEST,
+            "This login link is invalid or has expired, or was requested from another browser",
+        )
+    };
+
+    let Some(nonce) = cookies
+        .get(MAGIC_LINK_COOKIE)
+        .map(|c| c.value().to_string())
+    else {
+        return invalid();
+    };
+
+    // A link opened elsewhere stays usable from the right browser
+    let link = sqlx::query!(
+        r#"
+        UPDATE magic_links ml
+        SET used_at = NOW()
+        FROM users u
+        WHERE ml.token_hash = $1
+          AND ml.nonce_hash = $2
+          AND ml.used_at IS NULL
+          AND ml.expires_at > NOW()
+          AND u.id = ml.user_id
+        RETURNING ml.user_id, u.totp_enabled_at IS NOT NULL AS "totp_enabled!"
+        "#,
+        hash_token(&req.token),
+        hash_token(&nonce)
+    )
+    .fetch_optional(&pool)
+    .await;
+
+    let link = match link {
+        Ok(Some(link)) => link,
+        Ok(None) => return invalid(),
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to redeem magic link.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while logging you in",
+            );
+        }
+    };
+
+    let meta = SessionMeta::from_request(&headers, addr);
+    let mut resp = if link.totp_enabled {
+        require_second_factor(&pool, link.user_id, None, &meta).await
+    } else {
+        complete_login(&pool, link.user_id, None, meta, req.return_tokens).await
+    };
+
+    if resp.status().is_success() {
+        resp.headers_mut()
+            .append(SET_COOKIE, build_magic_link_removal_cookie());
+    }
+    resp
+}
@@ -0,0 +1,146 @@
+//! Magic link request endpoint handler.
+//!
+//! Handles emailing passwordless login links.
+
+use crate::mailer::{Email, SharedMailer, send_in_background};
+use crate::setup::public_url;
+use api_types::auth::magic_link::send::{ApiAuthMagicLinkRequest, ApiAuthMagicLinkResponse};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::http::header::SET_COOKIE;
+use axum::response::IntoResponse;
+use sqlx::PgPool;
+use utils::cookies::{MAGIC_LINK_DURATION, build_magic_link_cookie};
+use utils::errors::error_response;
+use utils::tokens::{generate_token, hash_token};
+
+/// Handles requests for a magic login link.
+///
+/// This endpoint:
+/// 1. Looks up the account using the email address
+/// 2. Invalidates the account's outstanding links and stores the hashes of a
+///    new token and of a nonce
+/// 3. Emails a link carrying the token, valid for 15 minutes
+/// 4. Sets the nonce cookie, so that the link only logs in from this browser
+///
+/// The response is the same whether or not an account uses the email address,
+/// so that the endpoint can't be used to find out who has an account.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `mailer` - The mailer delivering the link
+/// * `req` - The request containing the email address
+///
+/// # Returns
+///
+/// - `200 OK` whether or not an account uses the email address
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+///
+/// # Example Request
+///
+/// ```json
+/// {
+///   "email": "john@example.com"
+/// }
+/// ```
+#[tracing::instrument(skip(pool, mailer, req))]
+pub async fn api_auth_magic_link_post(
+    State(pool): State<PgPool>,
+    State(mailer): State<SharedMailer>,
+    Json(req): Json<ApiAuthMagicLinkRequest>,
+) -> impl IntoResponse {
+    let email = req.email.trim().to_string();
+    let token = generate_token();
+    let nonce = generate_token();
+    let expires_at = time::OffsetDateTime::now_utc() + MAGIC_LINK_DURATION;
+
+    let result: Result<Option<i64>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        let Some(user_id) = sqlx::query_scalar!("SELECT id FROM users WHERE email = $1", email)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        // Only the latest link works
+        sqlx::query!(
+            "DELETE FROM magic_links WHERE user_id = $1 AND used_at IS NULL",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO magic_links (user_id, token_hash, nonce_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            user_id,
+            hash_token(&token),
+            hash_token(&nonce),
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+    .await;
+
+    match result {
+        Ok(Some(user_id)) => {
+            let link = format!("{}/magic-link?token={}", public_url(), token);
+            send_in_background(
+                &mailer,
+                Email {
+                    to: email,
+                    subject: "Your login link".to_string(),
+                    body: format!(
+                        "Someone asked for a link to log into your account.\n\n\
+                         Open this link within {} minutes, in the browser you asked for it \
+                         from, to log in:\n{}\n\n\
+                         If it wasn't you, you can ignore this email.\n",
+                        MAGIC_LINK_DURATION.whole_minutes(),
+                        link
+                    ),
+                },
+            );
+            tracing::info!(user_id, "Magic link requested");
+        }
+        Ok(None) => {
+            tracing::info!("Magic link requested for unknown email");
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to create magic link.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while processing your request",
+            );
+        }
+    }
+
+    // Set even for unknown addresses, so the response doesn't tell them apart
+    let cookie = match build_magic_link_cookie(nonce) {
+        Ok(cookie) => cookie,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to build cookie.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("An error occurred on our end: {}", e),
+            );
+        }
+    };
+
+    let resp = ApiAuthMagicLinkResponse {
+        ok: true,
+        message: "If an account uses this email address, a login link was sent to it".to_string(),
+    };
+    let mut resp = (StatusCode::OK, Json(resp)).into_response();
+    resp.headers_mut().append(SET_COOKIE, cookie);
+    resp
+}
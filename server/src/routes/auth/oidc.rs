@@ -0,0 +1,11 @@
+/// Identity provider listing endpoint handler.
+pub mod providers;
+
+/// Login start endpoint handler.
+pub mod start;
+
+/// Provider redirect endpoint handler.
+pub mod callback;
+
+/// Single sign-on signup endpoint handler.
+pub mod signup;
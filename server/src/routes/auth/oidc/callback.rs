@@ -0,0 +1,297 @@
+//! Provider redirect endpoint handler.
+//!
+//! Finishes logins at identity providers: logs in users whose identity is
+//! linked, links identities to signed-in users who asked for it, and sends
+//! new users to pick a username.
+
+use crate::login_attempts::{self, Outcome};
+use crate::oidc::{
+    OidcError, OidcProvider, OidcProviders, ProviderIdentity, create_signup, finish_login,
+    link_identity, suggest_username,
+};
+use crate::routes::auth::login::security_notice;
+use crate::sessions::{SessionMeta, issue_session};
+use crate::setup::public_url;
+use crate::two_factor::create_challenge;
+use api_types::auth::MAX_USERNAME_LENGTH;
+use api_types::auth::oidc::callback::ApiAuthOidcCallbackQuery;
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::header::SET_COOKIE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_extra::extract::CookieJar;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use utils::cookies::{OIDC_STATE_COOKIE, build_oidc_state_removal_cookie};
+use utils::errors::error_response;
+
+/// Page users land on once logged in.
+const LOGGED_IN_PATH: &str = "/";
+
+/// Page asking for the second factor, given the challenge token in the fragment.
+const TWO_FACTOR_PATH: &str = "/login/2fa";
+
+/// Page where new users pick a username, given the signup token in the fragment.
+const SIGNUP_PATH: &str = "/signup/sso";
+
+/// Page users return to after linking an identity.
+const LINKED_PATH: &str = "/settings";
+
+/// Handles the redirect back from an identity provider.
+///
+/// This endpoint:
+/// 1. Checks that the `state` matches this browser's cookie, and removes the cookie
+/// 2. Exchanges the authorization code and verifies the ID token
+/// 3. If a signed-in user started the login, links the identity to them
+///    and redirects to the settings page
+/// 4. If the identity is linked to a user, logs them in: redirects to the
+///    second factor page with an `mfaToken` if they enabled two-factor
+///    authentication, or sets the session cookies and redirects to the app,
+///    with the failed attempts to log in since the previous login if any
+/// 5. If the provider verified the email address, and it is the verified
+///    address of a user, links the identity to them and logs them in
+/// 6. Otherwise, redirects new users to the signup page with a `signupToken`
+///    and a suggested `username`
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `providers` - The configured identity providers
+/// * `addr` - The client's socket address, recorded with the session
+/// * `headers` - The request headers, whose user agent is recorded with the session
+/// * `cookies` - The request cookies, holding the state cookie
+/// * `provider` - ID of the provider redirecting back
+/// * `query` - The authorization code and state, or the provider's error
+///
+/// # Returns
+///
+/// - `303 SEE OTHER` to the web app on success
+/// - `400 BAD REQUEST` if the provider refused the login, the state doesn't
+///   match or has expired, or the provider didn't share an email address for a new user
+/// - `404 NOT FOUND` if no provider is configured with the ID
+/// - `409 CONFLICT` if the identity is linked to another user, or a new
+///   user's email address is used by an account the identity can't be linked to
+/// - `500 INTERNAL SERVER ERROR` if any server-side operation fails
+/// - `502 BAD GATEWAY` if the provider could not complete the login
+#[tracing::instrument(skip(pool, providers, headers, cookies, query))]
+pub async fn api_auth_oidc_callback_get(
+    State(pool): State<PgPool>,
+    State(providers): State<OidcProviders>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(provider): Path<String>,
+    Query(query): Query<ApiAuthOidcCallbackQuery>,
+) -> impl IntoResponse {
+    let meta = SessionMeta::from_request(&headers, addr);
+    let state_cookie = cookies.get(OIDC_STATE_COOKIE).map(|c| c.value());
+
+    let mut resp = callback(&pool, &providers, &provider, query, state_cookie, meta).await;
+
+    // The state is single-use, whatever the outcome
+    resp.headers_mut()
+        .append(SET_COOKIE, build_oidc_state_removal_cookie());
+    resp
+}
+
+/// Finishes the login and builds the response, see [`api_auth_oidc_callback_get`].
+async fn callback(
+    pool: &PgPool,
+    providers: &OidcProviders,
+    provider: &str,
+    query: ApiAuthOidcCallbackQuery,
+    state_cookie: Option<&str>,
+    meta: SessionMeta,
+) -> Response {
+    let provider = match providers.get(provider) {
+        Ok(provider) => provider,
+        Err(e) => return e.into_response(),
+    };
+
+    if let Some(error) = query.error {
+        tracing::info!(
+            error,
+            provider = provider.id,
+            "Identity provider refused the login"
+        );
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "{} refused the login: {}",
+                provider.name,
+                query.error_description.unwrap_or(error)
+            ),
+        );
+    }
+
+    // Only the browser that started the login may finish it
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return OidcError::InvalidState.into_response();
+    };
+    if state_cookie != Some(state.as_str()) {
+        return OidcError::InvalidState.into_response();
+    }
+
+    let login = match finish_login(pool, providers, provider, code, &state).await {
+        Ok(login) => login,
+        Err(e) => return e.into_response(),
+    };
+    let identity = login.identity;
+
+    if let Some(user_id) = login.link_user_id {
+        return link(pool, provider, user_id, &identity).await;
+    }
+
+    // Returning users
+    let linked = sqlx::query!(
+        r#"
+        UPDATE user_identities ui
+        SET last_login_at = NOW(), email = COALESCE($3, ui.email)
+        FROM users u
+        WHERE ui.issuer = $1 AND ui.subject = $2 AND u.id = ui.user_id
+        RETURNING ui.user_id, u.totp_enabled_at IS NOT NULL AS "totp_enabled!"
+        "#,
+        identity.issuer,
+        identity.subject,
+        identity.email
+    )
+    .fetch_optional(pool)
+    .await;
+
+    match linked {
+        Ok(Some(user)) => return log_in(pool, user.user_id, user.totp_enabled, meta).await,
+        Ok(None) => {}
+        Err(e) => return OidcError::from(e).into_response(),
+    }
+
+    let Some(email) = identity.email.as_deref() else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "{} didn't share your email address, which is needed to create an account",
+                provider.name
+            ),
+        );
+    };
+
+    let existing = sqlx::query!(
+        r#"
+        SELECT id, email_verified_at IS NOT NULL AS "email_verified!",
+               totp_enabled_at IS NOT NULL AS "totp_enabled!"
+        FROM users
+        WHERE email = $1
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await;
+
+    match existing {
+        // Both sides proved ownership of the address, so it's the same person
+        Ok(Some(user)) if user.email_verified && identity.email_verified => {
+            let linked = match pool.acquire().await {
+                Ok(mut conn) => link_identity(&mut conn, user.id, &identity).await,
+                Err(e) => Err(e),
+            };
+            match linked {
+                Ok(_) => {
+                    tracing::info!(
+                        user_id = user.id,
+                        provider = provider.id,
+                        "Linked identity by email"
+                    );
+                    log_in(pool, user.id, user.totp_enabled, meta).await
+                }
+                Err(e) => OidcError::from(e).into_response(),
+            }
+        }
+        Ok(Some(_)) => error_response(
+            StatusCode::CONFLICT,
+            format!(
+                "An account already uses this email address. Log in with your password \
+                 and link {} in your settings.",
+                provider.name
+            ),
+        ),
+        Ok(None) => match create_signup(pool, &identity, email).await {
+            Ok(token) => Redirect::to(&format!(
+                "{}{SIGNUP_PATH}#signupToken={token}&username={}",
+                public_url(),
+                suggest_username(&identity, MAX_USERNAME_LENGTH)
+            ))
+            .into_response(),
+            Err(e) => OidcError::from(e).into_response(),
+        },
+        Err(e) => OidcError::from(e).into_response(),
+    }
+}
+
+/// Links the identity to the signed-in user who started the login.
+async fn link(
+    pool: &PgPool,
+    provider: &OidcProvider,
+    user_id: i64,
+    identity: &ProviderIdentity,
+) -> Response {
+    let owner = match pool.acquire().await {
+        Ok(mut conn) => link_identity(&mut conn, user_id, identity).await,
+        Err(e) => Err(e),
+    };
+
+    match owner {
+        Ok(owner) if owner == user_id => {
+            tracing::info!(user_id, provider = provider.id, "Linked identity");
+            Redirect::to(&format!("{}{LINKED_PATH}", public_url())).into_response()
+        }
+        Ok(_) => error_response(
+            StatusCode::CONFLICT,
+            format!(
+                "This {} account is already linked to another user",
+                provider.name
+            ),
+        ),
+        Err(e) => OidcError::from(e).into_response(),
+    }
+}
+
+/// Logs the user in, or holds the session back for the second factor.
+async fn log_in(pool: &PgPool, user_id: i64, totp_enabled: bool, meta: SessionMeta) -> Response {
+    if totp_enabled {
+        let mfa_token = match create_challenge(pool, user_id).await {
+            Ok(token) => token,
+            Err(e) => return OidcError::from(e).into_response(),
+        };
+        login_attempts::record(pool, Some(user_id), None, &meta, Outcome::MfaRequired).await;
+
+        return Redirect::to(&format!(
+            "{}{TWO_FACTOR_PATH}#mfaToken={mfa_token}",
+            public_url()
+        ))
+        .into_response();
+    }
+
+    // Look at the failures before this login becomes the latest success
+    let notice = security_notice(pool, user_id).await;
+    login_attempts::record(pool, Some(user_id), None, &meta, Outcome::Succeeded).await;
+    let tokens = match issue_session(pool, user_id, meta).await {
+        Ok(tokens) => tokens,
+        Err(resp) => return resp,
+    };
+
+    let mut location = format!("{}{LOGGED_IN_PATH}", public_url());
+    if let Some(notice) = notice {
+        location.push_str(&format!(
+            "#failedAttempts={}&lastAttemptAt={}",
+            notice.failed_attempts, notice.last_attempt_at
+        ));
+        if let Some(ip_address) = notice.last_ip_address {
+            location.push_str(&format!("&lastIpAddress={ip_address}"));
+        }
+    }
+
+    let mut resp = Redirect::to(&location).into_response();
+    if let Err(resp) = tokens.set_cookies(&mut resp) {
+        return resp;
+    }
+    resp
+}
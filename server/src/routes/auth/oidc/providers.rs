@@ -0,0 +1,39 @@
+//! Identity provider listing endpoint handler.
+//!
+//! Lists the identity providers users can log in with, so that clients can
+//! show a button for each.
+
+use crate::oidc::OidcProviders;
+use api_types::auth::oidc::providers::{ApiAuthOidcProvidersResponse, ApiOidcProvider};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+
+/// Handles identity provider listing requests.
+///
+/// # Arguments
+///
+/// * `providers` - The configured identity providers
+///
+/// # Returns
+///
+/// `200 OK` with the configured providers, possibly none.
+#[tracing::instrument(skip(providers))]
+pub async fn api_auth_oidc_providers_get(
+    State(providers): State<OidcProviders>,
+) -> impl IntoResponse {
+    let providers = providers
+        .list()
+        .iter()
+        .map(|provider| ApiOidcProvider {
+            id: provider.id.clone(),
+            name: provider.name.clone(),
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(ApiAuthOidcProvidersResponse { providers }),
+    )
+}
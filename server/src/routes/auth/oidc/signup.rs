@@ -0,0 +1,198 @@
+//! Single sign-on signup endpoint handler.
+//!
+//! Creates the account of a user who logged in with an identity provider for
+//! the first time, once they picked a username.
+
+use crate::login_attempts::{self, Outcome};
+use crate::mailer::SharedMailer;
+use crate::oidc::{ProviderIdentity, link_identity};
+use crate::sessions::{SessionMeta, issue_session};
+use crate::verification::start_verification;
+use api_types::auth::oidc::signup::ApiAuthOidcSignupRequest;
+use api_types::auth::register::ApiRegisterResponse;
+use axum::Json;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use utils::errors::error_response;
+use utils::hashing;
+use utils::tokens::{generate_token, hash_token};
+
+/// Errors returned while creating the account.
+enum SignupError {
+    /// The signup token is unknown, used or expired.
+    InvalidToken,
+    /// The username or email address was taken in the meantime.
+    Taken,
+    /// A database operation failed.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for SignupError {
+    fn from(e: sqlx::Error) -> Self {
+        match e.as_database_error() {
+            Some(db) if db.is_unique_violation() => SignupError::Taken,
+            _ => SignupError::Database(e),
+        }
+    }
+}
+
+/// Handles single sign-on signup requests.
+///
+/// This endpoint:
+/// 1. Validates the username
+/// 2. Redeems the signup token (which can only be used once)
+/// 3. Creates the user with an unusable random password, which they can
+///    replace through the forgotten password flow, and links the identity
+/// 4. Marks the email address as verified if the provider verified it, or
+///    emails a link to verify it
+/// 5. Starts a session and sets the session cookies, or returns the tokens in
+///    the body if the client asked for them with `returnTokens`
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `mailer` - The mailer delivering the verification link
+/// * `addr` - The client's socket address, recorded with the session
+/// * `headers` - The request headers, whose user agent is recorded with the session
+/// * `req` - The signup token and the chosen username
+///
+/// # Returns
+///
+/// - `201 CREATED` with user details and session cookies (or tokens) on success
+/// - `400 BAD REQUEST` if the username is invalid, or the token is invalid or expired
+/// - `409 CONFLICT` if the username or email address is already used
+/// - `500 INTERNAL SERVER ERROR` if any server-side operation fails
+#[tracing::instrument(skip(pool, mailer, headers, req))]
+pub async fn api_auth_oidc_signup_post(
+    State(pool): State<PgPool>,
+    State(mailer): State<SharedMailer>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<ApiAuthOidcSignupRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = req.validate() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("Your request was invalid: {}", e),
+        );
+    }
+
+    let hashed = match hashing::hash_password(generate_token()) {
+        Ok(h) => h,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to hash password, for a single sign-on signup.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("An error occurred on our end: {}", e),
+            );
+        }
+    };
+
+    let result: Result<_, SignupError> = async {
+        let mut tx = pool.begin().await?;
+
+        let signup = sqlx::query!(
+            r#"
+            DELETE FROM oidc_signups
+            WHERE token_hash = $1 AND expires_at > NOW()
+            RETURNING issuer, subject, email, email_verified
+            "#,
+            hash_token(&req.signup_token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(SignupError::InvalidToken)?;
+
+        let user_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO users (username, email, password_hash, bio, email_verified_at)
+            VALUES ($1, $2, $3, '', CASE WHEN $4 THEN NOW() END)
+            RETURNING id
+            "#,
+            req.username,
+            signup.email,
+            hashed,
+            signup.email_verified
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let identity = ProviderIdentity {
+            issuer: signup.issuer,
+            subject: signup.subject,
+            email: Some(signup.email.clone()),
+            email_verified: signup.email_verified,
+            preferred_username: None,
+        };
+        if link_identity(&mut tx, user_id, &identity).await? != user_id {
+            return Err(SignupError::Taken);
+        }
+
+        tx.commit().await?;
+        Ok((user_id, signup.email, signup.email_verified))
+    }
+    .await;
+
+    let (user_id, email, email_verified) = match result {
+        Ok(created) => created,
+        Err(SignupError::InvalidToken) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "This signup is invalid or has expired, please log in again",
+            );
+        }
+        Err(SignupError::Taken) => {
+            return error_response(
+                StatusCode::CONFLICT,
+                "Username or email address already exists",
+            );
+        }
+        Err(SignupError::Database(e)) => {
+            tracing::error!(error = ?e, "Failed to create single sign-on user.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while creating your account",
+            );
+        }
+    };
+
+    tracing::info!(user_id, "Created user through single sign-on");
+
+    // The account works without it, and the link can be requested again
+    if !email_verified && let Err(e) = start_verification(&pool, &mailer, user_id, &email).await {
+        tracing::error!(error = ?e, "Failed to create email verification for new user.");
+    }
+
+    let meta = SessionMeta::from_request(&headers, addr);
+    login_attempts::record(&pool, Some(user_id), None, &meta, Outcome::Succeeded).await;
+    let tokens = match issue_session(&pool, user_id, meta).await {
+        Ok(tokens) => tokens,
+        Err(resp) => return resp,
+    };
+
+    if req.return_tokens {
+        let resp = ApiRegisterResponse {
+            ok: true,
+            message: "User successfully created.".to_string(),
+            id: Some(user_id),
+            tokens: Some(tokens.into()),
+        };
+        return (StatusCode::CREATED, Json(resp)).into_response();
+    }
+
+    let resp = ApiRegisterResponse {
+        ok: true,
+        message: "User successfully created.".to_string(),
+        id: Some(user_id),
+        tokens: None,
+    };
+    let mut resp = (StatusCode::CREATED, Json(resp)).into_response();
+    if let Err(resp) = tokens.set_cookies(&mut resp) {
+        return resp;
+    }
+
+    resp
+}
@@ -0,0 +1,65 @@
+//! Login start endpoint handler.
+//!
+//! Sends the browser to the identity provider to log in.
+
+use crate::oidc::{OidcProviders, start_login};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::http::header::SET_COOKIE;
+use axum::response::{IntoResponse, Redirect};
+use sqlx::PgPool;
+use utils::cookies::build_oidc_state_cookie;
+use utils::errors::error_response;
+
+/// Handles login start requests.
+///
+/// This endpoint:
+/// 1. Stores a new login with its PKCE verifier and nonce
+/// 2. Sets the state cookie binding the login to this browser
+/// 3. Redirects the browser to the provider's authorization endpoint
+///
+/// Browsers are meant to navigate to this endpoint rather than fetch it.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `providers` - The configured identity providers
+/// * `provider` - ID of the provider to log in with
+///
+/// # Returns
+///
+/// - `303 SEE OTHER` to the provider on success
+/// - `404 NOT FOUND` if no provider is configured with the ID
+/// - `500 INTERNAL SERVER ERROR` if the login could not be stored
+/// - `502 BAD GATEWAY` if the provider could not be discovered
+#[tracing::instrument(skip(pool, providers))]
+pub async fn api_auth_oidc_start_get(
+    State(pool): State<PgPool>,
+    State(providers): State<OidcProviders>,
+    Path(provider): Path<String>,
+) -> impl IntoResponse {
+    let provider = match providers.get(&provider) {
+        Ok(provider) => provider,
+        Err(e) => return e.into_response(),
+    };
+
+    let login = match start_login(&pool, &providers, provider, None).await {
+        Ok(login) => login,
+        Err(e) => return e.into_response(),
+    };
+
+    let cookie = match build_oidc_state_cookie(login.state) {
+        Ok(cookie) => cookie,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to build cookie.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("An error occurred on our end: {}", e),
+            );
+        }
+    };
+
+    let mut resp = Redirect::to(&login.authorize_url).into_response();
+    resp.headers_mut().append(SET_COOKIE, cookie);
+    resp
+}
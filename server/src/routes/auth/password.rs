@@ -0,0 +1,5 @@
+/// Forgotten password endpoint handler.
+pub mod forgot;
+
+/// Password reset endpoint handler.
+pub mod reset;
@@ -0,0 +1,132 @@
+//! Forgotten password endpoint handler.
+//!
+//! Handles emailing password reset links.
+
+use crate::mailer::{Email, SharedMailer, send_in_background};
+use crate::setup::public_url;
+use api_types::auth::password::forgot::{
+    ApiAuthPasswordForgotRequest, ApiAuthPasswordForgotResponse,
+};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use sqlx::PgPool;
+use time::Duration;
+use utils::errors::error_response;
+use utils::tokens::{generate_token, hash_token};
+
+/// How long a password reset link stays valid.
+pub(crate) const RESET_TOKEN_DURATION: Duration = Duration::hours(1);
+
+/// Handles requests for a password reset email.
+///
+/// This endpoint:
+/// 1. Looks up the account using the email address
+/// 2. Invalidates the account's outstanding reset tokens and stores the hash of a new one
+/// 3. Emails a link carrying the token, valid for an hour
+///
+/// The response is the same whether or not an account uses the email address,
+/// so that the endpoint can't be used to find out who has an account.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `mailer` - The mailer delivering the reset link
+/// * `req` - The request containing the email address
+///
+/// # Returns
+///
+/// - `200 OK` whether or not an account uses the email address
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+///
+/// # Example Request
+///
+/// ```json
+/// {
+///   "email": "john@example.com"
+/// }
+/// ```
+#[tracing::instrument(skip(pool, mailer, req))]
+pub async fn api_auth_password_forgot_post(
+    State(pool): State<PgPool>,
+    State(mailer): State<SharedMailer>,
+    Json(req): Json<ApiAuthPasswordForgotRequest>,
+) -> impl IntoResponse {
+    let email = req.email.trim().to_string();
+    let token = generate_token();
+    let expires_at = time::OffsetDateTime::now_utc() + RESET_TOKEN_DURATION;
+
+    let result: Result<Option<i64>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        let Some(user_id) = sqlx::query_scalar!("SELECT id FROM users WHERE email = $1", email)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        // Only the latest link works
+        sqlx::query!(
+            "DELETE FROM password_resets WHERE user_id = $1 AND used_at IS NULL",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO password_resets (user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            hash_token(&token),
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+    .await;
+
+    match result {
+        Ok(Some(user_id)) => {
+            let link = format!("{}/reset-password?token={}", public_url(), token);
+            send_in_background(
+                &mailer,
+                Email {
+                    to: email,
+                    subject: "Reset your password".to_string(),
+                    body: format!(
+                        "Someone asked to reset the password of your account.\n\n\
+                         Open this link within {} minutes to choose a new password:\n{}\n\n\
+                         If it wasn't you, you can ignore this email.\n",
+                        RESET_TOKEN_DURATION.whole_minutes(),
+                        link
+                    ),
+                },
+            );
+            tracing::info!(user_id, "Password reset requested");
+        }
+        Ok(None) => {
+            tracing::info!("Password reset requested for unknown email");
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to create password reset token.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while processing your request",
+            );
+        }
+    }
+
+    let resp = ApiAuthPasswordForgotResponse {
+        ok: true,
+        message: "If an account uses this email address, a password reset link was sent to it"
+            .to_string(),
+    };
+    (StatusCode::OK, Json(resp)).into_response()
+}
@@ -0,0 +1,176 @@
+//! Password reset endpoint handler.
+//!
+//! Handles setting a new password with a token from a password reset email.
+
+use crate::login_attempts::{self, Outcome};
+use crate::sessions::{SessionMeta, revoke_all_sessions};
+use api_types::auth::ApiPasswordPolicyErrorResponse;
+use api_types::auth::password::reset::{ApiAuthPasswordResetRequest, ApiAuthPasswordResetResponse};
+use axum::Json;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use utils::errors::error_response;
+use utils::hashing;
+use utils::password_policy::{PasswordContext, PasswordViolations, check_password};
+use utils::tokens::hash_token;
+
+/// Errors returned when redeeming a password reset token.
+enum ResetError {
+    /// The token is unknown, expired or already used.
+    InvalidToken,
+    /// The new password breaks the password policy.
+    Unsuitable(PasswordViolations),
+    /// Hashing the new password failed, with the hashing error.
+    Hashing(String),
+    /// A database operation failed.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for ResetError {
+    fn from(e: sqlx::Error) -> Self {
+        ResetError::Database(e)
+    }
+}
+
+/// Handles password reset requests.
+///
+/// This endpoint:
+/// 1. Redeems the reset token, which can only be used once
+/// 2. Checks the new password against the password policy
+/// 3. Stores the hash of the new password
+/// 4. Revokes every session of the user, so that whoever knew the old password is signed out
+/// 5. Records the reset in the login audit log, which lifts a login lockout
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `addr` - The client's socket address, recorded in the login audit log
+/// * `headers` - The request headers, whose user agent is recorded in the login audit log
+/// * `req` - The request containing the reset token and the new password
+///
+/// # Returns
+///
+/// - `200 OK` if the password was reset
+/// - `400 BAD REQUEST` if the token is invalid, expired or already used, or with the
+///   violations if the new password breaks the password policy
+/// - `500 INTERNAL SERVER ERROR` if hashing or database operation fails
+///
+/// # Example Request
+///
+/// ```json
+/// {
+///   "token": "7f3c...",
+///   "newPassword": "NewSecurePass123!"
+/// }
+/// ```
+#[tracing::instrument(skip(pool, headers, req))]
+pub async fn api_auth_password_reset_post(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<ApiAuthPasswordResetRequest>,
+) -> impl IntoResponse {
+    let result: Result<(i64, u64), ResetError> = async {
+        let mut tx = pool.begin().await?;
+
+        // Lock the token so that concurrent resets with it are serialized
+        let Some(reset) = sqlx::query!(
+            r#"
+            SELECT pr.id, pr.user_id, u.username, u.email
+            FROM password_resets pr
+            JOIN users u ON u.id = pr.user_id
+            WHERE pr.token_hash = $1 AND pr.used_at IS NULL AND pr.expires_at > NOW()
+            FOR UPDATE OF pr
+            "#,
+            hash_token(&req.token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Err(ResetError::InvalidToken);
+        };
+
+        let context = PasswordContext {
+            username: Some(&reset.username),
+            email: Some(&reset.email),
+        };
+        check_password(&req.new_password, context)
+            .await
+            .map_err(ResetError::Unsuitable)?;
+        let password_hash = hashing::hash_password(&req.new_password)
+            .map_err(|e| ResetError::Hashing(e.to_string()))?;
+
+        sqlx::query!(
+            "UPDATE users SET password_hash = $2, updated_at = NOW() WHERE id = $1",
+            reset.user_id,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE password_resets SET used_at = NOW() WHERE id = $1",
+            reset.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM password_resets WHERE user_id = $1 AND used_at IS NULL",
+            reset.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let revoked = revoke_all_sessions(&mut tx, reset.user_id).await?;
+
+        tx.commit().await?;
+        Ok((reset.user_id, revoked))
+    }
+    .await;
+
+    match result {
+        Ok((user_id, revoked)) => {
+            tracing::info!(user_id, revoked, "Password reset");
+            let meta = SessionMeta::from_request(&headers, addr);
+            login_attempts::record(&pool, Some(user_id), None, &meta, Outcome::PasswordReset).await;
+            let resp = ApiAuthPasswordResetResponse {
+                ok: true,
+                message: "Your password was reset, please log in again".to_string(),
+            };
+            (StatusCode::OK, Json(resp)).into_response()
+        }
+        Err(ResetError::InvalidToken) => {
+            tracing::info!("Password reset attempt with invalid token");
+            error_response(
+                StatusCode::BAD_REQUEST,
+                "This password reset link is invalid or has expired",
+            )
+        }
+        Err(ResetError::Unsuitable(violations)) => {
+            tracing::info!(%violations, "New password breaks the password policy");
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiPasswordPolicyErrorResponse::from(violations)),
+            )
+                .into_response()
+        }
+        Err(ResetError::Hashing(e)) => {
+            tracing::error!(error = %e, "Failed to hash password.");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("An error occurred on our end: {}", e),
+            )
+        }
+        Err(ResetError::Database(e)) => {
+            tracing::error!(error = ?e, "Failed to reset password.");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while resetting your password",
+            )
+        }
+    }
+}
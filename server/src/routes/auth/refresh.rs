@@ -0,0 +1,111 @@
+//! Session refresh endpoint handler.
+//!
+//! Handles exchanging a refresh token for a new access token and a new
+//! refresh token.
+
+use crate::sessions::{RefreshError, SessionTokens, clear_cookies, refresh_session};
+use api_types::auth::refresh::{ApiAuthRefreshRequest, ApiAuthRefreshResponse};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use sqlx::PgPool;
+use utils::cookies::REFRESH_COOKIE;
+use utils::errors::error_response;
+
+/// Handles session refresh requests.
+///
+/// This endpoint:
+/// 1. Extracts the refresh token from the request body, or else from the `refresh_token` cookie
+/// 2. Exchanges it for a new refresh token, extending the session
+/// 3. Signs a new access token for the session
+/// 4. Returns both tokens in the body if the refresh token came from the body,
+///    or sets both cookies otherwise
+///
+/// A refresh token can only be used once. Presenting a token that was already
+/// exchanged revokes the whole session, since either the client or a thief
+/// holds a copy of it.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `cookies` - The request cookies
+/// * `payload` - The optional request body carrying the refresh token
+///
+/// # Returns
+///
+/// - `200 OK` with new session cookies (or tokens) on success
+/// - `401 UNAUTHORIZED` if the refresh token is missing, invalid, reused, or its session ended
+/// - `500 INTERNAL SERVER ERROR` if any server-side operation fails
+#[tracing::instrument(skip(pool, cookies, payload))]
+pub async fn api_auth_refresh_post(
+    State(pool): State<PgPool>,
+    cookies: CookieJar,
+    payload: Option<Json<ApiAuthRefreshRequest>>,
+) -> impl IntoResponse {
+    let body_token = payload.and_then(|Json(payload)| payload.refresh_token);
+    let return_tokens = body_token.is_some();
+
+    let Some(refresh_token) =
+        body_token.or_else(|| cookies.get(REFRESH_COOKIE).map(|c| c.value().to_string()))
+    else {
+        tracing::info!("Refresh attempt without refresh token");
+        return error_response(StatusCode::UNAUTHORIZED, "No refresh token provided");
+    };
+
+    let rotated = match refresh_session(&pool, &refresh_token).await {
+        Ok(rotated) => rotated,
+        Err(RefreshError::Invalid) => {
+            tracing::info!("Refresh attempt with invalid refresh token");
+            let mut resp =
+                error_response(StatusCode::UNAUTHORIZED, "Invalid or expired refresh token");
+            clear_cookies(&mut resp);
+            return resp;
+        }
+        Err(RefreshError::Reused) => {
+            let mut resp = error_response(
+                StatusCode::UNAUTHORIZED,
+                "Refresh token was already used. The session has been revoked.",
+            );
+            clear_cookies(&mut resp);
+            return resp;
+        }
+        Err(RefreshError::Database(e)) => {
+            tracing::error!(error = ?e, "Failed to refresh session.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while refreshing your session",
+            );
+        }
+    };
+
+    let tokens =
+        match SessionTokens::new(rotated.user_id, rotated.session_id, rotated.refresh_token) {
+            Ok(tokens) => tokens,
+            Err(resp) => return resp,
+        };
+
+    tracing::debug!(user_id = rotated.user_id, "Session refreshed");
+
+    if return_tokens {
+        let resp = ApiAuthRefreshResponse {
+            ok: true,
+            message: "Session refreshed".to_string(),
+            tokens: Some(tokens.into()),
+        };
+        return (StatusCode::OK, Json(resp)).into_response();
+    }
+
+    let resp = ApiAuthRefreshResponse {
+        ok: true,
+        message: "Session refreshed".to_string(),
+        tokens: None,
+    };
+    let mut resp = (StatusCode::OK, Json(resp)).into_response();
+    if let Err(resp) = tokens.set_cookies(&mut resp) {
+        return resp;
+    }
+
+    resp
+}
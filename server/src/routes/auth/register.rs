@@ -3,35 +3,46 @@
 //! Handles the creation of new user accounts with validation,
 //! password hashing, and JWT token generation.
 
+use crate::mailer::SharedMailer;
+use crate::sessions::{SessionMeta, issue_session};
+use crate::verification::start_verification;
+use api_types::auth::ApiPasswordPolicyErrorResponse;
 use api_types::auth::register::{ApiAuthRegisterRequest, ApiRegisterResponse};
 use axum::Json;
-use axum::extract::State;
-use axum::http::StatusCode;
-use axum::http::header::SET_COOKIE;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
 use sqlx::PgPool;
-use utils::cookies::create_auth_cookie;
+use std::net::SocketAddr;
 use utils::errors::error_response;
 use utils::hashing;
+use utils::password_policy::{PasswordContext, check_password};
 
 /// Handles user registration requests.
 ///
 /// This endpoint:
-/// 1. Validates the registration request (email format, password complexity)
+/// 1. Validates the registration request (username, email format), and checks
+///    the password against the password policy
 /// 2. Checks if the username or email already exists
 /// 3. Hashes the password using Argon2
 /// 4. Inserts the new user into the database
-/// 5. Generates a JWT token for the new user
-/// 6. Sets a session cookie with the JWT token
+/// 5. Emails a link to verify the email address
+/// 6. Starts a session and issues access and refresh tokens for the new user
+/// 7. Sets the access and refresh token cookies, or returns the tokens in the
+///    body if the client asked for them with `returnTokens`
 ///
 /// # Arguments
 ///
 /// * `pool` - The PostgreSQL connection pool
+/// * `mailer` - The mailer delivering the verification link
+/// * `addr` - The client's socket address, recorded with the session
+/// * `headers` - The request headers, whose user agent is recorded with the session
 /// * `req` - The registration request containing username, email, and password
 ///
 /// # Returns
 ///
-/// - `201 CREATED` with user details and session cookie on success
+/// - `201 CREATED` with user details and session cookies (or tokens) on success
+/// - `400 BAD REQUEST` with the violations if the password breaks the password policy
 /// - `401 UNAUTHORIZED` if validation fails
 /// - `409 CONFLICT` if username or email already exists
 /// - `500 INTERNAL SERVER ERROR` if any server-side operation fails
@@ -45,9 +56,12 @@ use utils::hashing;
 ///   "password": "SecurePass123"
 /// }
 /// ```
-#[tracing::instrument(skip(pool, req))]
+#[tracing::instrument(skip(pool, mailer, headers, req))]
 pub async fn api_auth_register_post(
     State(pool): State<PgPool>,
+    State(mailer): State<SharedMailer>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(req): Json<ApiAuthRegisterRequest>,
 ) -> impl IntoResponse {
     if let Err(e) = req.validate() {
@@ -58,11 +72,25 @@ pub async fn api_auth_register_post(
         );
     }
 
+    let context = PasswordContext {
+        username: Some(&req.username),
+        email: Some(&req.email),
+    };
+    if let Err(violations) = check_password(&req.password, context).await {
+        tracing::info!(%violations, "Password breaks the password policy");
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiPasswordPolicyErrorResponse::from(violations)),
+        )
+            .into_response();
+    }
+
     let ApiAuthRegisterRequest {
         username,
         email,
         password,
         bio,
+        return_tokens,
     } = req;
 
     // Convert bio to empty string if None
@@ -98,20 +126,17 @@ pub async fn api_auth_register_post(
             "Attempt to register with existing username and email",
         );
         tracing::info!("User registration failed: username and email already exist");
-        return error_response(
-            StatusCode::CONFLICT,
-            "This user already exists.".to_string(),
-        );
+        return error_response(StatusCode::CONFLICT, "This user already exists.");
     }
     if existing.username_exists {
         tracing::debug!(username, "Attempt to register with existing username",);
         tracing::info!("User registration failed: username already exists");
-        return error_response(StatusCode::CONFLICT, "Username already exists".to_string());
+        return error_response(StatusCode::CONFLICT, "Username already exists");
     }
     if existing.email_exists {
         tracing::debug!(email, "Attempt to register with existing email",);
         tracing::info!("User registration failed: email already exists");
-        return error_response(StatusCode::CONFLICT, "Email already exists".to_string());
+        return error_response(StatusCode::CONFLICT, "Email already exists");
     }
 
     let hashed = match hashing::hash_password(password) {
@@ -149,20 +174,39 @@ pub async fn api_auth_register_post(
         }
     };
 
-    let cookie = match create_auth_cookie(user.id) {
-        Ok(c) => c,
-        Err(resp) => return resp,
-    };
+    // The account works without it, and the link can be requested again
+    if let Err(e) = start_verification(&pool, &mailer, user.id, &email).await {
+        tracing::error!(error = ?e, "Failed to create email verification for new user.");
+    }
+
+    let tokens =
+        match issue_session(&pool, user.id, SessionMeta::from_request(&headers, addr)).await {
+            Ok(tokens) => tokens,
+            Err(resp) => return resp,
+        };
 
-    tracing::debug!("Setting session cookie for new user.");
+    tracing::debug!("Setting session cookies for new user.");
+
+    if return_tokens {
+        let resp = ApiRegisterResponse {
+            ok: true,
+            message: "User successfully created.".to_string(),
+            id: Some(user.id),
+            tokens: Some(tokens.into()),
+        };
+        return (StatusCode::CREATED, Json(resp)).into_response();
+    }
 
     let resp = ApiRegisterResponse {
         ok: true,
         message: "User successfully created.".to_string(),
         id: Some(user.id),
+        tokens: None,
     };
     let mut resp = (StatusCode::CREATED, Json(resp)).into_response();
-    resp.headers_mut().insert(SET_COOKIE, cookie);
+    if let Err(resp) = tokens.set_cookies(&mut resp) {
+        return resp;
+    }
 
     resp
 }
@@ -0,0 +1,5 @@
+/// List sessions endpoint handler.
+pub mod get;
+
+/// Revoke sessions endpoint handler.
+pub mod delete;
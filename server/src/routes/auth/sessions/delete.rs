@@ -0,0 +1,86 @@
+use crate::sessions::revoke_other_sessions;
+use api_types::auth::sessions::delete::{
+    ApiAuthSessionsDeleteRequest, ApiAuthSessionsDeleteResponse,
+};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::errors::error_response;
+
+/// Revokes sessions of the authenticated user on other devices.
+///
+/// Steps:
+/// 1. If a session ID is given, ensure it is not the current session.
+/// 2. Revoke that session, or every session except the current one.
+/// 3. Return the number of revoked sessions.
+///
+/// The current session is ended with `POST /api/auth/logout` instead.
+#[tracing::instrument(skip(pool, user_id, session_id), fields(target = ?payload.session_id))]
+pub async fn api_auth_sessions_delete(
+    Extension(user_id): Extension<i64>,
+    session_id: SessionId,
+    State(pool): State<PgPool>,
+    Json(payload): Json<ApiAuthSessionsDeleteRequest>,
+) -> impl IntoResponse {
+    let Some(target) = payload.session_id else {
+        return match revoke_other_sessions(&pool, user_id, session_id.0).await {
+            Ok(revoked) => {
+                tracing::info!(user_id, revoked, "Revoked all other sessions");
+                (
+                    StatusCode::OK,
+                    Json(ApiAuthSessionsDeleteResponse { revoked }),
+                )
+                    .into_response()
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to revoke other sessions");
+                error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "An error occurred while revoking sessions.",
+                )
+            }
+        };
+    };
+
+    if target == session_id.0 {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "Use /api/auth/logout to end the current session.",
+        );
+    }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = NOW()
+        WHERE id = $1
+          AND user_id = $2
+          AND revoked_at IS NULL
+          AND expires_at > NOW()
+        "#,
+        target,
+        user_id
+    )
+    .execute(&pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => {
+            error_response(StatusCode::NOT_FOUND, "Session not found.")
+        }
+        Ok(result) => {
+            tracing::info!(user_id, "Revoked session");
+            let response = ApiAuthSessionsDeleteResponse {
+                revoked: result.rows_affected(),
+            };
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to revoke session");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while revoking the session.",
+            )
+        }
+    }
+}
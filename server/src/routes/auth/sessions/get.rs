@@ -0,0 +1,99 @@
+//! List sessions endpoint handler.
+//!
+//! Handles listing the active sessions of the authenticated user.
+
+use api_types::auth::sessions::get::{ApiAuthSessionsGetResponse, SessionItem};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use utils::errors::error_response;
+use uuid::Uuid;
+
+/// Row structure for sessions from database.
+struct SessionRow {
+    id: Uuid,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    created_at: OffsetDateTime,
+    last_used_at: OffsetDateTime,
+    expires_at: OffsetDateTime,
+}
+
+/// Handles session listing requests.
+///
+/// This endpoint:
+/// 1. Extracts the user ID and session from the authentication cookie
+/// 2. Retrieves every session of the user that is neither revoked nor expired
+/// 3. Returns them ordered by `last_used_at`, most recent first, flagging the current one
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `session_id` - The session the request is authenticated with
+/// * `pool` - The PostgreSQL connection pool
+///
+/// # Returns
+///
+/// - `200 OK` with the list of sessions on success
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, session_id))]
+pub async fn api_auth_sessions_get(
+    Extension(user_id): Extension<i64>,
+    session_id: SessionId,
+    State(pool): State<PgPool>,
+) -> impl IntoResponse {
+    let result = sqlx::query_as!(
+        SessionRow,
+        r#"
+        SELECT id, user_agent, ip_address, created_at, last_used_at, expires_at
+        FROM sessions
+        WHERE user_id = $1
+          AND revoked_at IS NULL
+          AND expires_at > NOW()
+        ORDER BY last_used_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(&pool)
+    .await;
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to list sessions");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while listing sessions.",
+            );
+        }
+    };
+
+    let sessions = rows
+        .into_iter()
+        .map(|row| SessionItem {
+            id: row.id,
+            user_agent: row.user_agent,
+            ip_address: row.ip_address,
+            current: row.id == session_id.0,
+            created_at: format_timestamp(row.created_at),
+            last_used_at: format_timestamp(row.last_used_at),
+            expires_at: format_timestamp(row.expires_at),
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(ApiAuthSessionsGetResponse { sessions }),
+    )
+        .into_response()
+}
+
+/// Formats a timestamp as RFC3339 for inclusion in the response.
+#[inline(always)]
+fn format_timestamp(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339)
+        .unwrap_or("Wasn't able to format timestamp".to_string())
+}
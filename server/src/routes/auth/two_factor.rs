@@ -0,0 +1,117 @@
+//! Two-factor login step endpoint handler.
+//!
+//! Handles exchanging the token of a login that passed the password check,
+//! together with a second factor, for a session.
+
+use crate::login_attempts::{self, Outcome};
+use crate::routes::auth::login::complete_login;
+use crate::sessions::SessionMeta;
+use crate::two_factor::{claim_challenge_attempt, complete_challenge, verify_second_factor};
+use api_types::auth::two_factor::ApiAuthTwoFactorRequest;
+use axum::Json;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use utils::errors::error_response;
+
+/// Handles the second step of logging in with two-factor authentication.
+///
+/// This endpoint:
+/// 1. Looks up the challenge named by the `mfaToken` returned by `/api/auth/login`,
+///    counting the attempt
+/// 2. Verifies the TOTP code, or else consumes the recovery code
+/// 3. Consumes the challenge, so that its token cannot be used again
+/// 4. Starts a new session, like a login without two-factor authentication
+///
+/// A challenge expires after 5 minutes, and after 5 wrong codes.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `addr` - The client's socket address, recorded with the session
+/// * `headers` - The request headers, whose user agent is recorded with the session
+/// * `req` - The challenge token and second factor
+///
+/// # Returns
+///
+/// - `200 OK` with user details and session cookies (or tokens) on success
+/// - `401 UNAUTHORIZED` if the token is invalid or expired, or the code is wrong
+/// - `500 INTERNAL SERVER ERROR` if any server-side operation fails
+#[tracing::instrument(skip(pool, headers, req))]
+pub async fn api_auth_two_factor_post(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<ApiAuthTwoFactorRequest>,
+) -> impl IntoResponse {
+    let meta = SessionMeta::from_request(&headers, addr);
+
+    let challenge = match claim_challenge_attempt(&pool, &req.mfa_token).await {
+        Ok(Some(challenge)) => challenge,
+        Ok(None) => {
+            tracing::info!("Two-factor attempt with invalid or expired token");
+            return error_response(
+                StatusCode::UNAUTHORIZED,
+                "Invalid or expired two-factor token. Please log in again.",
+            );
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to look up two-factor challenge.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while logging you in",
+            );
+        }
+    };
+
+    match verify_second_factor(&pool, challenge.user_id, &challenge.totp_secret, &req.code).await {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::info!(
+                user_id = challenge.user_id,
+                "Login attempt with invalid two-factor code"
+            );
+            login_attempts::record(
+                &pool,
+                Some(challenge.user_id),
+                None,
+                &meta,
+                Outcome::MfaFailed,
+            )
+            .await;
+            return error_response(StatusCode::UNAUTHORIZED, "Invalid two-factor code");
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to verify two-factor code.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while logging you in",
+            );
+        }
+    }
+
+    match complete_challenge(&pool, challenge.id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return error_response(
+                StatusCode::UNAUTHORIZED,
+                "Invalid or expired two-factor token. Please log in again.",
+            );
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to complete two-factor challenge.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while logging you in",
+            );
+        }
+    }
+
+    tracing::info!(
+        user_id = challenge.user_id,
+        "Two-factor verification successful"
+    );
+    complete_login(&pool, challenge.user_id, None, meta, req.return_tokens).await
+}
@@ -0,0 +1,142 @@
+//! Email verification endpoint handler.
+//!
+//! Handles confirming an email address with the token from a verification email.
+
+use api_types::auth::verify_email::{ApiAuthVerifyEmailRequest, ApiAuthVerifyEmailResponse};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use sqlx::PgPool;
+use utils::errors::error_response;
+use utils::tokens::hash_token;
+
+/// Errors returned when redeeming a verification token.
+enum VerifyError {
+    /// The token is unknown, expired or already used.
+    InvalidToken,
+    /// Another account took the email address since the token was sent.
+    EmailTaken,
+    /// A database operation failed.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for VerifyError {
+    fn from(e: sqlx::Error) -> Self {
+        if e.as_database_error()
+            .is_some_and(|e| e.is_unique_violation())
+        {
+            return VerifyError::EmailTaken;
+        }
+        VerifyError::Database(e)
+    }
+}
+
+/// Handles email verification requests.
+///
+/// This endpoint:
+/// 1. Redeems the verification token, which can only be used once
+/// 2. Marks the email address as verified, replacing the account's email
+///    address if the token was sent for an email change
+/// 3. Invalidates the password reset links sent to the previous address
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `req` - The request containing the verification token
+///
+/// # Returns
+///
+/// - `200 OK` with the verified email address
+/// - `400 BAD REQUEST` if the token is invalid, expired or already used
+/// - `409 CONFLICT` if another account uses the email address by now
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+///
+/// # Example Request
+///
+/// ```json
+/// {
+///   "token": "Jq0b..."
+/// }
+/// ```
+#[tracing::instrument(skip(pool, req))]
+pub async fn api_auth_verify_email_post(
+    State(pool): State<PgPool>,
+    Json(req): Json<ApiAuthVerifyEmailRequest>,
+) -> impl IntoResponse {
+    let result: Result<(i64, String), VerifyError> = async {
+        let mut tx = pool.begin().await?;
+
+        let Some(verification) = sqlx::query!(
+            r#"
+            DELETE FROM email_verifications
+            WHERE token_hash = $1 AND expires_at > NOW()
+            RETURNING user_id, email
+            "#,
+            hash_token(&req.token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Err(VerifyError::InvalidToken);
+        };
+
+        let changed = sqlx::query_scalar!(
+            r#"
+            UPDATE users u
+            SET email = $2, email_verified_at = NOW(), updated_at = NOW()
+            FROM (SELECT email FROM users WHERE id = $1) previous
+            WHERE u.id = $1
+            RETURNING previous.email <> $2 AS "changed!"
+            "#,
+            verification.user_id,
+            verification.email
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // Reset links went to the previous address
+        if changed == Some(true) {
+            sqlx::query!(
+                "DELETE FROM password_resets WHERE user_id = $1 AND used_at IS NULL",
+                verification.user_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok((verification.user_id, verification.email))
+    }
+    .await;
+
+    match result {
+        Ok((user_id, email)) => {
+            tracing::info!(user_id, "Email address verified");
+            let resp = ApiAuthVerifyEmailResponse {
+                ok: true,
+                message: "Your email address was verified".to_string(),
+                email,
+            };
+            (StatusCode::OK, Json(resp)).into_response()
+        }
+        Err(VerifyError::InvalidToken) => {
+            tracing::info!("Email verification attempt with invalid token");
+            error_response(
+                StatusCode::BAD_REQUEST,
+                "This verification link is invalid or has expired",
+            )
+        }
+        Err(VerifyError::EmailTaken) => error_response(
+            StatusCode::CONFLICT,
+            "This email address is already used by another account",
+        ),
+        Err(VerifyError::Database(e)) => {
+            tracing::error!(error = ?e, "Failed to verify email address.");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while verifying your email address",
+            )
+        }
+    }
+}
@@ -3,11 +3,20 @@
 //! This module contains all chat-related endpoints including creation,
 //! deletion, and real-time WebSocket communication.
 
+/// List conversations endpoint handler.
+pub mod get;
+
 /// Submit chat code endpoint handler.
 pub mod post;
 
 pub mod messages;
 
+/// Read marker endpoint handlers.
+pub mod reads;
+
+/// Chat request endpoint handlers.
+pub mod requests;
+
 /// WebSocket real-time chat handler.
 pub mod ws;
 
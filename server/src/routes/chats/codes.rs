@@ -1,3 +1,9 @@
 pub mod post;
 
 pub mod delete;
+
+/// List chat codes endpoint handler.
+pub mod get;
+
+/// Chat code QR image endpoint handler.
+pub mod qr;
@@ -2,10 +2,13 @@
 //!
 //! Handles deletion of chat codes for the authenticated user.
 
+use crate::chat_codes::StoredCode;
 use api_types::chats::codes::delete::{ApiChatsCodeDeleteResponse, ApiChatsCodesDeleteRequest};
 use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
 use sqlx::PgPool;
 use utils::errors::error_response;
+use utils::scopes::Scope;
 
 /// Handles chat code deletion requests.
 ///
@@ -18,24 +21,41 @@ use utils::errors::error_response;
 /// # Arguments
 ///
 /// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `chats:write` scope
 /// * `pool` - The PostgreSQL connection pool
 /// * `payload` - The delete request containing the chat code
 ///
 /// # Returns
 ///
 /// - `200 OK` with deletion confirmation
-/// - `404 NOT FOUND` if the chat code doesn't exist or isn't owned by the user
+/// - `404 NOT FOUND` if the chat code doesn't exist, is a mistyped invite code,
+///   or isn't owned by the user
+/// - `403 FORBIDDEN` if the API key lacks the `chats:write` scope
 /// - `500 INTERNAL SERVER ERROR` if database operation fails
-#[tracing::instrument(name = "Delete a chat code", skip(pool, user_id, payload))]
+#[tracing::instrument(name = "Delete a chat code", skip(pool, user_id, payload, access))]
 pub async fn api_chats_codes_delete(
     Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
     State(pool): State<PgPool>,
     Json(payload): Json<ApiChatsCodesDeleteRequest>,
 ) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::ChatsWrite) {
+        return resp;
+    }
+
+    let Some(stored) = StoredCode::parse(&payload.code) else {
+        return error_response(StatusCode::NOT_FOUND, "Chat code not found.");
+    };
+
     // Check if the chat code exists and delete it
     let result = sqlx::query!(
-        "DELETE FROM chat_codes WHERE code = $1 AND user_id = $2 RETURNING id",
-        payload.code as i32,
+        r#"
+        DELETE FROM chat_codes
+        WHERE (code = $1 OR invite_code = $2) AND user_id = $3
+        RETURNING id
+        "#,
+        stored.code,
+        stored.invite_code,
         user_id
     )
     .fetch_optional(&pool)
@@ -54,7 +74,7 @@ pub async fn api_chats_codes_delete(
         Err(e) => {
             tracing::error!(
                 error = ?e,
-                "An error occurred while trying to delete code: {}",
+                "An error occurred while trying to delete code: {:?}",
                 payload.code
             );
             error_response(
@@ -0,0 +1,117 @@
+//! List chat codes endpoint handler.
+//!
+//! Handles listing the active chat codes of the authenticated user.
+
+use crate::chat_codes::{ChatCodePolicies, StoredCode, invite_url};
+use api_types::chats::codes::get::{ApiChatsCodesGetResponse, ChatCodeItem};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use utils::errors::error_response;
+use utils::scopes::Scope;
+
+/// Handles chat code listing requests.
+///
+/// This endpoint:
+/// 1. Extracts the user ID from the authentication cookie
+/// 2. Retrieves the user's codes that haven't expired, with their remaining uses
+/// 3. Returns them most recently created first, with their invite links if
+///    enabled and the number of active codes the user's tier allows
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `chats:read` scope
+/// * `pool` - The PostgreSQL connection pool
+/// * `policies` - The chat code limits of each user tier
+///
+/// # Returns
+///
+/// - `200 OK` with the list of active codes on success
+/// - `403 FORBIDDEN` if the API key lacks the `chats:read` scope
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, policies, user_id, access))]
+pub async fn api_chats_codes_get(
+    Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
+    State(pool): State<PgPool>,
+    State(policies): State<ChatCodePolicies>,
+) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::ChatsRead) {
+        return resp;
+    }
+
+    tracing::debug!(user_id, "Listing chat codes");
+
+    let tier = match policies.for_user(&pool, user_id).await {
+        Ok(tier) => tier,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to fetch user tier");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while listing chat codes.",
+            );
+        }
+    };
+
+    let result = sqlx::query!(
+        r#"
+        SELECT id, code, invite_code, label, uses, max_uses, requires_approval,
+               created_at, expires_at
+        FROM chat_codes
+        WHERE user_id = $1 AND expires_at > NOW()
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(&pool)
+    .await;
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to list chat codes");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while listing chat codes.",
+            );
+        }
+    };
+
+    let codes = rows
+        .into_iter()
+        .map(|row| ChatCodeItem {
+            code: StoredCode {
+                code: row.code,
+                invite_code: row.invite_code,
+            }
+            .to_api(),
+            invite_url: invite_url(row.id, row.expires_at),
+            label: row.label,
+            uses: row.uses,
+            max_uses: row.max_uses,
+            remaining_uses: row.max_uses - row.uses,
+            requires_approval: row.requires_approval,
+            created_at: format_timestamp(row.created_at),
+            expires_at: format_timestamp(row.expires_at),
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(ApiChatsCodesGetResponse {
+            codes,
+            max_codes: tier.max_codes,
+        }),
+    )
+        .into_response()
+}
+
+/// Formats a timestamp as RFC3339 for inclusion in the response.
+#[inline(always)]
+fn format_timestamp(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339)
+        .unwrap_or("Wasn't able to format timestamp".to_string())
+}
@@ -2,78 +2,236 @@
 //!
 //! Handles creation of new chat conversations.
 
-use api_types::chats::post::ApiChatsPostResponse;
+use crate::chat_codes::{
+    ChatCodePolicies, MAX_GENERATION_ATTEMPTS, MIN_TTL, StoredCode, invite_url,
+};
+use crate::verification::{Restriction, UnverifiedLimits};
+use api_types::chats::codes::ChatCodeFormat;
+use api_types::chats::post::{ApiChatsPostRequest, ApiChatsPostResponse};
 use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
 use sqlx::PgPool;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
 use utils::errors::error_response;
+use utils::invites::generate_invite_code;
+use utils::scopes::Scope;
+
+/// Errors returned while creating a chat code.
+enum CreateError {
+    /// The user already has as many active codes as their tier allows.
+    TooMany,
+    /// Every code drawn was already taken.
+    Taken,
+    /// A database operation failed.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for CreateError {
+    fn from(e: sqlx::Error) -> Self {
+        CreateError::Database(e)
+    }
+}
 
 /// Handles chat creation requests.
 ///
 /// This endpoint:
 /// 1. Extracts the user ID from the authentication cookie
-/// 2. Generates a unique random numeric code for the chat
-/// 3. Creates a new chat code in the database linked to the user
-/// 4. Returns the chat code
+/// 2. Checks the requested time to live and number of uses against the
+///    limits of the user's tier
+/// 3. Generates a random numeric or invite code for the chat, drawing again
+///    when it is already taken
+/// 4. Creates a new chat code in the database linked to the user, unless they
+///    already have as many active codes as their tier allows
+/// 5. Returns the chat code with its expiry, and its invite link if enabled
 ///
 /// # Arguments
 ///
 /// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `chats:write` scope
 /// * `pool` - The PostgreSQL connection pool
+/// * `limits` - What unverified accounts can't do, checked for creating chat codes
+/// * `policies` - The chat code limits of each user tier
+/// * `payload` - The optional time to live, number of uses, label, format and
+///   approval requirement of the code
 ///
 /// # Returns
 ///
 /// - `201 CREATED` with the chat code on success
+/// - `400 BAD REQUEST` if the label is too long, the time to live or number of
+///   uses is out of the tier's bounds, or the user has too many active codes
+/// - `403 FORBIDDEN` if the API key lacks the `chats:write` scope, or the
+///   user must verify their email address first
 /// - `500 INTERNAL SERVER ERROR` if database operation fails
-#[tracing::instrument(skip(pool, user_id))]
+/// - `503 SERVICE UNAVAILABLE` if every code drawn was already taken
+#[tracing::instrument(skip(pool, limits, policies, user_id, access, payload))]
 pub async fn api_chats_codes_post(
     Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
     State(pool): State<PgPool>,
+    State(limits): State<UnverifiedLimits>,
+    State(policies): State<ChatCodePolicies>,
+    payload: Option<Json<ApiChatsPostRequest>>,
 ) -> impl IntoResponse {
-    tracing::debug!(user_id, "Creating new chat code");
+    if let Err(resp) = access.require(Scope::ChatsWrite) {
+        return resp;
+    }
+    if let Err(resp) = limits.require(&pool, user_id, Restriction::ChatCodes).await {
+        return resp;
+    }
+
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    if let Err(e) = payload.validate() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("Your request was invalid: {}", e),
+        );
+    }
+
+    let tier = match policies.for_user(&pool, user_id).await {
+        Ok(tier) => tier,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to fetch user tier");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create chat code",
+            );
+        }
+    };
+
+    let ttl = payload
+        .ttl_seconds
+        .map(Duration::seconds)
+        .unwrap_or(tier.default_ttl);
+    if ttl < MIN_TTL || ttl > tier.max_ttl {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "The time to live must be between {} and {} seconds.",
+                MIN_TTL.whole_seconds(),
+                tier.max_ttl.whole_seconds()
+            ),
+        );
+    }
 
-    // Generate a random 6-digit numeric code
-    let code = generate_chat_code();
+    let max_uses = payload.max_uses.unwrap_or(1);
+    if !(1..=tier.max_uses).contains(&max_uses) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "The number of uses must be between 1 and {}.",
+                tier.max_uses
+            ),
+        );
+    }
+
+    tracing::debug!(user_id, tier = tier.name, "Creating new chat code");
+
+    let label = payload.label().map(str::to_string);
+    let expires_at = OffsetDateTime::now_utc() + ttl;
 
-    // Insert the chat code into the database
-    let result = sqlx::query!(
-        r#"
-        WITH user_chat_count AS (
-            SELECT COUNT(*)::INT AS count FROM chat_codes WHERE user_id = $2
+    let result: Result<(i64, StoredCode), CreateError> = async {
+        let mut tx = pool.begin().await?;
+
+        // Lock the user so that concurrent requests can't exceed the tier's
+        // number of active codes, expired codes don't count
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let active = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM chat_codes
+            WHERE user_id = $1 AND expires_at > NOW()
+            "#,
+            user_id
         )
-        INSERT INTO chat_codes (code, user_id)
-        SELECT $1, $2
-        FROM user_chat_count
-        WHERE user_chat_count.count < 5
-        "#,
-        code as i32,
-        user_id
-    )
-    .execute(&pool)
+        .fetch_one(&mut *tx)
+        .await?;
+        if active >= tier.max_codes {
+            return Err(CreateError::TooMany);
+        }
+
+        // A fresh code is drawn when the random one is already taken
+        for attempts in 1..=MAX_GENERATION_ATTEMPTS {
+            let stored = match payload.format {
+                ChatCodeFormat::Numeric => StoredCode {
+                    code: Some(generate_chat_code() as i32),
+                    invite_code: None,
+                },
+                ChatCodeFormat::Invite => StoredCode {
+                    code: None,
+                    invite_code: Some(generate_invite_code()),
+                },
+            };
+
+            let id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO chat_codes
+                    (code, invite_code, user_id, label, max_uses, expires_at, requires_approval)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT DO NOTHING
+                RETURNING id
+                "#,
+                stored.code,
+                stored.invite_code,
+                user_id,
+                label,
+                max_uses,
+                expires_at,
+                payload.requires_approval
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some(id) = id {
+                tx.commit().await?;
+                return Ok((id, stored));
+            }
+            tracing::debug!(user_id, attempts, "Chat code already taken, retrying");
+        }
+
+        Err(CreateError::Taken)
+    }
     .await;
 
-    match result {
-        Ok(r) if r.rows_affected() == 1 => {}
-        Ok(_) => {
+    let (id, stored) = match result {
+        Ok(created) => created,
+        Err(CreateError::TooMany) => {
             return error_response(
                 StatusCode::BAD_REQUEST,
-                "You already have 5 chat codes.".to_string(),
+                format!("You already have {} active chat codes.", tier.max_codes),
             );
         }
-        Err(e) => {
+        Err(CreateError::Taken) => {
+            tracing::error!(user_id, "Failed to find a free chat code");
+            return error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "No free chat code was found, please try again.",
+            );
+        }
+        Err(CreateError::Database(e)) => {
             tracing::error!(error = ?e, user_id, "Failed to create chat code");
             return error_response(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to create chat code".to_string(),
+                "Failed to create chat code",
             );
         }
-    }
+    };
 
-    tracing::info!(user_id, code, "Chat code created successfully");
+    tracing::info!(user_id, id, max_uses, "Chat code created successfully");
     (
         StatusCode::CREATED,
         Json(ApiChatsPostResponse {
             message: "Chat code created successfully".to_string(),
-            code,
+            code: stored.to_api(),
+            invite_url: invite_url(id, expires_at),
+            label,
+            max_uses,
+            requires_approval: payload.requires_approval,
+            expires_at: expires_at
+                .format(&Rfc3339)
+                .unwrap_or("Wasn't able to format timestamp".to_string()),
         }),
     )
         .into_response()
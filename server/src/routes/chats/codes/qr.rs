@@ -0,0 +1,217 @@
+//! Chat code QR image endpoint handler.
+//!
+//! Handles rendering the authenticated user's chat codes, or their invite
+//! links, as QR images to be scanned in person.
+
+use crate::chat_codes::{StoredCode, invite_url};
+use api_types::chats::codes::ChatCode;
+use api_types::chats::codes::qr::{
+    ApiChatsCodesQrRequest, QrContent, QrErrorCorrection, QrImageFormat,
+};
+use axum::extract::{Path, Query, State};
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use axum::http::{HeaderValue, StatusCode};
+use axum::{Extension, response::IntoResponse};
+use middleware::Access;
+use qrcode::render::svg;
+use qrcode::{Color, EcLevel, QrCode};
+use sqlx::PgPool;
+use utils::errors::error_response;
+use utils::invites::invite_links_enabled;
+use utils::scopes::Scope;
+
+/// Width of the light border around QR codes, in modules, as the standard requires.
+const QUIET_ZONE: u32 = 4;
+
+/// Handles chat code QR image requests.
+///
+/// This endpoint:
+/// 1. Extracts the user ID from the authentication cookie
+/// 2. Validates that the chat code exists, hasn't expired and is owned by the user
+/// 3. Encodes the code, or its signed invite link, as a QR code with the
+///    requested error correction level
+/// 4. Returns it as an SVG or PNG image no larger than the requested size
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `chats:read` scope
+/// * `pool` - The PostgreSQL connection pool
+/// * `code` - The chat code, numeric or invite code
+/// * `query` - The image format, size, error correction level and content
+///
+/// # Returns
+///
+/// - `200 OK` with the QR image on success
+/// - `400 BAD REQUEST` if the size is out of bounds or too small for the QR
+///   code to fit, or the invite link is requested while invite links are disabled
+/// - `404 NOT FOUND` if the chat code doesn't exist, expired, is a mistyped
+///   invite code, or isn't owned by the user
+/// - `403 FORBIDDEN` if the API key lacks the `chats:read` scope
+/// - `500 INTERNAL SERVER ERROR` if database operation or rendering fails
+#[tracing::instrument(skip(pool, user_id, access))]
+pub async fn api_chats_codes_qr_get(
+    Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
+    State(pool): State<PgPool>,
+    Path(code): Path<String>,
+    Query(query): Query<ApiChatsCodesQrRequest>,
+) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::ChatsRead) {
+        return resp;
+    }
+
+    if let Err(e) = query.validate() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("Your request was invalid: {}", e),
+        );
+    }
+
+    let content = query.content.unwrap_or(if invite_links_enabled() {
+        QrContent::Link
+    } else {
+        QrContent::Code
+    });
+    if content == QrContent::Link && !invite_links_enabled() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "Invite links are not enabled on this server.",
+        );
+    }
+
+    let code = match code.parse() {
+        Ok(number) => ChatCode::Numeric(number),
+        Err(_) => ChatCode::Invite(code),
+    };
+    let Some(stored) = StoredCode::parse(&code) else {
+        return error_response(StatusCode::NOT_FOUND, "Chat code not found.");
+    };
+
+    let result = sqlx::query!(
+        r#"
+        SELECT id, code, invite_code, expires_at
+        FROM chat_codes
+        WHERE (code = $1 OR invite_code = $2) AND user_id = $3 AND expires_at > NOW()
+        "#,
+        stored.code,
+        stored.invite_code,
+        user_id
+    )
+    .fetch_optional(&pool)
+    .await;
+
+    let row = match result {
+        Ok(Some(row)) => row,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Chat code not found."),
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to fetch chat code");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while rendering the chat code.",
+            );
+        }
+    };
+
+    let data = match content {
+        QrContent::Link => invite_url(row.id, row.expires_at).unwrap_or_default(),
+        QrContent::Code => match (StoredCode {
+            code: row.code,
+            invite_code: row.invite_code,
+        })
+        .to_api()
+        {
+            ChatCode::Numeric(number) => number.to_string(),
+            ChatCode::Invite(invite_code) => invite_code,
+        },
+    };
+
+    let qr = match QrCode::with_error_correction_level(&data, ec_level(query.error_correction)) {
+        Ok(qr) => qr,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to encode chat code as QR code");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while rendering the chat code.",
+            );
+        }
+    };
+
+    // Whole pixels per module, as many as fit in the requested size
+    let modules = qr.width() as u32 + 2 * QUIET_ZONE;
+    if query.size() < modules {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("The size must be at least {modules} pixels for this chat code."),
+        );
+    }
+    let module_size = query.size() / modules;
+
+    let (content_type, body) = match query.format {
+        QrImageFormat::Svg => {
+            let image = qr
+                .render::<svg::Color>()
+                .quiet_zone(true)
+                .module_dimensions(module_size, module_size)
+                .build();
+            ("image/svg+xml", image.into_bytes())
+        }
+        QrImageFormat::Png => match render_png(&qr, module_size) {
+            Ok(image) => ("image/png", image),
+            Err(e) => {
+                tracing::error!(error = ?e, user_id, "Failed to render QR code as PNG");
+                return error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "An error occurred while rendering the chat code.",
+                );
+            }
+        },
+    };
+
+    let mut resp = (StatusCode::OK, body).into_response();
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    // Invite links are secrets, keep them out of shared caches
+    resp.headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    resp
+}
+
+/// Maps the requested error correction level to the encoder's.
+fn ec_level(level: QrErrorCorrection) -> EcLevel {
+    match level {
+        QrErrorCorrection::Low => EcLevel::L,
+        QrErrorCorrection::Medium => EcLevel::M,
+        QrErrorCorrection::Quartile => EcLevel::Q,
+        QrErrorCorrection::High => EcLevel::H,
+    }
+}
+
+/// Renders a QR code as a grayscale PNG image, with its quiet zone.
+fn render_png(qr: &QrCode, module_size: u32) -> Result<Vec<u8>, png::EncodingError> {
+    let width = qr.width() as u32;
+    let side = (width + 2 * QUIET_ZONE) * module_size;
+    let colors = qr.to_colors();
+
+    let mut pixels = vec![u8::MAX; (side * side) as usize];
+    for y in 0..width {
+        for x in 0..width {
+            if colors[(y * width + x) as usize] != Color::Dark {
+                continue;
+            }
+            let left = (x + QUIET_ZONE) * module_size;
+            let top = (y + QUIET_ZONE) * module_size;
+            for row in top..top + module_size {
+                let start = (row * side + left) as usize;
+                pixels[start..start + module_size as usize].fill(0);
+            }
+        }
+    }
+
+    let mut image = Vec::new();
+    let mut encoder = png::Encoder::new(&mut image, side, side);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(image)
+}
@@ -0,0 +1,154 @@
+//! List conversations endpoint handler.
+//!
+//! Handles listing the conversations the authenticated user is part of.
+
+use api_types::chats::get::{ApiChatsGetResponse, ConversationItem, LastMessagePreview};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use utils::errors::error_response;
+use utils::scopes::Scope;
+use uuid::Uuid;
+
+/// Maximum number of characters of the last message included in the preview.
+const PREVIEW_LENGTH: i32 = 100;
+
+/// Row structure for conversations from database.
+struct ConversationRow {
+    id: Uuid,
+    other_username: String,
+    created_at: OffsetDateTime,
+    last_message_at: OffsetDateTime,
+    last_message_id: Option<Uuid>,
+    last_message_preview: Option<String>,
+    last_message_user: Option<String>,
+    last_message_sent_at: Option<OffsetDateTime>,
+    unread_count: i64,
+}
+
+/// Handles conversation listing requests.
+///
+/// This endpoint:
+/// 1. Extracts the user ID from the authentication cookie
+/// 2. Retrieves every conversation the user is part of, with the other
+///    participant's username, a preview of the last message and the number
+///    of unread messages (based on the user's read marker)
+/// 3. Returns them ordered by `last_message_at`, most recent first
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `chats:read` scope
+/// * `pool` - The PostgreSQL connection pool
+///
+/// # Returns
+///
+/// - `200 OK` with the list of conversations on success
+/// - `403 FORBIDDEN` if the API key lacks the `chats:read` scope
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, access))]
+pub async fn api_chats_get(
+    Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
+    State(pool): State<PgPool>,
+) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::ChatsRead) {
+        return resp;
+    }
+
+    tracing::debug!(user_id, "Listing conversations");
+
+    let result = sqlx::query_as!(
+        ConversationRow,
+        r#"
+        SELECT
+            c.id,
+            other.username AS other_username,
+            c.created_at,
+            c.last_message_at,
+            lm.id AS "last_message_id?",
+            LEFT(lm.content, $2) AS "last_message_preview?",
+            sender.username AS "last_message_user?",
+            lm.sent_at AS "last_message_sent_at?",
+            (
+                SELECT COUNT(*)
+                FROM messages m
+                WHERE m.conversation_id = c.id
+                  AND m.user_sent_id <> $1
+                  AND m.sent_at > COALESCE(r.last_read_at, '-infinity'::TIMESTAMPTZ)
+            ) AS "unread_count!"
+        FROM conversations c
+        JOIN users other
+          ON other.id = CASE WHEN c.user_id_1 = $1 THEN c.user_id_2 ELSE c.user_id_1 END
+        LEFT JOIN conversation_reads r
+          ON r.conversation_id = c.id AND r.user_id = $1
+        LEFT JOIN LATERAL (
+            SELECT id, content, user_sent_id, sent_at
+            FROM messages
+            WHERE conversation_id = c.id
+            ORDER BY sent_at DESC
+            LIMIT 1
+        ) lm ON TRUE
+        LEFT JOIN users sender ON sender.id = lm.user_sent_id
+        WHERE c.user_id_1 = $1 OR c.user_id_2 = $1
+        ORDER BY c.last_message_at DESC
+        "#,
+        user_id,
+        PREVIEW_LENGTH,
+    )
+    .fetch_all(&pool)
+    .await;
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to list conversations");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while listing conversations.",
+            );
+        }
+    };
+
+    let conversations = rows
+        .into_iter()
+        .map(|row| {
+            let last_message = match (
+                row.last_message_id,
+                row.last_message_preview,
+                row.last_message_user,
+                row.last_message_sent_at,
+            ) {
+                (Some(id), Some(preview), Some(user_sent), Some(sent_at)) => {
+                    Some(LastMessagePreview {
+                        id,
+                        preview,
+                        user_sent,
+                        sent_at: format_timestamp(sent_at),
+                    })
+                }
+                _ => None,
+            };
+
+            ConversationItem {
+                id: row.id,
+                other_username: row.other_username,
+                last_message,
+                unread_count: row.unread_count,
+                last_message_at: format_timestamp(row.last_message_at),
+                created_at: format_timestamp(row.created_at),
+            }
+        })
+        .collect();
+
+    (StatusCode::OK, Json(ApiChatsGetResponse { conversations })).into_response()
+}
+
+/// Formats a timestamp as RFC3339 for inclusion in the response.
+#[inline(always)]
+fn format_timestamp(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339)
+        .unwrap_or("Wasn't able to format timestamp".to_string())
+}
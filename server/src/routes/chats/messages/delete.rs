@@ -2,8 +2,10 @@ use api_types::chats::messages::delete::{
     ApiChatsMessagesDeleteRequest, ApiChatsMessagesDeleteResponse,
 };
 use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
 use sqlx::PgPool;
 use utils::errors::error_response;
+use utils::scopes::Scope;
 
 /// Deletes a message within a conversation for an authenticated user.
 ///
@@ -12,14 +14,19 @@ use utils::errors::error_response;
 /// 2. Verify the message belongs to the conversation.
 /// 3. Delete the message and return confirmation.
 #[tracing::instrument(
-    skip(pool, user_id),
+    skip(pool, user_id, access),
     fields(conversation_id = ?payload.conversation_id, message_id = ?payload.message_id)
 )]
 pub async fn api_chats_messages_delete(
     Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
     State(pool): State<PgPool>,
     Json(payload): Json<ApiChatsMessagesDeleteRequest>,
 ) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::MessagesWrite) {
+        return resp;
+    }
+
     match delete_message_impl(user_id, &pool, payload.conversation_id, payload.message_id).await {
         Ok(response) => (StatusCode::OK, Json(response)).into_response(),
         Err((status, message)) => error_response(status, &message),
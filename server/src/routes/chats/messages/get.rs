@@ -7,8 +7,10 @@ use axum::{
     http::StatusCode,
     response::IntoResponse,
 };
+use middleware::Access;
 use sqlx::PgPool;
 use utils::errors::error_response;
+use utils::scopes::Scope;
 use uuid::Uuid;
 
 /// Handles chat message retrieval requests.
@@ -22,19 +24,26 @@ use uuid::Uuid;
 /// # Arguments
 ///
 /// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `messages:read` scope
 /// * `pool` - The PostgreSQL connection pool
 /// * `query` - Query parameters including conversation_id and optional filters
 ///
 /// # Returns
 ///
 /// - `200 OK` with the list of messages on success
+/// - `403 FORBIDDEN` if the API key lacks the `messages:read` scope
 /// - `500 INTERNAL SERVER ERROR` if database operation fails
-#[tracing::instrument(skip(pool, user_id), fields(cursor = ?query.cursor, limit = ?query.limit))]
+#[tracing::instrument(skip(pool, user_id, access), fields(cursor = ?query.cursor, limit = ?query.limit))]
 pub async fn api_chats_messages_get(
     Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
     State(pool): State<PgPool>,
     Query(query): Query<ApiChatsMessagesGetRequest>,
 ) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::MessagesRead) {
+        return resp;
+    }
+
     tracing::debug!(user_id, conversation_id = ?query.conversation_id, "Retrieving messages");
 
     match get_messages_impl(
@@ -57,6 +66,7 @@ pub struct ChatRow {
     pub content: String,
     pub username: String,
     pub sent_at: time::OffsetDateTime,
+    pub read_by_partner: bool,
 }
 
 /// Handles chat message retrieval logic.
@@ -144,9 +154,13 @@ pub async fn get_messages_impl(
     let result = sqlx::query_as!(
         ChatRow,
         r#"
-                SELECT messages.id as "id: Uuid", messages.content, users.username, messages.sent_at
+                SELECT messages.id as "id: Uuid", messages.content, users.username, messages.sent_at,
+               COALESCE(partner_read.last_read_at >= messages.sent_at, FALSE) as "read_by_partner!"
         FROM messages
         JOIN users ON messages.user_sent_id = users.id
+        LEFT JOIN conversation_reads partner_read
+          ON partner_read.conversation_id = messages.conversation_id
+         AND partner_read.user_id <> $4
         WHERE messages.conversation_id = $1::UUID
           AND ($2::TIMESTAMPTZ IS NULL OR messages.sent_at < $2::TIMESTAMPTZ)
         ORDER BY messages.sent_at DESC
@@ -154,7 +168,8 @@ pub async fn get_messages_impl(
         "#,
         conversation_id,
         cursor_timestamp,
-        fetch_limit
+        fetch_limit,
+        user_id
     )
     .fetch_all(pool)
     .await;
@@ -182,6 +197,7 @@ pub async fn get_messages_impl(
                         .sent_at
                         .format(&time::format_description::well_known::Rfc3339)
                         .unwrap_or("Wasn't able to format timestamp".to_string()),
+                    read_by_partner: row.read_by_partner,
                 })
                 .collect();
 
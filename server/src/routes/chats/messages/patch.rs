@@ -2,8 +2,10 @@ use api_types::chats::messages::patch::{
     ApiChatsMessagesPatchRequest, ApiChatsMessagesPatchResponse,
 };
 use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
 use sqlx::PgPool;
 use utils::errors::error_response;
+use utils::scopes::Scope;
 use uuid::Uuid;
 
 /// Updates a message within a conversation for an authenticated user.
@@ -13,14 +15,19 @@ use uuid::Uuid;
 /// 2. Verify the message belongs to the conversation and was sent by the user.
 /// 3. Update the message content and edited_at timestamp.
 #[tracing::instrument(
-    skip(pool, user_id),
+    skip(pool, user_id, access),
     fields(conversation_id = ?payload.conversation_id, message_id = ?payload.message_id)
 )]
 pub async fn api_chats_messages_patch(
     Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
     State(pool): State<PgPool>,
     Json(payload): Json<ApiChatsMessagesPatchRequest>,
 ) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::MessagesWrite) {
+        return resp;
+    }
+
     match update_message_impl(
         user_id,
         &pool,
@@ -2,109 +2,302 @@
 //!
 //! Handles the submission of chat codes to establish conversations between users.
 
+use crate::chat_code_attempts::{self, Outcome, record_failure, too_many_attempts};
+use crate::chat_codes::StoredCode;
+use crate::conversations::{create_conversation, find_conversation};
+use crate::sessions::SessionMeta;
+use crate::verification::{Restriction, UnverifiedLimits};
 use api_types::chats::codes::post::{ApiChatsCodesPostRequest, ApiChatsCodesPostResponse};
-use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::{Extension, Json, response::IntoResponse};
+use middleware::Access;
 use sqlx::PgPool;
+use std::net::SocketAddr;
+use time::Duration;
 use utils::errors::error_response;
+use utils::invites::verify_invite_token;
+use utils::scopes::Scope;
+use uuid::Uuid;
+
+/// Errors returned while redeeming a chat code.
+enum RedeemError {
+    /// The invite code's check symbol doesn't match, it was mistyped.
+    Mistyped,
+    /// The invite link token is forged, malformed or expired.
+    InvalidToken,
+    /// The code doesn't exist, expired or has no uses left.
+    NotFound,
+    /// The code belongs to the user redeeming it.
+    OwnCode,
+    /// The users already have a conversation.
+    Exists,
+    /// The user already has a pending chat request to the code's owner.
+    Pending,
+    /// The user or their IP address is on a cooldown for too many invalid codes.
+    Cooldown(Duration),
+    /// A database operation failed.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for RedeemError {
+    fn from(e: sqlx::Error) -> Self {
+        RedeemError::Database(e)
+    }
+}
+
+/// What redeeming a chat code started.
+enum Redemption {
+    /// A conversation with the code's owner.
+    Conversation(Uuid),
+    /// A chat request waiting for the code owner's approval.
+    Request(Uuid),
+}
 
 /// Handles chat code submission requests.
 ///
+/// The code is given either directly, as a number or an invite code, or as the
+/// token of an invite link.
+///
 /// This endpoint:
-/// 1. Validates that the chat code exists and is owned by another user
-/// 2. Checks if a conversation already exists between the two users
-/// 3. Creates a new conversation if one doesn't exist
-/// 4. Returns the conversation ID
+/// 1. Refuses the request while the user or their IP address is on a cooldown
+///    for submitting too many invalid codes
+/// 2. Validates that the chat code or invite link token exists, hasn't
+///    expired, has uses left and is owned by another user
+/// 3. Checks if a conversation already exists between the two users
+/// 4. Creates a new conversation if one doesn't exist, or a chat request for
+///    the owner to approve if the code requires approval
+/// 5. Counts the use of the code, and deletes it once it has no uses left
+/// 6. Records the attempt, flagging users and addresses that look like they
+///    are enumerating codes
+/// 7. Returns the conversation or chat request ID
 ///
 /// # Arguments
 ///
 /// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `chats:write` scope
 /// * `pool` - The PostgreSQL connection pool
-/// * `payload` - The submit request containing the chat code
+/// * `limits` - What unverified accounts can't do, checked for starting conversations
+/// * `addr` - The client's socket address, for the per-IP cooldown
+/// * `headers` - The request headers, for the client's IP address and user agent
+/// * `payload` - The submit request containing the chat code or invite link token
 ///
 /// # Returns
 ///
-/// - `200 OK` with the conversation ID
-/// - `400 BAD REQUEST` if trying to start a conversation with yourself
-/// - `404 NOT FOUND` if the chat code doesn't exist
+/// - `201 CREATED` with the conversation ID
+/// - `202 ACCEPTED` with the chat request ID, if the code requires approval
+/// - `400 BAD REQUEST` if neither or both of a code and a token are given, the
+///   invite code is mistyped, the invite link is invalid or expired, or when
+///   trying to start a conversation with yourself
+/// - `404 NOT FOUND` if the chat code doesn't exist, expired or has no uses left
+/// - `409 CONFLICT` if the users already have a conversation or a pending chat
+///   request, which doesn't use the code
+/// - `403 FORBIDDEN` if the API key lacks the `chats:write` scope, or the
+///   user must verify their email address first
+/// - `429 TOO MANY REQUESTS` with a `Retry-After` header after too many invalid codes
 /// - `500 INTERNAL SERVER ERROR` if database operations fail
-#[tracing::instrument(name = "Submit a chat code", skip(user_id, pool, payload))]
+#[tracing::instrument(
+    name = "Submit a chat code",
+    skip(user_id, pool, limits, headers, payload, access)
+)]
 pub async fn api_chats_post(
     Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
     State(pool): State<PgPool>,
+    State(limits): State<UnverifiedLimits>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<ApiChatsCodesPostRequest>,
 ) -> impl IntoResponse {
-    tracing::debug!(user_id, code = payload.code, "Submitting chat code");
-
-    // Verify the code exists and fetch its owner
-    let owner = sqlx::query!(
-        "SELECT user_id FROM chat_codes WHERE code = $1",
-        payload.code as i32
-    )
-    .fetch_optional(&pool)
-    .await;
-
-    let target_user_id = match owner {
-        Ok(Some(row)) => row.user_id,
-        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Chat code not found."),
-        Err(e) => {
-            tracing::error!(error = ?e, user_id, code = payload.code, "Failed to look up chat code");
-            return error_response(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "An error occurred while looking up the chat code.",
-            );
-        }
-    };
+    if let Err(resp) = access.require(Scope::ChatsWrite) {
+        return resp;
+    }
+    if let Err(resp) = limits.require(&pool, user_id, Restriction::Chats).await {
+        return resp;
+    }
 
-    if target_user_id == user_id {
+    if let Err(e) = payload.validate() {
         return error_response(
             StatusCode::BAD_REQUEST,
-            "You cannot start a conversation with yourself.",
+            format!("Your request was invalid: {}", e),
         );
     }
 
-    // Attempt to create the conversation if it doesn't already exist
-    let insert_result = sqlx::query!(
-        r#"
-        INSERT INTO conversations (user_id_1, user_id_2)
-        VALUES (LEAST($1, $2)::BIGINT, GREATEST($1, $2)::BIGINT)
-        ON CONFLICT (user_id_1, user_id_2) DO NOTHING
-        RETURNING id
-        "#,
-        target_user_id.to_string(),
-        user_id.to_string(),
-    )
-    .fetch_optional(&pool)
-    .await;
+    let meta = SessionMeta::from_request(&headers, addr);
+
+    tracing::debug!(user_id, code = ?payload.code, "Submitting chat code");
+
+    let result: Result<Redemption, RedeemError> = async {
+        let mut tx = pool.begin().await?;
+
+        // Lock the user until the attempt is recorded, so that concurrent
+        // submissions can't all pass the cooldown check before any of their
+        // failures counts
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let cooldown = chat_code_attempts::retry_after(&mut tx, user_id, &meta).await?;
+        if let Some(retry_after) = cooldown {
+            tracing::warn!(
+                user_id,
+                ip_address = meta.ip_address(),
+                "Chat code submitted during cooldown"
+            );
+            chat_code_attempts::record(&mut tx, user_id, None, &meta, Outcome::Locked).await;
+            tx.commit().await?;
+            return Err(RedeemError::Cooldown(retry_after));
+        }
+
+        // Look the code up by exactly one of its number, invite code or ID
+        let lookup = match (&payload.code, &payload.token) {
+            (Some(code), _) => StoredCode::parse(code)
+                .map(|stored| (stored.code, stored.invite_code, None))
+                .ok_or(RedeemError::Mistyped),
+            (None, token) => verify_invite_token(token.as_deref().unwrap_or_default())
+                .map(|code_id| (None, None, Some(code_id)))
+                .ok_or(RedeemError::InvalidToken),
+        };
 
-    match insert_result {
-        Ok(Some(uid)) => {
-            // Delete the chat code after successful conversation creation
-            if let Err(e) = sqlx::query!(
-                "DELETE FROM chat_codes WHERE code = $1",
-                payload.code as i32
+        let code = match lookup {
+            // Lock the code so that concurrent redemptions can't exceed its uses
+            Ok((number, invite_code, code_id)) => sqlx::query!(
+                r#"
+                SELECT id, user_id, label, uses, max_uses, requires_approval
+                FROM chat_codes
+                WHERE (code = $1 OR invite_code = $2 OR id = $3)
+                  AND expires_at > NOW() AND uses < max_uses
+                FOR UPDATE
+                "#,
+                number,
+                invite_code,
+                code_id
             )
-            .execute(&pool)
-            .await
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(RedeemError::NotFound),
+            Err(e) => Err(e),
+        };
+
+        let code = match code {
+            Ok(code) => code,
+            Err(e) => {
+                record_failure(&mut tx, user_id, &meta).await;
+                tx.commit().await?;
+                return Err(e);
+            }
+        };
+
+        if code.user_id == user_id {
+            return Err(RedeemError::OwnCode);
+        }
+
+        let redemption = if code.requires_approval {
+            if find_conversation(&mut tx, code.user_id, user_id)
+                .await?
+                .is_some()
             {
-                tracing::warn!(error = ?e, code = payload.code, "Failed to delete chat code");
+                return Err(RedeemError::Exists);
             }
+
+            // The owner decides later, the conversation is created on approval
+            let request_id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO chat_requests (owner_id, requester_id, label)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (owner_id, requester_id) WHERE status = 'pending' DO NOTHING
+                RETURNING id
+                "#,
+                code.user_id,
+                user_id,
+                code.label
+            )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(RedeemError::Pending)?;
+            Redemption::Request(request_id)
+        } else {
+            // Attempt to create the conversation if it doesn't already exist
+            let conversation_id = create_conversation(&mut tx, code.user_id, user_id)
+                .await?
+                .ok_or(RedeemError::Exists)?;
+            Redemption::Conversation(conversation_id)
+        };
+
+        let outcome = match redemption {
+            Redemption::Conversation(_) => Outcome::Redeemed,
+            Redemption::Request(_) => Outcome::Requested,
+        };
+        chat_code_attempts::record(&mut tx, user_id, Some(code.id), &meta, outcome).await;
+
+        // Spent codes are deleted right away, freeing a slot for a new one
+        if code.uses + 1 >= code.max_uses {
+            sqlx::query!("DELETE FROM chat_codes WHERE id = $1", code.id)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE chat_codes SET uses = uses + 1 WHERE id = $1",
+                code.id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(redemption)
+    }
+    .await;
+
+    match result {
+        Ok(Redemption::Conversation(conversation_id)) => (
+            StatusCode::CREATED,
+            Json(ApiChatsCodesPostResponse {
+                conversation_id: Some(conversation_id),
+                request_id: None,
+                message: "Conversation created successfully".to_string(),
+            }),
+        )
+            .into_response(),
+        Ok(Redemption::Request(request_id)) => {
+            tracing::info!(user_id, %request_id, "Chat request sent");
             (
-                StatusCode::CREATED,
+                StatusCode::ACCEPTED,
                 Json(ApiChatsCodesPostResponse {
-                    conversation_id: Some(uid.id),
-                    message: "Conversation created successfully".to_string(),
+                    conversation_id: None,
+                    request_id: Some(request_id),
+                    message: "Chat request sent, waiting for approval".to_string(),
                 }),
             )
                 .into_response()
         }
-        Ok(None) => error_response(StatusCode::CONFLICT, "Conversation already exists."),
-        Err(e) => {
+        Err(RedeemError::Mistyped) => error_response(
+            StatusCode::BAD_REQUEST,
+            "This invite code is mistyped, please check it.",
+        ),
+        Err(RedeemError::InvalidToken) => error_response(
+            StatusCode::BAD_REQUEST,
+            "This invite link is invalid or expired.",
+        ),
+        Err(RedeemError::NotFound) => error_response(StatusCode::NOT_FOUND, "Chat code not found."),
+        Err(RedeemError::Cooldown(retry_after)) => too_many_attempts(retry_after),
+        Err(RedeemError::OwnCode) => error_response(
+            StatusCode::BAD_REQUEST,
+            "You cannot start a conversation with yourself.",
+        ),
+        Err(RedeemError::Exists) => {
+            error_response(StatusCode::CONFLICT, "Conversation already exists.")
+        }
+        Err(RedeemError::Pending) => error_response(
+            StatusCode::CONFLICT,
+            "You already sent a chat request to this user.",
+        ),
+        Err(RedeemError::Database(e)) => {
             tracing::error!(
                 error = ?e,
                 user_id,
-                target_user_id,
-                code = payload.code,
-                "Failed to create conversation"
+                code = ?payload.code,
+                "Failed to redeem chat code"
             );
             error_response(
                 StatusCode::INTERNAL_SERVER_ERROR,
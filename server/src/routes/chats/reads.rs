@@ -0,0 +1,2 @@
+/// Advance read marker endpoint handler.
+pub mod post;
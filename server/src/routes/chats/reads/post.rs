@@ -0,0 +1,157 @@
+use api_types::chats::reads::post::{ApiChatsReadsPostRequest, ApiChatsReadsPostResponse};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
+use sqlx::PgPool;
+use utils::errors::error_response;
+use utils::scopes::Scope;
+use uuid::Uuid;
+
+/// Advances the user's read marker in a conversation.
+///
+/// Steps:
+/// 1. Ensure the user participates in the conversation.
+/// 2. Verify the message belongs to the conversation.
+/// 3. Move the read marker to the message, unless a newer one was already read.
+#[tracing::instrument(
+    skip(pool, user_id, access),
+    fields(conversation_id = ?payload.conversation_id, message_id = ?payload.message_id)
+)]
+pub async fn api_chats_reads_post(
+    Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
+    State(pool): State<PgPool>,
+    Json(payload): Json<ApiChatsReadsPostRequest>,
+) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::MessagesWrite) {
+        return resp;
+    }
+
+    match mark_read_impl(user_id, &pool, payload.conversation_id, payload.message_id).await {
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+        Err((status, message)) => error_response(status, &message),
+    }
+}
+
+/// Advances the user's read marker in a conversation.
+///
+/// The marker only ever moves forward; marking an older message as read
+/// leaves it unchanged. Moving the marker notifies the conversation channel,
+/// which delivers a `read` frame to connected WebSockets.
+///
+/// Steps:
+/// 1. Ensure the user participates in the conversation.
+/// 2. Verify the message belongs to the conversation.
+/// 3. Move the read marker to the message, unless a newer one was already read.
+pub async fn mark_read_impl(
+    user_id: i64,
+    pool: &PgPool,
+    conversation_id: Uuid,
+    message_id: Uuid,
+) -> Result<ApiChatsReadsPostResponse, (StatusCode, String)> {
+    // Validate user participation in the conversation
+    let is_participant = sqlx::query!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM conversations
+            WHERE id = $1::UUID
+              AND (user_id_1 = $2 OR user_id_2 = $2)
+        ) as "exists!"
+        "#,
+        conversation_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await;
+
+    match is_participant {
+        Ok(record) if !record.exists => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "You are not a participant in this conversation.".to_string(),
+            ));
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to verify conversation participation");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while verifying conversation access.".to_string(),
+            ));
+        }
+        _ => {}
+    }
+
+    // Ensure the message exists in the conversation
+    let message_check = sqlx::query!(
+        r#"
+        SELECT sent_at
+        FROM messages
+        WHERE id = $1::UUID
+          AND conversation_id = $2::UUID
+        "#,
+        message_id,
+        conversation_id
+    )
+    .fetch_optional(pool)
+    .await;
+
+    let message_row = match message_check {
+        Ok(Some(row)) => row,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                "Message not found in this conversation.".to_string(),
+            ));
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to verify message existence");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while verifying the message.".to_string(),
+            ));
+        }
+    };
+
+    // Move the marker forward and return its current position
+    let marker = sqlx::query!(
+        r#"
+        WITH advanced AS (
+            INSERT INTO conversation_reads (conversation_id, user_id, last_read_message_id, last_read_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (conversation_id, user_id)
+            DO UPDATE SET
+                last_read_message_id = EXCLUDED.last_read_message_id,
+                last_read_at = EXCLUDED.last_read_at
+            WHERE conversation_reads.last_read_at <= EXCLUDED.last_read_at
+            RETURNING last_read_message_id, last_read_at
+        )
+        SELECT last_read_message_id, last_read_at as "last_read_at!" FROM advanced
+        UNION ALL
+        SELECT last_read_message_id, last_read_at FROM conversation_reads
+        WHERE conversation_id = $1 AND user_id = $2
+          AND NOT EXISTS (SELECT 1 FROM advanced)
+        "#,
+        conversation_id,
+        user_id,
+        message_id,
+        message_row.sent_at
+    )
+    .fetch_one(pool)
+    .await;
+
+    match marker {
+        Ok(row) => Ok(ApiChatsReadsPostResponse {
+            last_read_message_id: row.last_read_message_id,
+            last_read_at: row
+                .last_read_at
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or("Wasn't able to format timestamp".to_string()),
+        }),
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to update read marker");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while updating the read marker.".to_string(),
+            ))
+        }
+    }
+}
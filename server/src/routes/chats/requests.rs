@@ -0,0 +1,5 @@
+/// List chat requests endpoint handler.
+pub mod get;
+
+/// Accept or decline chat request endpoint handler.
+pub mod post;
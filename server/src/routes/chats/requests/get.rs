@@ -0,0 +1,99 @@
+//! List chat requests endpoint handler.
+//!
+//! Handles listing the pending chat requests sent to and by the authenticated user.
+
+use api_types::chats::requests::get::{ApiChatsRequestsGetResponse, ChatRequestItem};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use utils::errors::error_response;
+use utils::scopes::Scope;
+
+/// Handles chat request listing requests.
+///
+/// This endpoint:
+/// 1. Extracts the user ID from the authentication cookie
+/// 2. Retrieves the pending requests to chat with the user, and the ones they sent
+/// 3. Returns both lists oldest first, with the labels of the user's own codes
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `chats:read` scope
+/// * `pool` - The PostgreSQL connection pool
+///
+/// # Returns
+///
+/// - `200 OK` with the incoming and outgoing pending requests on success
+/// - `403 FORBIDDEN` if the API key lacks the `chats:read` scope
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, access))]
+pub async fn api_chats_requests_get(
+    Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
+    State(pool): State<PgPool>,
+) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::ChatsRead) {
+        return resp;
+    }
+
+    tracing::debug!(user_id, "Listing chat requests");
+
+    let result = sqlx::query!(
+        r#"
+        SELECT cr.id, cr.owner_id, cr.label, cr.created_at, u.username
+        FROM chat_requests cr
+        JOIN users u
+          ON u.id = CASE WHEN cr.owner_id = $1 THEN cr.requester_id ELSE cr.owner_id END
+        WHERE (cr.owner_id = $1 OR cr.requester_id = $1) AND cr.status = 'pending'
+        ORDER BY cr.created_at
+        "#,
+        user_id
+    )
+    .fetch_all(&pool)
+    .await;
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to list chat requests");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while listing chat requests.",
+            );
+        }
+    };
+
+    let mut incoming = Vec::new();
+    let mut outgoing = Vec::new();
+    for row in rows {
+        let is_owner = row.owner_id == user_id;
+        let item = ChatRequestItem {
+            id: row.id,
+            username: row.username,
+            // Labels are the owner's notes, not shown to requesters
+            label: row.label.filter(|_| is_owner),
+            created_at: format_timestamp(row.created_at),
+        };
+        if is_owner {
+            incoming.push(item);
+        } else {
+            outgoing.push(item);
+        }
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiChatsRequestsGetResponse { incoming, outgoing }),
+    )
+        .into_response()
+}
+
+/// Formats a timestamp as RFC3339 for inclusion in the response.
+#[inline(always)]
+fn format_timestamp(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339)
+        .unwrap_or("Wasn't able to format timestamp".to_string())
+}
@@ -0,0 +1,153 @@
+//! Accept or decline chat request endpoint handler.
+//!
+//! Handles the code owner's decision on a chat request sent by redeeming one
+//! of their codes that requires approval.
+
+use crate::conversations::{create_conversation, find_conversation};
+use api_types::chats::requests::post::{ApiChatsRequestsPostRequest, ApiChatsRequestsPostResponse};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
+use sqlx::PgPool;
+use utils::errors::error_response;
+use utils::scopes::Scope;
+use uuid::Uuid;
+
+/// Errors returned while deciding on a chat request.
+enum DecideError {
+    /// The request doesn't exist, isn't addressed to the user or was already decided.
+    NotFound,
+    /// A database operation failed.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for DecideError {
+    fn from(e: sqlx::Error) -> Self {
+        DecideError::Database(e)
+    }
+}
+
+/// Handles chat request decisions.
+///
+/// This endpoint:
+/// 1. Extracts the user ID from the authentication cookie
+/// 2. Validates that the request is pending and addressed to the user
+/// 3. When accepting, creates the conversation with the requester, unless
+///    they already have one
+/// 4. Marks the request as accepted or declined
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `access` - What the request is allowed to do, checked for the `chats:write` scope
+/// * `pool` - The PostgreSQL connection pool
+/// * `payload` - The request ID and the decision
+///
+/// # Returns
+///
+/// - `201 CREATED` with the conversation ID if accepting started a conversation
+/// - `200 OK` if the request was declined, or accepted while the users
+///   already had a conversation, whose ID is returned
+/// - `404 NOT FOUND` if the request doesn't exist, isn't addressed to the
+///   user or was already decided
+/// - `403 FORBIDDEN` if the API key lacks the `chats:write` scope
+/// - `500 INTERNAL SERVER ERROR` if database operations fail
+#[tracing::instrument(skip(pool, user_id, access))]
+pub async fn api_chats_requests_post(
+    Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
+    State(pool): State<PgPool>,
+    Json(payload): Json<ApiChatsRequestsPostRequest>,
+) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::ChatsWrite) {
+        return resp;
+    }
+
+    let result: Result<Option<(Uuid, bool)>, DecideError> = async {
+        let mut tx = pool.begin().await?;
+
+        // Lock the request so that it is decided once
+        let request = sqlx::query!(
+            r#"
+            SELECT requester_id
+            FROM chat_requests
+            WHERE id = $1 AND owner_id = $2 AND status = 'pending'
+            FOR UPDATE
+            "#,
+            payload.request_id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(DecideError::NotFound)?;
+
+        let conversation = if payload.accept {
+            match create_conversation(&mut tx, user_id, request.requester_id).await? {
+                Some(conversation_id) => Some((conversation_id, true)),
+                None => {
+                    let existing = find_conversation(&mut tx, user_id, request.requester_id)
+                        .await?
+                        .ok_or(sqlx::Error::RowNotFound)?;
+                    Some((existing, false))
+                }
+            }
+        } else {
+            None
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE chat_requests
+            SET status = CASE WHEN $2 THEN 'accepted' ELSE 'declined' END, decided_at = NOW()
+            WHERE id = $1
+            "#,
+            payload.request_id,
+            payload.accept
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(conversation)
+    }
+    .await;
+
+    match result {
+        Ok(Some((conversation_id, created))) => {
+            tracing::info!(user_id, %conversation_id, "Chat request accepted");
+            let status = if created {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            };
+            (
+                status,
+                Json(ApiChatsRequestsPostResponse {
+                    message: "Chat request accepted".to_string(),
+                    conversation_id: Some(conversation_id),
+                }),
+            )
+                .into_response()
+        }
+        Ok(None) => {
+            tracing::info!(user_id, "Chat request declined");
+            (
+                StatusCode::OK,
+                Json(ApiChatsRequestsPostResponse {
+                    message: "Chat request declined".to_string(),
+                    conversation_id: None,
+                }),
+            )
+                .into_response()
+        }
+        Err(DecideError::NotFound) => {
+            error_response(StatusCode::NOT_FOUND, "Chat request not found.")
+        }
+        Err(DecideError::Database(e)) => {
+            tracing::error!(error = ?e, user_id, "Failed to decide on chat request");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while deciding on the chat request.",
+            )
+        }
+    }
+}
@@ -1,180 +1,1157 @@
 //! WebSocket handler for real-time chat functionality.
 //!
 //! This module implements a real-time chat system using WebSockets and PostgreSQL LISTEN/NOTIFY.
-//! Messages are persisted to the database and broadcast to connected clients in real-time.
+//! Messages are persisted to the database and broadcast to connected clients in real-time
+//! through the shared [`NotificationHub`]. A single socket can subscribe to any number of
+//! the user's conversations; every frame carries the conversation it belongs to.
 
-use api_types::chats::ws::ApiChatsWsQuery;
+use crate::notifications::{Notification, NotificationHub, Subscriber};
+use crate::presence::{Presence, broadcast_presence};
+use crate::routes::chats::reads::post::mark_read_impl;
+use crate::verification::{Restriction, UnverifiedLimits, is_verified};
+use api_types::chats::ws::{
+    ApiChatsWsQuery, WS_PROTOCOL_VERSION, WsClientFrame, WsEnvelope, WsServerFrame,
+};
 use axum::Extension;
 use axum::http::StatusCode;
 use axum::{
     extract::{
         Query, State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code},
     },
     response::IntoResponse,
 };
-use futures_util::StreamExt;
-use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, postgres::PgListener};
+use middleware::Access;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
 use utils::errors::error_response;
+use utils::scopes::Scope;
+use uuid::Uuid;
 
 /// Represents a message notification payload from PostgreSQL LISTEN/NOTIFY.
-#[derive(Serialize, Deserialize)]
-struct MessageNotification {
-    /// ID of the user who sent the message
-    user_id: i64,
-    /// Content of the message
-    content: String,
+///
+/// The `kind` field tells which trigger produced the notification.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum MessageNotification {
+    /// A message was inserted.
+    Message {
+        /// ID of the message
+        id: Uuid,
+        /// Conversation the message belongs to
+        conversation_id: Uuid,
+        /// Username of the user who sent the message
+        username: String,
+        /// Content of the message
+        content: String,
+        /// Timestamp when the message was sent
+        #[serde(with = "time::serde::rfc3339")]
+        sent_at: OffsetDateTime,
+    },
+    /// A message's content was updated.
+    Edited {
+        /// ID of the message
+        id: Uuid,
+        /// Conversation the message belongs to
+        conversation_id: Uuid,
+        /// New content of the message
+        content: String,
+        /// Timestamp when the message was edited
+        #[serde(with = "time::serde::rfc3339")]
+        edited_at: OffsetDateTime,
+    },
+    /// A message was deleted.
+    Deleted {
+        /// ID of the message
+        id: Uuid,
+        /// Conversation the message belonged to
+        conversation_id: Uuid,
+    },
+    /// A participant started or stopped typing. Never persisted.
+    Typing {
+        /// Conversation the participant is typing in
+        conversation_id: Uuid,
+        /// ID of the participant
+        user_id: i64,
+        /// Username of the participant
+        username: String,
+        /// Whether the participant is typing
+        typing: bool,
+    },
+    /// A participant came online or went offline.
+    Presence {
+        /// Conversation shared with the participant
+        conversation_id: Uuid,
+        /// ID of the participant
+        user_id: i64,
+        /// Username of the participant
+        username: String,
+        /// Whether the participant has at least one open socket
+        online: bool,
+        /// Timestamp when the participant's last socket closed
+        #[serde(with = "time::serde::rfc3339::option")]
+        last_seen_at: Option<OffsetDateTime>,
+    },
+    /// A participant's read marker moved.
+    Read {
+        /// Conversation the read marker belongs to
+        conversation_id: Uuid,
+        /// Username of the participant who read the messages
+        username: String,
+        /// The last message the participant has read
+        message_id: Uuid,
+        /// Timestamp of the last message the participant has read
+        #[serde(with = "time::serde::rfc3339")]
+        read_at: OffsetDateTime,
+    },
+}
+
+impl MessageNotification {
+    /// Returns the conversation the notification belongs to.
+    fn conversation_id(&self) -> Uuid {
+        match self {
+            MessageNotification::Message {
+                conversation_id, ..
+            }
+            | MessageNotification::Edited {
+                conversation_id, ..
+            }
+            | MessageNotification::Deleted {
+                conversation_id, ..
+            }
+            | MessageNotification::Typing {
+                conversation_id, ..
+            }
+            | MessageNotification::Presence {
+                conversation_id, ..
+            }
+            | MessageNotification::Read {
+                conversation_id, ..
+            } => *conversation_id,
+        }
+    }
+
+    /// Returns the user an ephemeral notification (typing, presence) is about.
+    ///
+    /// Those are only relevant to the other participant, so they are not
+    /// forwarded to the user's own sockets.
+    fn ephemeral_user_id(&self) -> Option<i64> {
+        match self {
+            MessageNotification::Typing { user_id, .. }
+            | MessageNotification::Presence { user_id, .. } => Some(*user_id),
+            _ => None,
+        }
+    }
+
+    /// Converts the notification into the frame forwarded to clients.
+    fn into_frame(self) -> WsServerFrame {
+        match self {
+            MessageNotification::Message {
+                id,
+                conversation_id,
+                username,
+                content,
+                sent_at,
+            } => WsServerFrame::Message {
+                conversation_id,
+                message_id: id,
+                user_sent: username,
+                content,
+                sent_at: format_timestamp(sent_at),
+            },
+            MessageNotification::Edited {
+                id,
+                conversation_id,
+                content,
+                edited_at,
+            } => WsServerFrame::Edited {
+                conversation_id,
+                message_id: id,
+                content,
+                edited_at: format_timestamp(edited_at),
+            },
+            MessageNotification::Deleted {
+                id,
+                conversation_id,
+            } => WsServerFrame::Deleted {
+                conversation_id,
+                message_id: id,
+            },
+            MessageNotification::Typing {
+                conversation_id,
+                username,
+                typing: true,
+                ..
+            } => WsServerFrame::TypingStart {
+                conversation_id,
+                user_typing: username,
+            },
+            MessageNotification::Typing {
+                conversation_id,
+                username,
+                typing: false,
+                ..
+            } => WsServerFrame::TypingStop {
+                conversation_id,
+                user_typing: username,
+            },
+            MessageNotification::Presence {
+                conversation_id,
+                username,
+                online,
+                last_seen_at,
+                ..
+            } => WsServerFrame::Presence {
+                conversation_id,
+                username,
+                online,
+                last_seen_at: last_seen_at.map(format_timestamp),
+            },
+            MessageNotification::Read {
+                conversation_id,
+                username,
+                message_id,
+                read_at,
+            } => WsServerFrame::Read {
+                conversation_id,
+                user_read: username,
+                message_id,
+                read_at: format_timestamp(read_at),
+            },
+        }
+    }
 }
 
 /// Handles WebSocket upgrades for real-time chat.
 ///
-/// Validates that the user is a participant in the conversation, then upgrades
-/// the HTTP connection to a WebSocket and delegates to `handle_socket`.
+/// Upgrades the HTTP connection to a WebSocket and delegates to
+/// `handle_socket`. Conversations are subscribed to with `subscribe` frames;
+/// when `chatId` is given, the socket is subscribed to that conversation
+/// right away, after validating that the user is a participant.
+///
+/// When `since` is given along with `chatId`, messages sent after that point
+/// are replayed before the socket switches to live events.
+///
+/// API keys need the `messages:read` scope to connect, and `messages:write`
+/// to send, mark read and type. Users who must verify their email address
+/// before sending messages can connect, but their `send` frames are refused.
 ///
 /// # Arguments
-/// * `params` - Query parameters containing the chat ID
+/// * `params` - Query parameters containing the optional chat ID and replay point
 /// * `user_id` - The authenticated user ID from the JWT extension
+/// * `access` - What the request is allowed to do
 /// * `ws` - WebSocket upgrade handler
 /// * `pool` - PostgreSQL connection pool
+/// * `hub` - Shared notification hub the socket subscribes to
+/// * `presence` - Connected socket counts used for online presence
+/// * `limits` - What unverified accounts can't do, checked for sending messages
 ///
 /// # Returns
 /// Either an error response (if validation fails) or a WebSocket upgrade response
-#[tracing::instrument(skip(ws, pool, user_id, params))]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(ws, pool, hub, presence, limits, user_id, access, params))]
 pub async fn api_chats_ws(
     Query(params): Query<ApiChatsWsQuery>,
     Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
     ws: WebSocketUpgrade,
     State(pool): State<PgPool>,
+    State(hub): State<NotificationHub>,
+    State(presence): State<Presence>,
+    State(limits): State<UnverifiedLimits>,
 ) -> impl IntoResponse {
-    let chat_id = match params.chat_id {
-        Some(id) => id,
-        None => return error_response(StatusCode::BAD_REQUEST, "Chat ID not provided"),
+    if let Err(resp) = access.require(Scope::MessagesRead) {
+        return resp;
+    }
+
+    let may_send = if limits.restricts(Restriction::Messages) {
+        match is_verified(&pool, user_id).await {
+            Ok(verified) => verified,
+            Err(e) => {
+                tracing::error!("Failed to check email verification: {}", e);
+                return error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to check email verification",
+                );
+            }
+        }
+    } else {
+        true
+    };
+
+    let initial = match params.chat_id {
+        Some(chat_id) => {
+            match is_participant(&pool, chat_id, user_id).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    return error_response(
+                        StatusCode::UNAUTHORIZED,
+                        "Not authorized for this conversation",
+                    );
+                }
+                Err(e) => {
+                    tracing::error!("Failed to verify conversation participant: {}", e);
+                    return error_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to verify conversation participant",
+                    );
+                }
+            }
+
+            let replay_from = match params.since.as_deref() {
+                Some(since) => match resolve_since(&pool, chat_id, since).await {
+                    Ok(cursor) => Some(cursor),
+                    Err((status, message)) => return error_response(status, &message),
+                },
+                None => None,
+            };
+
+            Some((chat_id, replay_from))
+        }
+        None if params.since.is_some() => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "since requires chatId. Use the since field of subscribe frames instead.",
+            );
+        }
+        None => None,
     };
 
-    let is_participant = match sqlx::query_scalar!(
+    ws.on_upgrade(move |socket| async move {
+        handle_socket(
+            socket, pool, hub, presence, user_id, access, may_send, initial,
+        )
+        .await;
+    })
+}
+
+/// Checks whether the user is a participant in the conversation.
+async fn is_participant(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    user_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let exists = sqlx::query_scalar!(
         r#"
         SELECT EXISTS (
             SELECT 1 FROM conversations
             WHERE id = $1 AND (user_id_1 = $2 OR user_id_2 = $2)
         )
         "#,
-        chat_id,
+        conversation_id,
         user_id
     )
-    .fetch_one(&pool)
-    .await
-    {
-        Ok(res) => res.unwrap_or(false),
-        Err(e) => {
-            tracing::error!("Failed to verify conversation participant: {}", e);
-            return error_response(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to verify conversation participant",
-            );
-        }
-    };
+    .fetch_one(pool)
+    .await?;
+
+    Ok(exists.unwrap_or(false))
+}
+
+/// Number of messages fetched per query while replaying missed messages.
+const REPLAY_PAGE_SIZE: i64 = 100;
+
+/// Position in a conversation after which missed messages are replayed.
+///
+/// Messages are ordered by `(sent_at, id)`, so that messages sharing a
+/// timestamp are neither skipped nor replayed twice across pages.
+#[derive(Debug, Clone, Copy)]
+struct ReplayCursor {
+    sent_at: OffsetDateTime,
+    id: Uuid,
+}
+
+/// Resolves the `since` query parameter into a replay cursor.
+///
+/// # Returns
+///
+/// - `Ok(ReplayCursor)` positioned right after the given message or timestamp
+/// - `Err((StatusCode, String))` if `since` is invalid or the message is not in the conversation
+async fn resolve_since(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    since: &str,
+) -> Result<ReplayCursor, (StatusCode, String)> {
+    if let Ok(message_id) = Uuid::parse_str(since) {
+        let message = sqlx::query!(
+            r#"
+            SELECT sent_at
+            FROM messages
+            WHERE id = $1 AND conversation_id = $2
+            "#,
+            message_id,
+            conversation_id
+        )
+        .fetch_optional(pool)
+        .await;
 
-    if !is_participant {
-        return error_response(
-            StatusCode::UNAUTHORIZED,
-            "Not authorized for this conversation",
-        );
+        return match message {
+            Ok(Some(row)) => Ok(ReplayCursor {
+                sent_at: row.sent_at,
+                id: message_id,
+            }),
+            Ok(None) => Err((
+                StatusCode::NOT_FOUND,
+                "Message not found in this conversation.".to_string(),
+            )),
+            Err(e) => {
+                tracing::error!("Failed to look up replay message: {}", e);
+                Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to look up replay message".to_string(),
+                ))
+            }
+        };
     }
 
-    ws.on_upgrade(move |socket| async move {
-        handle_socket(socket, pool, chat_id, user_id).await;
-    })
+    match OffsetDateTime::parse(since, &Rfc3339) {
+        // Sorts after every message sent at exactly that time
+        Ok(sent_at) => Ok(ReplayCursor {
+            sent_at,
+            id: Uuid::max(),
+        }),
+        Err(_) => Err((
+            StatusCode::BAD_REQUEST,
+            "Invalid since. Use a message ID or an RFC3339 timestamp.".to_string(),
+        )),
+    }
 }
 
-#[tracing::instrument(skip(socket, pool, user_id, conversation_id))]
+/// How long a typing indicator lasts unless the client repeats `typing_start`.
+const TYPING_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// Maximum number of conversations a single socket can subscribe to.
+const MAX_SUBSCRIPTIONS: usize = 100;
+
+/// State of a single WebSocket connection.
+struct Connection {
+    socket: WebSocket,
+    pool: PgPool,
+    presence: Presence,
+    subscriber: Subscriber,
+    /// Notifications fanned out to this socket by the hub.
+    notifications: mpsc::Receiver<Notification>,
+    /// Notifications drained from the hub during a replay, forwarded once it
+    /// is complete.
+    held: VecDeque<Notification>,
+    user_id: i64,
+    /// What the connection is allowed to do.
+    access: Access,
+    /// Whether the user may send messages, which unverified accounts can be kept from.
+    may_send: bool,
+    /// Conversation from the `chatId` query parameter, targeted by client
+    /// frames that omit `conversationId`.
+    default_conversation: Option<Uuid>,
+    /// Conversations this socket is subscribed to.
+    subscriptions: HashSet<Uuid>,
+    /// Messages sent through this socket, which were already acknowledged and
+    /// must not be echoed back when their notification arrives.
+    acked: HashSet<Uuid>,
+    /// Messages delivered by a replay, whose notification may be among the
+    /// held ones. Cleared once those are forwarded.
+    replayed: HashSet<Uuid>,
+    /// When the user's typing indicator expires, per conversation they are typing in.
+    typing_until: HashMap<Uuid, Instant>,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(socket, pool, hub, presence, user_id, access, may_send, initial))]
 async fn handle_socket(
-    mut socket: WebSocket,
+    socket: WebSocket,
     pool: PgPool,
-    conversation_id: uuid::Uuid,
+    hub: NotificationHub,
+    presence: Presence,
     user_id: i64,
+    access: Access,
+    may_send: bool,
+    initial: Option<(Uuid, Option<ReplayCursor>)>,
 ) {
-    // Create a PostgreSQL listener for this conversation
-    let mut listener = match PgListener::connect_with(&pool).await {
-        Ok(listener) => listener,
-        Err(e) => {
-            tracing::error!("Failed to create PgListener: {}", e);
-            return;
-        }
+    // Register on the shared listener; channels are added per subscription
+    let (subscriber, notifications) = hub.subscriber();
+
+    let mut conn = Connection {
+        socket,
+        pool,
+        presence,
+        subscriber,
+        notifications,
+        held: VecDeque::new(),
+        user_id,
+        access,
+        may_send,
+        default_conversation: initial.map(|(conversation_id, _)| conversation_id),
+        subscriptions: HashSet::new(),
+        acked: HashSet::new(),
+        replayed: HashSet::new(),
+        typing_until: HashMap::new(),
     };
 
-    let channel = format!("conversation_{}", conversation_id);
-    if let Err(e) = listener.listen(&channel).await {
-        tracing::error!("Failed to listen to channel {}: {}", channel, e);
-        return;
+    if conn.presence.connect(user_id)
+        && let Err(e) = broadcast_presence(&conn.pool, user_id, true).await
+    {
+        tracing::error!("Failed to broadcast presence: {}", e);
     }
 
-    let mut notification_stream = listener.into_stream();
+    let mut open = match initial {
+        Some((conversation_id, replay_from)) => conn
+            .open_subscription(None, conversation_id, replay_from)
+            .await
+            .is_ok(),
+        None => true,
+    };
+
+    while open {
+        // Notifications held back by a replay follow it, in order
+        if let Some(notification) = conn.held.pop_front() {
+            if let Err(e) = conn.forward_notification(&notification).await {
+                tracing::error!("Failed to send message to WebSocket: {}", e);
+                open = false;
+            }
+            continue;
+        }
+        // Every notification queued during a replay was checked against it
+        if !conn.replayed.is_empty() {
+            conn.replayed.clear();
+        }
+
+        let typing_deadline = conn.typing_until.values().min().copied();
 
-    loop {
         tokio::select! {
             // Handle incoming WebSocket messages from the client
-            msg_result = socket.recv() => {
+            msg_result = conn.socket.recv() => {
                 match msg_result {
                     Some(Ok(Message::Text(text))) => {
-                        let content = text.trim();
-                        if content.is_empty() {
-                            continue;
-                        }
-
-                        // Insert message into database (trigger will send notification)
-                        if let Err(e) = sqlx::query!(
-                            r#"
-                            INSERT INTO messages (conversation_id, user_sent_id, content)
-                            VALUES ($1, $2, $3)
-                            "#,
-                            conversation_id,
-                            user_id,
-                            content
-                        )
-                        .execute(&pool)
-                        .await
-                        {
-                            tracing::error!("Failed to persist message: {}", e);
-                            break;
+                        if let Err(e) = conn.handle_client_frame(&text).await {
+                            tracing::error!("Failed to send frame to WebSocket: {}", e);
+                            open = false;
                         }
                     }
-                    Some(Ok(Message::Close(_))) | None => break,
-                    Some(Ok(_)) => continue,
+                    Some(Ok(Message::Close(_))) | None => open = false,
+                    Some(Ok(_)) => {}
                     Some(Err(e)) => {
                         tracing::error!("WebSocket error: {}", e);
-                        break;
+                        open = false;
                     }
                 }
             }
 
-            // Handle incoming PostgreSQL notifications
-            notification = notification_stream.next() => {
-                match notification {
-                    Some(Ok(notification)) => {
-                        // Parse the notification payload
-                        match serde_json::from_str::<MessageNotification>(notification.payload()) {
-                            Ok(msg_notif) => {
-                                // Don't send the message back to the sender
-                                if msg_notif.user_id != user_id {
-                                    if let Err(e) = socket.send(Message::Text(msg_notif.content.into())).await {
-                                        tracing::error!("Failed to send message to WebSocket: {}", e);
-                                        break;
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                tracing::error!("Failed to parse notification payload: {}", e);
-                            }
-                        }
+            // Handle notifications fanned out by the hub
+            notification = conn.notifications.recv() => {
+                let Some(notification) = notification else {
+                    // The hub dropped this socket for not keeping up
+                    tracing::warn!("Socket fell behind on notifications, disconnecting");
+                    let _ = conn
+                        .socket
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::AGAIN,
+                            reason: "Too slow to keep up with notifications".into(),
+                        })))
+                        .await;
+                    break;
+                };
+
+                if let Err(e) = conn.forward_notification(&notification).await {
+                    tracing::error!("Failed to send message to WebSocket: {}", e);
+                    open = false;
+                }
+            }
+
+            // Expire typing indicators the client stopped refreshing
+            _ = tokio::time::sleep_until(typing_deadline.unwrap_or_else(Instant::now)), if typing_deadline.is_some() => {
+                conn.expire_typing().await;
+            }
+        }
+    }
+
+    let typing: Vec<Uuid> = conn.typing_until.keys().copied().collect();
+    for conversation_id in typing {
+        conn.set_typing(conversation_id, false).await;
+    }
+
+    if conn.presence.disconnect(user_id)
+        && let Err(e) = broadcast_presence(&conn.pool, user_id, false).await
+    {
+        tracing::error!("Failed to broadcast presence: {}", e);
+    }
+}
+
+impl Connection {
+    /// Serializes a server frame into a versioned envelope and sends it to the client.
+    async fn send(&mut self, frame: WsServerFrame) -> Result<(), axum::Error> {
+        send_frame(&mut self.socket, frame).await
+    }
+
+    /// Sends an `error` frame.
+    async fn send_error(
+        &mut self,
+        client_id: Option<String>,
+        conversation_id: Option<Uuid>,
+        message: impl Into<String>,
+    ) -> Result<(), axum::Error> {
+        self.send(WsServerFrame::Error {
+            client_id,
+            conversation_id,
+            message: message.into(),
+        })
+        .await
+    }
+
+    /// Parses and processes a single text frame received from the client.
+    ///
+    /// Frames that cannot be processed are answered with an `error` frame.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` once the frame was handled
+    /// - `Err(axum::Error)` if replying to the client failed
+    async fn handle_client_frame(&mut self, text: &str) -> Result<(), axum::Error> {
+        let envelope = match serde_json::from_str::<WsEnvelope<WsClientFrame>>(text) {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                tracing::debug!(error = ?e, "Received malformed WebSocket frame");
+                return self
+                    .send_error(None, None, format!("Malformed frame: {}", e))
+                    .await;
+            }
+        };
+
+        if envelope.v != WS_PROTOCOL_VERSION {
+            let message = format!(
+                "Unsupported protocol version {}, expected {}",
+                envelope.v, WS_PROTOCOL_VERSION
+            );
+            return self.send_error(None, None, message).await;
+        }
+
+        // Everything but (un)subscribing acts on behalf of the user
+        let (writes, client_id) = match &envelope.frame {
+            WsClientFrame::Subscribe { .. } | WsClientFrame::Unsubscribe { .. } => (false, None),
+            WsClientFrame::Send { client_id, .. } | WsClientFrame::Read { client_id, .. } => {
+                (true, client_id.clone())
+            }
+            WsClientFrame::TypingStart { .. } | WsClientFrame::TypingStop { .. } => (true, None),
+        };
+        if writes && !self.access.allows(Scope::MessagesWrite) {
+            let message = format!("This API key lacks the {} scope.", Scope::MessagesWrite);
+            return self.send_error(client_id, None, message).await;
+        }
+
+        match envelope.frame {
+            WsClientFrame::Subscribe {
+                client_id,
+                conversation_id,
+                since,
+            } => self.subscribe(client_id, conversation_id, since).await,
+            WsClientFrame::Unsubscribe {
+                client_id,
+                conversation_id,
+            } => self.unsubscribe(client_id, conversation_id).await,
+            WsClientFrame::Send {
+                client_id,
+                conversation_id,
+                ..
+            } if !self.may_send => {
+                let message = Restriction::Messages.message();
+                self.send_error(client_id, conversation_id, message).await
+            }
+            WsClientFrame::Send {
+                client_id,
+                conversation_id,
+                content,
+            } => match self.target(conversation_id) {
+                Ok(conversation_id) => {
+                    self.send_message(client_id, conversation_id, &content)
+                        .await
+                }
+                Err(message) => self.send_error(client_id, conversation_id, message).await,
+            },
+            WsClientFrame::Read {
+                client_id,
+                conversation_id,
+                message_id,
+            } => {
+                let conversation_id = match self.target(conversation_id) {
+                    Ok(conversation_id) => conversation_id,
+                    Err(message) => {
+                        return self.send_error(client_id, conversation_id, message).await;
                     }
-                    Some(Err(e)) => {
-                        tracing::error!("Notification stream error: {}", e);
-                        break;
+                };
+                match mark_read_impl(self.user_id, &self.pool, conversation_id, message_id).await {
+                    // The `read` notification confirms the new marker
+                    Ok(_) => Ok(()),
+                    Err((_, message)) => {
+                        self.send_error(client_id, Some(conversation_id), message)
+                            .await
                     }
-                    None => break,
                 }
             }
+            WsClientFrame::TypingStart { conversation_id } => match self.target(conversation_id) {
+                Ok(conversation_id) => {
+                    self.set_typing(conversation_id, true).await;
+                    Ok(())
+                }
+                Err(message) => self.send_error(None, conversation_id, message).await,
+            },
+            WsClientFrame::TypingStop { conversation_id } => match self.target(conversation_id) {
+                Ok(conversation_id) => {
+                    self.set_typing(conversation_id, false).await;
+                    Ok(())
+                }
+                Err(message) => self.send_error(None, conversation_id, message).await,
+            },
+        }
+    }
+
+    /// Resolves the conversation a client frame targets.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Uuid)` if the frame targets a conversation the socket is subscribed to
+    /// - `Err(String)` describing why the frame cannot be processed otherwise
+    fn target(&self, conversation_id: Option<Uuid>) -> Result<Uuid, String> {
+        let Some(conversation_id) = conversation_id.or(self.default_conversation) else {
+            return Err("conversationId is required".to_string());
+        };
+        if !self.subscriptions.contains(&conversation_id) {
+            return Err("Not subscribed to this conversation".to_string());
+        }
+        Ok(conversation_id)
+    }
+
+    /// Handles a `subscribe` frame: validates the request, then subscribes.
+    async fn subscribe(
+        &mut self,
+        client_id: Option<String>,
+        conversation_id: Uuid,
+        since: Option<String>,
+    ) -> Result<(), axum::Error> {
+        if self.subscriptions.contains(&conversation_id) {
+            return self
+                .send(WsServerFrame::Subscribed {
+                    client_id,
+                    conversation_id,
+                })
+                .await;
+        }
+
+        if self.subscriptions.len() >= MAX_SUBSCRIPTIONS {
+            let message = format!(
+                "Cannot subscribe to more than {} conversations",
+                MAX_SUBSCRIPTIONS
+            );
+            return self
+                .send_error(client_id, Some(conversation_id), message)
+                .await;
+        }
+
+        match is_participant(&self.pool, conversation_id, self.user_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return self
+                    .send_error(
+                        client_id,
+                        Some(conversation_id),
+                        "Not authorized for this conversation",
+                    )
+                    .await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to verify conversation participant: {}", e);
+                return self
+                    .send_error(
+                        client_id,
+                        Some(conversation_id),
+                        "Failed to verify conversation participant",
+                    )
+                    .await;
+            }
+        }
+
+        let replay_from = match since.as_deref() {
+            Some(since) => match resolve_since(&self.pool, conversation_id, since).await {
+                Ok(cursor) => Some(cursor),
+                Err((_, message)) => {
+                    return self
+                        .send_error(client_id, Some(conversation_id), message)
+                        .await;
+                }
+            },
+            None => None,
+        };
+
+        self.open_subscription(client_id, conversation_id, replay_from)
+            .await
+    }
+
+    /// Subscribes to a conversation the user is known to participate in.
+    ///
+    /// Sends `subscribed`, then replays missed messages if requested, then
+    /// the other participant's `presence`.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` once subscribed, or once the failure was reported to the client
+    /// - `Err(axum::Error)` if sending to the client failed
+    async fn open_subscription(
+        &mut self,
+        client_id: Option<String>,
+        conversation_id: Uuid,
+        replay_from: Option<ReplayCursor>,
+    ) -> Result<(), axum::Error> {
+        let channel = format!("conversation_{}", conversation_id);
+
+        if let Err(e) = self.subscriber.subscribe(&channel).await {
+            tracing::error!("Failed to subscribe to channel {}: {}", channel, e);
+            return self
+                .send_error(client_id, Some(conversation_id), "Failed to subscribe")
+                .await;
+        }
+        self.subscriptions.insert(conversation_id);
+
+        self.send(WsServerFrame::Subscribed {
+            client_id: client_id.clone(),
+            conversation_id,
+        })
+        .await?;
+
+        // Replay only after subscribing, so that messages sent in between are
+        // received twice (and deduplicated) rather than lost
+        if let Some(cursor) = replay_from {
+            match self.replay_missed(conversation_id, cursor).await {
+                Ok(()) => {}
+                Err(ReplayError::Socket(e)) => return Err(e),
+                Err(ReplayError::Database(e)) => {
+                    tracing::error!("Failed to replay missed messages: {}", e);
+                    self.subscriptions.remove(&conversation_id);
+                    self.subscriber.unsubscribe(&channel);
+                    return self
+                        .send_error(
+                            client_id,
+                            Some(conversation_id),
+                            "Failed to replay missed messages",
+                        )
+                        .await;
+                }
+            }
+        }
+
+        // Let the client know whether the other participant is online
+        match partner_presence(&self.pool, &self.presence, conversation_id, self.user_id).await {
+            Ok(frame) => self.send(frame).await,
+            Err(e) => {
+                tracing::error!("Failed to fetch partner presence: {}", e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Handles an `unsubscribe` frame. Unsubscribing from a conversation the
+    /// socket is not subscribed to is not an error.
+    async fn unsubscribe(
+        &mut self,
+        client_id: Option<String>,
+        conversation_id: Uuid,
+    ) -> Result<(), axum::Error> {
+        if self.subscriptions.remove(&conversation_id) {
+            self.subscriber
+                .unsubscribe(&format!("conversation_{}", conversation_id));
+            self.set_typing(conversation_id, false).await;
+        }
+
+        self.send(WsServerFrame::Unsubscribed {
+            client_id,
+            conversation_id,
+        })
+        .await
+    }
+
+    /// Persists a message sent through this socket and acknowledges it.
+    async fn send_message(
+        &mut self,
+        client_id: Option<String>,
+        conversation_id: Uuid,
+        content: &str,
+    ) -> Result<(), axum::Error> {
+        let content = content.trim();
+        if content.is_empty() {
+            return self
+                .send_error(
+                    client_id,
+                    Some(conversation_id),
+                    "Message content cannot be empty",
+                )
+                .await;
+        }
+
+        // Insert message into database (trigger will send notification)
+        let row = match sqlx::query!(
+            r#"
+            INSERT INTO messages (conversation_id, user_sent_id, content)
+            VALUES ($1, $2, $3)
+            RETURNING id, sent_at
+            "#,
+            conversation_id,
+            self.user_id,
+            content
+        )
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                tracing::error!("Failed to persist message: {}", e);
+                return self
+                    .send_error(
+                        client_id,
+                        Some(conversation_id),
+                        "Failed to persist message",
+                    )
+                    .await;
+            }
+        };
+        self.acked.insert(row.id);
+
+        // Sending the message ends the typing indicator
+        self.set_typing(conversation_id, false).await;
+
+        self.send(WsServerFrame::Ack {
+            client_id,
+            conversation_id,
+            message_id: row.id,
+            sent_at: format_timestamp(row.sent_at),
+        })
+        .await
+    }
+
+    /// Forwards a notification from the hub to the client, unless this socket
+    /// should not receive it.
+    async fn forward_notification(
+        &mut self,
+        notification: &Notification,
+    ) -> Result<(), axum::Error> {
+        // Parse the notification payload
+        let msg_notif = match serde_json::from_str::<MessageNotification>(&notification.payload) {
+            Ok(msg_notif) => msg_notif,
+            Err(e) => {
+                tracing::error!(
+                    "Failed to parse notification payload on {}: {}",
+                    notification.channel,
+                    e
+                );
+                return Ok(());
+            }
+        };
+
+        // Notifications may still be queued after unsubscribing
+        if !self.subscriptions.contains(&msg_notif.conversation_id()) {
+            return Ok(());
         }
+
+        // Don't send the message back to the socket that sent it, nor
+        // messages a replay already delivered
+        if let MessageNotification::Message { id, .. } = &msg_notif
+            && (self.acked.remove(id) || self.replayed.remove(id))
+        {
+            return Ok(());
+        }
+
+        // Typing and presence are only relevant to the other participant
+        if msg_notif.ephemeral_user_id() == Some(self.user_id) {
+            return Ok(());
+        }
+
+        self.send(msg_notif.into_frame()).await
+    }
+
+    /// Sends every message of the conversation after `cursor` in chronological
+    /// order, followed by a `replay_complete` frame.
+    ///
+    /// The hub's queue is drained into `held` between pages, so that a long
+    /// replay doesn't get the socket dropped for falling behind, and once more
+    /// after syncing with the hub, so that every notification of a replayed
+    /// message is held. Replayed messages are remembered until the held
+    /// notifications are forwarded, so that they aren't sent twice.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` once every missed message was sent
+    /// - `Err(ReplayError)` if a query or a send failed
+    async fn replay_missed(
+        &mut self,
+        conversation_id: Uuid,
+        mut cursor: ReplayCursor,
+    ) -> Result<(), ReplayError> {
+        let mut count = 0;
+
+        loop {
+            let page = sqlx::query!(
+                r#"
+                SELECT m.id, m.content, u.username, m.sent_at
+                FROM messages m
+                JOIN users u ON u.id = m.user_sent_id
+                WHERE m.conversation_id = $1
+                  AND (m.sent_at, m.id) > ($2, $3)
+                ORDER BY m.sent_at, m.id
+                LIMIT $4
+                "#,
+                conversation_id,
+                cursor.sent_at,
+                cursor.id,
+                REPLAY_PAGE_SIZE
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ReplayError::Database)?;
+
+            let last_page = (page.len() as i64) < REPLAY_PAGE_SIZE;
+
+            for row in page {
+                cursor = ReplayCursor {
+                    sent_at: row.sent_at,
+                    id: row.id,
+                };
+                self.replayed.insert(row.id);
+                count += 1;
+
+                let frame = WsServerFrame::Message {
+                    conversation_id,
+                    message_id: row.id,
+                    user_sent: row.username,
+                    content: row.content,
+                    sent_at: format_timestamp(row.sent_at),
+                };
+                self.send(frame).await.map_err(ReplayError::Socket)?;
+            }
+
+            while let Ok(notification) = self.notifications.try_recv() {
+                self.held.push_back(notification);
+            }
+
+            if last_page {
+                break;
+            }
+        }
+
+        // A closed hub is noticed by the receiver
+        let _ = self.subscriber.sync().await;
+        while let Ok(notification) = self.notifications.try_recv() {
+            self.held.push_back(notification);
+        }
+
+        self.send(WsServerFrame::ReplayComplete {
+            conversation_id,
+            count,
+        })
+        .await
+        .map_err(ReplayError::Socket)
     }
+
+    /// Updates the user's typing indicator in a conversation, notifying the
+    /// conversation only when it actually changes. Starting to type (again)
+    /// pushes the expiry back.
+    async fn set_typing(&mut self, conversation_id: Uuid, typing: bool) {
+        let was_typing = if typing {
+            self.typing_until
+                .insert(conversation_id, Instant::now() + TYPING_TIMEOUT)
+                .is_some()
+        } else {
+            self.typing_until.remove(&conversation_id).is_some()
+        };
+        if was_typing == typing {
+            return;
+        }
+
+        // Typing indicators are ephemeral: they are only sent as notifications
+        if let Err(e) = sqlx::query!(
+            r#"
+            SELECT pg_notify(
+                'conversation_' || $1::UUID::TEXT,
+                json_build_object(
+                    'kind', 'typing',
+                    'conversation_id', $1::UUID,
+                    'user_id', $2::BIGINT,
+                    'username', (SELECT username FROM users WHERE id = $2),
+                    'typing', $3::BOOLEAN
+                )::TEXT
+            )
+            "#,
+            conversation_id,
+            self.user_id,
+            typing
+        )
+        .execute(&self.pool)
+        .await
+        {
+            tracing::error!("Failed to publish typing indicator: {}", e);
+        }
+    }
+
+    /// Stops every typing indicator whose expiry has passed.
+    async fn expire_typing(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<Uuid> = self
+            .typing_until
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(conversation_id, _)| *conversation_id)
+            .collect();
+
+        for conversation_id in expired {
+            self.set_typing(conversation_id, false).await;
+        }
+    }
+}
+
+/// Errors that abort a replay.
+#[derive(Debug)]
+enum ReplayError {
+    Database(sqlx::Error),
+    Socket(axum::Error),
+}
+
+/// Builds the `presence` frame describing the other participant of a conversation.
+async fn partner_presence(
+    pool: &PgPool,
+    presence: &Presence,
+    conversation_id: Uuid,
+    user_id: i64,
+) -> Result<WsServerFrame, sqlx::Error> {
+    let partner = sqlx::query!(
+        r#"
+        SELECT u.id, u.username, u.last_seen_at
+        FROM conversations c
+        JOIN users u
+          ON u.id = CASE WHEN c.user_id_1 = $2 THEN c.user_id_2 ELSE c.user_id_1 END
+        WHERE c.id = $1
+        "#,
+        conversation_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(WsServerFrame::Presence {
+        conversation_id,
+        username: partner.username,
+        online: presence.is_online(partner.id),
+        last_seen_at: partner.last_seen_at.map(format_timestamp),
+    })
+}
+
+/// Serializes a server frame into a versioned envelope and sends it to the client.
+async fn send_frame(socket: &mut WebSocket, frame: WsServerFrame) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(&WsEnvelope::new(frame)).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text.into())).await
+}
+
+/// Formats a timestamp as RFC3339 for inclusion in a frame.
+#[inline(always)]
+fn format_timestamp(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339)
+        .unwrap_or("Wasn't able to format timestamp".to_string())
 }
@@ -5,5 +5,13 @@
 
 /// Get current user profile endpoint handler.
 pub mod get;
+/// Linked identity provider account endpoint handlers.
+pub mod identities;
+/// API key management endpoint handlers.
+pub mod keys;
 /// Update user profile endpoint handler.
 pub mod patch;
+/// Two-factor authentication enrollment endpoint handlers.
+pub mod two_factor;
+/// Verification email resend endpoint handler.
+pub mod verify_email;
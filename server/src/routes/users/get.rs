@@ -4,8 +4,10 @@
 
 use api_types::users::get::UsersMeResponseInternal;
 use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::Access;
 use sqlx::PgPool;
 use utils::errors::error_response;
+use utils::scopes::Scope;
 
 /// Handles fetching the authenticated user's profile.
 ///
@@ -15,13 +17,15 @@ use utils::errors::error_response;
 /// # Arguments
 ///
 /// * `claims` - JWT claims containing the authenticated user's ID
+/// * `access` - What the request is allowed to do, checked for the `profile:read` scope
 /// * `pool` - The PostgreSQL connection pool
 ///
 /// # Returns
 ///
-/// - `200 OK` with user profile data (email, username, bio, timestamps)
+/// - `200 OK` with user profile data (email, verification status, username, bio, timestamps)
 /// - `400 BAD REQUEST` if the user ID in the JWT is invalid
 /// - `404 NOT FOUND` if the user doesn't exist in the database
+/// - `403 FORBIDDEN` if the API key lacks the `profile:read` scope
 /// - `500 INTERNAL SERVER ERROR` if a database error occurs
 ///
 /// # Example Response
@@ -31,19 +35,29 @@ use utils::errors::error_response;
 ///   "email": "john@example.com",
 ///   "username": "john_doe",
 ///   "bio": "Software developer",
+///   "email_verified": true,
 ///   "created_at": "2026-01-14T10:30:00Z",
 ///   "updated_at": "2026-01-14T10:30:00Z"
 /// }
 /// ```
-#[tracing::instrument(skip(pool, user_id))]
+#[tracing::instrument(skip(pool, user_id, access))]
 pub async fn api_users_get(
     Extension(user_id): Extension<i64>,
+    Extension(access): Extension<Access>,
     State(pool): State<PgPool>,
 ) -> impl IntoResponse {
+    if let Err(resp) = access.require(Scope::ProfileRead) {
+        return resp;
+    }
+
     let user = match sqlx::query_as!(
         UsersMeResponseInternal,
         r#"
-        SELECT email, username, bio, created_at, updated_at
+        SELECT email, username, bio, created_at, updated_at,
+               email_verified_at IS NOT NULL AS "email_verified!",
+               (SELECT ev.email FROM email_verifications ev
+                WHERE ev.user_id = users.id AND ev.email <> users.email
+                  AND ev.expires_at > NOW()) AS pending_email
         FROM users
         WHERE id = $1
         "#,
@@ -70,8 +84,16 @@ pub async fn api_users_get(
         email: user.email,
         username: user.username,
         bio: user.bio,
-        created_at: user.created_at.format(&time::format_description::well_known::Rfc3339).unwrap(),
-        updated_at: user.updated_at.format(&time::format_description::well_known::Rfc3339).unwrap(),
+        email_verified: user.email_verified,
+        pending_email: user.pending_email,
+        created_at: user
+            .created_at
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap(),
+        updated_at: user
+            .updated_at
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap(),
     };
 
     (StatusCode::OK, Json(user)).into_response()
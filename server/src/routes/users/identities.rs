@@ -0,0 +1,8 @@
+/// List linked identities endpoint handler.
+pub mod get;
+
+/// Link identity endpoint handler.
+pub mod post;
+
+/// Unlink identity endpoint handler.
+pub mod delete;
@@ -0,0 +1,66 @@
+//! Unlink identity endpoint handler.
+//!
+//! Handles unlinking identity provider accounts from the authenticated user.
+
+use api_types::users::identities::delete::{
+    ApiUsersIdentitiesDeleteRequest, ApiUsersIdentitiesDeleteResponse,
+};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::errors::error_response;
+
+/// Handles identity unlinking requests.
+///
+/// The identity can't be used to log in anymore, and can be linked again.
+/// Users who signed up with a provider can still log in with a password once
+/// they set one through the forgotten password flow. API keys cannot unlink
+/// identities; the request must be made with a session.
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+/// * `payload` - The delete request containing the identity ID
+///
+/// # Returns
+///
+/// - `200 OK` with unlinking confirmation
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `404 NOT FOUND` if the identity doesn't exist or isn't linked to the user
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, _session_id, payload), fields(identity_id = %payload.identity_id))]
+pub async fn api_users_identities_delete(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+    Json(payload): Json<ApiUsersIdentitiesDeleteRequest>,
+) -> impl IntoResponse {
+    let result = sqlx::query!(
+        "DELETE FROM user_identities WHERE id = $1 AND user_id = $2",
+        payload.identity_id,
+        user_id
+    )
+    .execute(&pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => {
+            error_response(StatusCode::NOT_FOUND, "Linked identity not found.")
+        }
+        Ok(_) => {
+            tracing::info!(user_id, "Unlinked identity");
+            let response = ApiUsersIdentitiesDeleteResponse {
+                message: "Identity unlinked successfully".to_string(),
+            };
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to unlink identity");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while unlinking the identity.",
+            )
+        }
+    }
+}
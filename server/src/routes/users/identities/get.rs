@@ -0,0 +1,99 @@
+//! List linked identities endpoint handler.
+//!
+//! Handles listing the identity provider accounts linked to the authenticated user.
+
+use crate::oidc::OidcProviders;
+use api_types::users::identities::get::{ApiUsersIdentitiesGetResponse, ApiUsersIdentity};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use utils::errors::error_response;
+
+/// Row structure for linked identities from database.
+struct IdentityRow {
+    id: i64,
+    issuer: String,
+    email: Option<String>,
+    created_at: OffsetDateTime,
+    last_login_at: Option<OffsetDateTime>,
+}
+
+/// Handles linked identity listing requests.
+///
+/// Identities are returned oldest first, with the ID of their provider if it
+/// is still configured. API keys cannot list identities; the request must be
+/// made with a session.
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+/// * `providers` - The configured identity providers
+///
+/// # Returns
+///
+/// - `200 OK` with the list of linked identities on success
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, providers, user_id, _session_id))]
+pub async fn api_users_identities_get(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+    State(providers): State<OidcProviders>,
+) -> impl IntoResponse {
+    let result = sqlx::query_as!(
+        IdentityRow,
+        r#"
+        SELECT id, issuer, email, created_at, last_login_at
+        FROM user_identities
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(&pool)
+    .await;
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to list linked identities");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while listing linked identities.",
+            );
+        }
+    };
+
+    let identities = rows
+        .into_iter()
+        .map(|row| ApiUsersIdentity {
+            id: row.id,
+            provider: providers
+                .list()
+                .iter()
+                .find(|provider| provider.issuer() == row.issuer)
+                .map(|provider| provider.id.clone()),
+            issuer: row.issuer,
+            email: row.email,
+            created_at: format_timestamp(row.created_at),
+            last_login_at: row.last_login_at.map(format_timestamp),
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(ApiUsersIdentitiesGetResponse { identities }),
+    )
+        .into_response()
+}
+
+/// Formats a timestamp as RFC3339 for inclusion in the response.
+#[inline(always)]
+fn format_timestamp(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339)
+        .unwrap_or("Wasn't able to format timestamp".to_string())
+}
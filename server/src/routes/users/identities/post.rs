@@ -0,0 +1,76 @@
+//! Link identity endpoint handler.
+//!
+//! Handles starting to link an identity provider account to the authenticated user.
+
+use crate::oidc::{OidcProviders, start_login};
+use api_types::users::identities::post::{
+    ApiUsersIdentitiesPostRequest, ApiUsersIdentitiesPostResponse,
+};
+use axum::http::header::SET_COOKIE;
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::cookies::build_oidc_state_cookie;
+use utils::errors::error_response;
+
+/// Handles identity linking requests.
+///
+/// This endpoint:
+/// 1. Stores a new login at the provider, to be linked to the user
+/// 2. Sets the state cookie binding the login to this browser
+/// 3. Returns the provider's authorization URL, which the client navigates to
+///
+/// Once the user logged into the provider, it redirects back to
+/// `/api/auth/oidc/{provider}/callback`, which links the identity. API keys
+/// cannot link identities; the request must be made with a session.
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+/// * `providers` - The configured identity providers
+/// * `payload` - The link request containing the provider ID
+///
+/// # Returns
+///
+/// - `200 OK` with the authorization URL on success
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `404 NOT FOUND` if no provider is configured with the ID
+/// - `500 INTERNAL SERVER ERROR` if the login could not be stored
+/// - `502 BAD GATEWAY` if the provider could not be discovered
+#[tracing::instrument(skip(pool, providers, user_id, _session_id, payload), fields(provider = %payload.provider))]
+pub async fn api_users_identities_post(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+    State(providers): State<OidcProviders>,
+    Json(payload): Json<ApiUsersIdentitiesPostRequest>,
+) -> impl IntoResponse {
+    let provider = match providers.get(&payload.provider) {
+        Ok(provider) => provider,
+        Err(e) => return e.into_response(),
+    };
+
+    let login = match start_login(&pool, &providers, provider, Some(user_id)).await {
+        Ok(login) => login,
+        Err(e) => return e.into_response(),
+    };
+
+    let cookie = match build_oidc_state_cookie(login.state) {
+        Ok(cookie) => cookie,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to build cookie.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("An error occurred on our end: {}", e),
+            );
+        }
+    };
+
+    let response = ApiUsersIdentitiesPostResponse {
+        authorize_url: login.authorize_url,
+    };
+    let mut resp = (StatusCode::OK, Json(response)).into_response();
+    resp.headers_mut().append(SET_COOKIE, cookie);
+    resp
+}
@@ -0,0 +1,8 @@
+/// List API keys endpoint handler.
+pub mod get;
+
+/// Create API key endpoint handler.
+pub mod post;
+
+/// Revoke API key endpoint handler.
+pub mod delete;
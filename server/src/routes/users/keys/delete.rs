@@ -0,0 +1,62 @@
+//! Revoke API key endpoint handler.
+//!
+//! Handles revoking API keys of the authenticated user.
+
+use api_types::users::keys::delete::{ApiUsersKeysDeleteRequest, ApiUsersKeysDeleteResponse};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::errors::error_response;
+
+/// Handles API key revocation requests.
+///
+/// The key is deleted, so requests made with it are rejected right away.
+/// API keys cannot revoke API keys; the request must be made with a session.
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+/// * `payload` - The delete request containing the key ID
+///
+/// # Returns
+///
+/// - `200 OK` with revocation confirmation
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `404 NOT FOUND` if the key doesn't exist or isn't owned by the user
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, _session_id), fields(key_id = %payload.key_id))]
+pub async fn api_users_keys_delete(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+    Json(payload): Json<ApiUsersKeysDeleteRequest>,
+) -> impl IntoResponse {
+    let result = sqlx::query!(
+        "DELETE FROM api_keys WHERE id = $1 AND user_id = $2",
+        payload.key_id,
+        user_id
+    )
+    .execute(&pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => {
+            error_response(StatusCode::NOT_FOUND, "API key not found.")
+        }
+        Ok(_) => {
+            tracing::info!(user_id, "Revoked API key");
+            let response = ApiUsersKeysDeleteResponse {
+                message: "API key revoked successfully".to_string(),
+            };
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to revoke API key");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while revoking the API key.",
+            )
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! List API keys endpoint handler.
+//!
+//! Handles listing the API keys of the authenticated user.
+
+use api_types::users::keys::get::{ApiKeyItem, ApiUsersKeysGetResponse};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use utils::errors::error_response;
+use utils::scopes::Scope;
+use uuid::Uuid;
+
+/// Row structure for API keys from database.
+struct ApiKeyRow {
+    id: Uuid,
+    name: String,
+    prefix: String,
+    scopes: Vec<String>,
+    created_at: OffsetDateTime,
+    last_used_at: Option<OffsetDateTime>,
+}
+
+/// Handles API key listing requests.
+///
+/// This endpoint:
+/// 1. Extracts the user ID from the authentication cookie
+/// 2. Retrieves every API key of the user
+/// 3. Returns them ordered by `created_at`, most recent first, without the keys themselves
+///
+/// API keys cannot list API keys; the request must be made with a session.
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+///
+/// # Returns
+///
+/// - `200 OK` with the list of API keys on success
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, _session_id))]
+pub async fn api_users_keys_get(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+) -> impl IntoResponse {
+    let result = sqlx::query_as!(
+        ApiKeyRow,
+        r#"
+        SELECT id, name, prefix, scopes, created_at, last_used_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(&pool)
+    .await;
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to list API keys");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while listing API keys.",
+            );
+        }
+    };
+
+    let keys = rows
+        .into_iter()
+        .map(|row| ApiKeyItem {
+            id: row.id,
+            name: row.name,
+            prefix: row.prefix,
+            scopes: row
+                .scopes
+                .iter()
+                .filter_map(|scope| scope.parse::<Scope>().ok())
+                .collect(),
+            created_at: format_timestamp(row.created_at),
+            last_used_at: row.last_used_at.map(format_timestamp),
+        })
+        .collect();
+
+    (StatusCode::OK, Json(ApiUsersKeysGetResponse { keys })).into_response()
+}
+
+/// Formats a timestamp as RFC3339 for inclusion in the response.
+#[inline(always)]
+fn format_timestamp(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339)
+        .unwrap_or("Wasn't able to format timestamp".to_string())
+}
@@ -0,0 +1,126 @@
+//! Create API key endpoint handler.
+//!
+//! Handles creating scoped API keys for bots and integrations.
+
+use api_types::users::keys::post::{ApiUsersKeysPostRequest, ApiUsersKeysPostResponse};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use time::format_description::well_known::Rfc3339;
+use utils::errors::error_response;
+use utils::scopes::Scope;
+use utils::tokens::{API_KEY_PREFIX, generate_token, hash_token};
+
+/// Maximum number of API keys a user can have.
+const MAX_API_KEYS: i64 = 25;
+
+/// Number of random characters of the key kept as its displayed prefix.
+const PREFIX_LENGTH: usize = 8;
+
+/// Handles API key creation requests.
+///
+/// This endpoint:
+/// 1. Validates the name and scopes
+/// 2. Generates a random key and stores its SHA-256 digest, unless the user
+///    already has [`MAX_API_KEYS`] keys
+/// 3. Returns the key, which is never shown again
+///
+/// API keys cannot create API keys; the request must be made with a session.
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+/// * `payload` - The name and scopes of the key
+///
+/// # Returns
+///
+/// - `201 CREATED` with the key on success
+/// - `400 BAD REQUEST` if validation fails or the user has too many keys
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, _session_id), fields(scopes = ?payload.scopes))]
+pub async fn api_users_keys_post(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+    Json(payload): Json<ApiUsersKeysPostRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = payload.validate() {
+        tracing::debug!(error = ?e, "Invalid API key request");
+        return error_response(StatusCode::BAD_REQUEST, e);
+    }
+
+    let name = payload.name.trim().to_string();
+    // Deduplicate the scopes, keeping them in their documented order
+    let scopes: Vec<Scope> = Scope::ALL
+        .into_iter()
+        .filter(|scope| payload.scopes.contains(scope))
+        .collect();
+    let stored_scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+
+    let key = format!("{API_KEY_PREFIX}{}", generate_token());
+    let prefix: String = key
+        .chars()
+        .take(API_KEY_PREFIX.len() + PREFIX_LENGTH)
+        .collect();
+
+    let result: Result<Option<_>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        // Lock the user so that concurrent requests can't exceed the limit
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes)
+            SELECT $1, $2, $3, $4, $5
+            WHERE (SELECT COUNT(*) FROM api_keys WHERE user_id = $1) < $6
+            RETURNING id, created_at
+            "#,
+            user_id,
+            name,
+            prefix,
+            hash_token(&key),
+            &stored_scopes,
+            MAX_API_KEYS
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(row)
+    }
+    .await;
+
+    match result {
+        Ok(Some(row)) => {
+            tracing::info!(user_id, key_id = %row.id, "Created API key");
+            let response = ApiUsersKeysPostResponse {
+                id: row.id,
+                name,
+                key,
+                prefix,
+                scopes,
+                created_at: row
+                    .created_at
+                    .format(&Rfc3339)
+                    .unwrap_or("Wasn't able to format timestamp".to_string()),
+            };
+            (StatusCode::CREATED, Json(response)).into_response()
+        }
+        Ok(None) => error_response(
+            StatusCode::BAD_REQUEST,
+            format!("You can have at most {MAX_API_KEYS} API keys. Revoke one first."),
+        ),
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to create API key");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while creating the API key.",
+            )
+        }
+    }
+}
@@ -2,13 +2,19 @@
 //!
 //! Handles updating user profile information including email, username, and bio.
 
+use crate::mailer::SharedMailer;
+use crate::sessions::revoke_other_sessions;
+use crate::two_factor::verify_totp;
+use crate::verification::start_verification;
 use api_types::{
-    auth::EMAIL_REGEX,
+    auth::{ApiPasswordPolicyErrorResponse, EMAIL_REGEX},
     users::patch::{UsersUpdateRequest, UsersUpdateResponse},
 };
 use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
 use sqlx::PgPool;
 use utils::errors::error_response;
+use utils::password_policy::{PasswordContext, check_password};
 
 #[derive(sqlx::FromRow)]
 struct UserUpdateFields {
@@ -16,6 +22,8 @@ struct UserUpdateFields {
     username: String,
     bio: Option<String>,
     password_hash: String,
+    totp_secret: Option<String>,
+    totp_enabled: bool,
 }
 
 /// Handles user profile update requests.
@@ -23,33 +31,47 @@ struct UserUpdateFields {
 /// This endpoint:
 /// 1. Retrieves the current user's profile information
 /// 2. Validates the provided email and username if they differ from current values
-/// 3. Checks that the new email/username don't already exist for other users
-/// 4. Updates the user's profile in the database
-/// 5. Returns the updated profile information
+/// 3. Requires a current TOTP code to change the email or password if the user
+///    enabled two-factor authentication
+/// 4. Checks that the new email/username don't already exist for other users
+/// 5. Updates the user's profile in the database
+/// 6. Revokes every other session of the user if the password changed
+/// 7. Emails a verification link to a new email address, which only replaces
+///    the current one once confirmed
+/// 8. Returns the updated fields and the pending email address
 ///
 /// # Arguments
 ///
 /// * `pool` - The PostgreSQL connection pool
+/// * `mailer` - The mailer delivering the verification link
 /// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `session_id` - The session the request is authenticated with, which stays active
 /// * `payload` - The update request with new profile information
 ///
 /// # Returns
 ///
 /// - `200 OK` with the updated user profile
-/// - `400 BAD REQUEST` if validation fails (invalid email, email/username already exists)
+/// - `400 BAD REQUEST` if validation fails (invalid email, email/username already exists),
+///   with the violations if the new password breaks the password policy
+/// - `401 UNAUTHORIZED` if the password is wrong, or the TOTP code is missing or wrong
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
 /// - `404 NOT FOUND` if the user doesn't exist
+/// - `409 CONFLICT` if another account uses the new email address
 /// - `500 INTERNAL SERVER ERROR` if database operations fail
-#[tracing::instrument(skip(pool, user_id, payload))]
+#[tracing::instrument(skip(pool, mailer, user_id, session_id, payload))]
 pub async fn api_users_patch(
     State(pool): State<PgPool>,
+    State(mailer): State<SharedMailer>,
     Extension(user_id): Extension<i64>,
+    session_id: SessionId,
     Json(payload): Json<UsersUpdateRequest>,
 ) -> impl IntoResponse {
     // Query the email username bio and password from the user id
     let user = match sqlx::query_as!(
         UserUpdateFields,
         r#"
-        SELECT email, username, bio, password_hash
+        SELECT email, username, bio, password_hash, totp_secret,
+               totp_enabled_at IS NOT NULL AS "totp_enabled!"
         FROM users
         WHERE id = $1
         "#,
@@ -76,7 +98,7 @@ pub async fn api_users_patch(
     let hash_result = utils::hashing::verify_password(&pswd, &user.password_hash);
 
     let verified = match hash_result {
-        Ok(valid) => valid,
+        Ok(verification) => verification.is_match(),
         Err(e) => {
             tracing::error!(error = ?e, "An error occurred while verifying password");
             return error_response(
@@ -97,7 +119,7 @@ pub async fn api_users_patch(
     let new_username = payload.username.as_deref().unwrap_or(&user.username);
     let new_bio = payload.bio.as_ref().or(user.bio.as_ref());
 
-    if !EMAIL_REGEX.is_match(&new_email) {
+    if !EMAIL_REGEX.is_match(new_email) {
         tracing::debug!("Invalid email address during profile update");
         return error_response(StatusCode::BAD_REQUEST, "Email format is invalid");
     }
@@ -105,9 +127,7 @@ pub async fn api_users_patch(
     let mut updated_fields = vec![];
     let mut new_password_hash = user.password_hash.clone();
 
-    if payload.email.is_some() && payload.email.as_deref() != Some(user.email.as_str()) {
-        updated_fields.push("email".to_string());
-    }
+    let email_changed = new_email != user.email;
     if payload.username.is_some() && payload.username.as_deref() != Some(user.username.as_str()) {
         updated_fields.push("username".to_string());
     }
@@ -115,16 +135,73 @@ pub async fn api_users_patch(
         updated_fields.push("bio".to_string());
     }
 
-    // Handle password update if a new password is provided
-    if let Some(ref new_password) = payload.new_password {
-        // Validate the new password meets requirements
-        match utils::hashing::is_password_suitable(new_password) {
-            Ok(_) => (),
+    // Changing the credentials of a 2FA account requires a current code
+    let credentials_changed = email_changed || payload.new_password.is_some();
+    if credentials_changed
+        && user.totp_enabled
+        && let Some(ref secret) = user.totp_secret
+    {
+        let Some(ref code) = payload.totp_code else {
+            return error_response(
+                StatusCode::UNAUTHORIZED,
+                "A two-factor code is required to change your email or password",
+            );
+        };
+        match verify_totp(&pool, user_id, secret, code).await {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::warn!(user_id, "Invalid two-factor code during profile update");
+                return error_response(StatusCode::UNAUTHORIZED, "Invalid two-factor code");
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to verify two-factor code");
+                return error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error during two-factor verification",
+                );
+            }
+        }
+    }
+
+    if email_changed {
+        match sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE email = $1 AND id <> $2) AS "exists!""#,
+            new_email,
+            user_id
+        )
+        .fetch_one(&pool)
+        .await
+        {
+            Ok(false) => {}
+            Ok(true) => {
+                tracing::debug!(user_id, "Attempt to change to an existing email");
+                return error_response(StatusCode::CONFLICT, "Email already exists");
+            }
             Err(e) => {
-                tracing::warn!(error = ?e, "New password is not suitable: {e}");
-                return error_response(StatusCode::BAD_REQUEST, e);
+                tracing::error!(error = ?e, "Failed to query existing emails");
+                return error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Database error while querying",
+                );
             }
         }
+    }
+
+    // Handle password update if a new password is provided
+    if let Some(ref new_password) = payload.new_password {
+        // Validate the new password against the password policy
+        let context = PasswordContext {
+            username: Some(new_username),
+            email: Some(new_email),
+        };
+        if let Err(violations) = check_password(new_password, context).await {
+            tracing::info!(%violations, "New password breaks the password policy");
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiPasswordPolicyErrorResponse::from(violations)),
+            )
+                .into_response();
+        }
         // Hash the new password
         match utils::hashing::hash_password(new_password) {
             Ok(h) => {
@@ -138,14 +215,13 @@ pub async fn api_users_patch(
         }
     }
 
-    // Update the user in the database
+    // Update the user in the database, the email address changes once verified
     match sqlx::query!(
         r#"
         UPDATE users
-        SET email = $1, username = $2, bio = $3, password_hash = $4, updated_at = NOW()
-        WHERE id = $5
+        SET username = $1, bio = $2, password_hash = $3, updated_at = NOW()
+        WHERE id = $4
         "#,
-        new_email,
         new_username,
         new_bio,
         new_password_hash,
@@ -154,13 +230,63 @@ pub async fn api_users_patch(
     .execute(&pool)
     .await
     {
-        Ok(_) => {
-            let response = UsersUpdateResponse { updated_fields };
-            (StatusCode::OK, Json(response)).into_response()
-        }
+        Ok(_) => {}
         Err(e) => {
             tracing::error!(error = ?e, "Failed to update user");
-            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update user")
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update user");
         }
     }
+
+    // A password change signs out every other device
+    if updated_fields.iter().any(|field| field == "password") {
+        match revoke_other_sessions(&pool, user_id, session_id.0).await {
+            Ok(revoked) => tracing::info!(
+                user_id,
+                revoked,
+                "Revoked other sessions after password change"
+            ),
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to revoke other sessions after password change");
+                return error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Password updated, but failed to sign out other devices",
+                );
+            }
+        }
+    }
+
+    let pending_email = if email_changed {
+        if let Err(e) = start_verification(&pool, &mailer, user_id, new_email).await {
+            tracing::error!(error = ?e, "Failed to create email verification");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to send the verification email for your new address",
+            );
+        }
+        Some(new_email.to_string())
+    } else {
+        // Setting the current address again cancels a pending change
+        if payload.email.is_some()
+            && let Err(e) = sqlx::query!(
+                "DELETE FROM email_verifications WHERE user_id = $1 AND email <> $2",
+                user_id,
+                user.email
+            )
+            .execute(&pool)
+            .await
+        {
+            tracing::error!(error = ?e, "Failed to cancel pending email change");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to cancel the pending email change",
+            );
+        }
+        None
+    };
+
+    let response = UsersUpdateResponse {
+        updated_fields,
+        pending_email,
+    };
+    (StatusCode::OK, Json(response)).into_response()
 }
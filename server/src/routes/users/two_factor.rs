@@ -0,0 +1,8 @@
+/// Start enrollment endpoint handler.
+pub mod post;
+
+/// Confirm enrollment endpoint handler.
+pub mod confirm;
+
+/// Disable two-factor authentication endpoint handler.
+pub mod delete;
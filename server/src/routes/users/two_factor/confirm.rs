@@ -0,0 +1,117 @@
+//! Two-factor enrollment confirmation endpoint handler.
+//!
+//! Handles enabling two-factor authentication once the user proved their
+//! authenticator app produces valid codes.
+
+use crate::two_factor::verify_totp;
+use api_types::users::two_factor::confirm::{
+    ApiUsersTwoFactorConfirmRequest, ApiUsersTwoFactorConfirmResponse,
+};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::errors::error_response;
+
+/// Handles two-factor enrollment confirmation requests.
+///
+/// This endpoint:
+/// 1. Retrieves the secret generated by `POST /api/users/2fa`
+/// 2. Verifies the code against it
+/// 3. Enables two-factor authentication, which is required at login from now on
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+/// * `payload` - The current TOTP code
+///
+/// # Returns
+///
+/// - `200 OK` once two-factor authentication is enabled
+/// - `400 BAD REQUEST` if enrollment was not started or the code is invalid
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `409 CONFLICT` if two-factor authentication is already enabled
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, _session_id, payload))]
+pub async fn api_users_two_factor_confirm_post(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+    Json(payload): Json<ApiUsersTwoFactorConfirmRequest>,
+) -> impl IntoResponse {
+    let user = match sqlx::query!(
+        "SELECT totp_secret, totp_enabled_at FROM users WHERE id = $1",
+        user_id
+    )
+    .fetch_one(&pool)
+    .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to fetch two-factor state");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while confirming two-factor authentication.",
+            );
+        }
+    };
+
+    if user.totp_enabled_at.is_some() {
+        return error_response(
+            StatusCode::CONFLICT,
+            "Two-factor authentication is already enabled.",
+        );
+    }
+    let Some(secret) = user.totp_secret else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "Start enrollment with POST /api/users/2fa first.",
+        );
+    };
+
+    match verify_totp(&pool, user_id, &secret, &payload.code).await {
+        Ok(true) => {}
+        Ok(false) => return error_response(StatusCode::BAD_REQUEST, "Invalid two-factor code"),
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to verify two-factor code");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while confirming two-factor authentication.",
+            );
+        }
+    }
+
+    // Guard against a concurrent enrollment replacing the secret in between
+    let result = sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_enabled_at = NOW()
+        WHERE id = $1 AND totp_secret = $2 AND totp_enabled_at IS NULL
+        "#,
+        user_id,
+        secret
+    )
+    .execute(&pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 1 => {
+            tracing::info!(user_id, "Enabled two-factor authentication");
+            let response = ApiUsersTwoFactorConfirmResponse {
+                message: "Two-factor authentication enabled".to_string(),
+            };
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Ok(_) => error_response(
+            StatusCode::CONFLICT,
+            "Two-factor enrollment changed in the meantime, please try again.",
+        ),
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to enable two-factor authentication");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while confirming two-factor authentication.",
+            )
+        }
+    }
+}
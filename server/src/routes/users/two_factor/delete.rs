@@ -0,0 +1,136 @@
+//! Disable two-factor authentication endpoint handler.
+//!
+//! Handles turning two-factor authentication off, or cancelling an
+//! unconfirmed enrollment.
+
+use crate::two_factor::verify_second_factor;
+use api_types::users::two_factor::delete::{
+    ApiUsersTwoFactorDeleteRequest, ApiUsersTwoFactorDeleteResponse,
+};
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::errors::error_response;
+
+/// Handles requests to disable two-factor authentication.
+///
+/// This endpoint:
+/// 1. Verifies the user's password
+/// 2. If two-factor authentication is enabled, verifies a TOTP or recovery code
+/// 3. Removes the secret, the recovery codes and pending login challenges
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+/// * `payload` - The password and second factor
+///
+/// # Returns
+///
+/// - `200 OK` once two-factor authentication is disabled
+/// - `400 BAD REQUEST` if two-factor authentication is not set up
+/// - `401 UNAUTHORIZED` if the password or code is wrong, or the code is missing
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, user_id, _session_id, payload))]
+pub async fn api_users_two_factor_delete(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+    Json(payload): Json<ApiUsersTwoFactorDeleteRequest>,
+) -> impl IntoResponse {
+    let user = match sqlx::query!(
+        "SELECT password_hash, totp_secret, totp_enabled_at FROM users WHERE id = $1",
+        user_id
+    )
+    .fetch_one(&pool)
+    .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to fetch two-factor state");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while disabling two-factor authentication.",
+            );
+        }
+    };
+
+    match utils::hashing::verify_password(&payload.password, &user.password_hash) {
+        Ok(verification) if verification.is_match() => {}
+        Ok(_) => return error_response(StatusCode::UNAUTHORIZED, "Invalid password"),
+        Err(e) => {
+            tracing::error!(error = ?e, "An error occurred while verifying password");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error during password verification",
+            );
+        }
+    }
+
+    let Some(secret) = user.totp_secret else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "Two-factor authentication is not enabled.",
+        );
+    };
+
+    // An unconfirmed enrollment can be cancelled with the password alone
+    if user.totp_enabled_at.is_some() {
+        let Some(code) = payload.code.as_deref() else {
+            return error_response(StatusCode::UNAUTHORIZED, "A two-factor code is required");
+        };
+        match verify_second_factor(&pool, user_id, &secret, code).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return error_response(StatusCode::UNAUTHORIZED, "Invalid two-factor code");
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, user_id, "Failed to verify two-factor code");
+                return error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "An error occurred while disabling two-factor authentication.",
+                );
+            }
+        }
+    }
+
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM mfa_challenges WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(user_id, "Disabled two-factor authentication");
+            let response = ApiUsersTwoFactorDeleteResponse {
+                message: "Two-factor authentication disabled".to_string(),
+            };
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to disable two-factor authentication");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while disabling two-factor authentication.",
+            )
+        }
+    }
+}
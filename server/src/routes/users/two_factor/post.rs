@@ -0,0 +1,102 @@
+//! Two-factor enrollment endpoint handler.
+//!
+//! Handles generating a TOTP secret and recovery codes for the authenticated user.
+
+use crate::two_factor::{issuer, replace_recovery_codes};
+use api_types::users::two_factor::post::ApiUsersTwoFactorPostResponse;
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::encryption::encrypt_totp_secret;
+use utils::errors::error_response;
+use utils::totp;
+
+/// Handles two-factor enrollment requests.
+///
+/// This endpoint:
+/// 1. Ensures two-factor authentication is not enabled yet
+/// 2. Generates a new TOTP secret and recovery codes, replacing those of an
+///    unconfirmed enrollment; the secret is stored encrypted
+/// 3. Returns the secret, its `otpauth://` URI and the recovery codes
+///
+/// Two-factor authentication is only enforced once the first code is
+/// confirmed at `/api/users/2fa/confirm`.
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+///
+/// # Returns
+///
+/// - `200 OK` with the secret and recovery codes
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `409 CONFLICT` if two-factor authentication is already enabled
+/// - `500 INTERNAL SERVER ERROR` if the secret can't be encrypted or database operation fails
+#[tracing::instrument(skip(pool, user_id, _session_id))]
+pub async fn api_users_two_factor_post(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+) -> impl IntoResponse {
+    let secret = totp::generate_secret();
+    let stored_secret = match encrypt_totp_secret(&secret, user_id) {
+        Ok(stored_secret) => stored_secret,
+        Err(e) => {
+            tracing::error!(error = %e, user_id, "Failed to encrypt TOTP secret");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while enabling two-factor authentication.",
+            );
+        }
+    };
+
+    let result: Result<Option<(String, Vec<String>)>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        // Only replace the secret of users who did not confirm 2FA yet
+        let Some(username) = sqlx::query_scalar!(
+            r#"
+            UPDATE users
+            SET totp_secret = $2, totp_last_step = NULL
+            WHERE id = $1 AND totp_enabled_at IS NULL
+            RETURNING username
+            "#,
+            user_id,
+            stored_secret
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let recovery_codes = replace_recovery_codes(&mut tx, user_id).await?;
+        tx.commit().await?;
+        Ok(Some((username, recovery_codes)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((username, recovery_codes))) => {
+            tracing::info!(user_id, "Started two-factor enrollment");
+            let response = ApiUsersTwoFactorPostResponse {
+                otpauth_uri: totp::otpauth_uri(&issuer(), &username, &secret),
+                secret,
+                recovery_codes,
+            };
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Ok(None) => error_response(
+            StatusCode::CONFLICT,
+            "Two-factor authentication is already enabled.",
+        ),
+        Err(e) => {
+            tracing::error!(error = ?e, user_id, "Failed to start two-factor enrollment");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred while enabling two-factor authentication.",
+            )
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Verification email resend endpoint handler.
+//!
+//! Handles sending a new verification link to the authenticated user.
+
+use crate::mailer::SharedMailer;
+use crate::verification::start_verification;
+use api_types::users::verify_email::ApiUsersVerifyEmailResponse;
+use axum::{Extension, Json, extract::State, http::StatusCode, response::IntoResponse};
+use middleware::SessionId;
+use sqlx::PgPool;
+use utils::errors::error_response;
+
+/// Handles requests to resend the verification email.
+///
+/// The link is sent to the address the user is changing to if there is one,
+/// and to their current address otherwise. Previously sent links stop working.
+///
+/// # Arguments
+///
+/// * `user_id` - The authenticated user's ID from the JWT cookie
+/// * `pool` - The PostgreSQL connection pool
+/// * `mailer` - The mailer delivering the verification link
+///
+/// # Returns
+///
+/// - `200 OK` with the address the link was sent to
+/// - `400 BAD REQUEST` if the email address is verified and no change is pending
+/// - `403 FORBIDDEN` if the request is authenticated with an API key
+/// - `404 NOT FOUND` if the user doesn't exist
+/// - `500 INTERNAL SERVER ERROR` if database operation fails
+#[tracing::instrument(skip(pool, mailer, user_id, _session_id))]
+pub async fn api_users_verify_email_post(
+    Extension(user_id): Extension<i64>,
+    _session_id: SessionId,
+    State(pool): State<PgPool>,
+    State(mailer): State<SharedMailer>,
+) -> impl IntoResponse {
+    let user = match sqlx::query!(
+        r#"
+        SELECT email, email_verified_at IS NOT NULL AS "verified!",
+               (SELECT ev.email FROM email_verifications ev
+                WHERE ev.user_id = users.id AND ev.email <> users.email) AS pending_email
+        FROM users
+        WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "User not found"),
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to query email verification.");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while sending the email",
+            );
+        }
+    };
+
+    let email = match user.pending_email {
+        Some(pending_email) => pending_email,
+        None if !user.verified => user.email,
+        None => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "Your email address is already verified",
+            );
+        }
+    };
+
+    if let Err(e) = start_verification(&pool, &mailer, user_id, &email).await {
+        tracing::error!(error = ?e, "Failed to create email verification.");
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "An error occurred on our end while sending the email",
+        );
+    }
+
+    tracing::info!(user_id, "Verification email resent");
+    let resp = ApiUsersVerifyEmailResponse {
+        ok: true,
+        message: "A verification link was sent to your email address".to_string(),
+        email,
+    };
+    (StatusCode::OK, Json(resp)).into_response()
+}
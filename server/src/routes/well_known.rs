@@ -0,0 +1,2 @@
+/// JSON Web Key Set endpoint handler.
+pub mod jwks;
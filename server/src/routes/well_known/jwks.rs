@@ -0,0 +1,42 @@
+//! JSON Web Key Set endpoint handler.
+//!
+//! Publishes the public keys access tokens are signed with, so that other
+//! services can verify them without the shared secret.
+
+use axum::Json;
+use axum::http::header::CACHE_CONTROL;
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use utils::errors::error_response;
+
+/// How long clients may cache the key set, in seconds. Short enough for a
+/// newly added key to be picked up before it becomes the active one.
+const CACHE_MAX_AGE: &str = "max-age=300";
+
+/// Handles JSON Web Key Set requests.
+///
+/// Lists the `ES256` and `EdDSA` keys of the keyring that still verify tokens,
+/// identified by their `kid`. HMAC secrets are never published.
+///
+/// # Returns
+///
+/// - `200 OK` with the key set (RFC 7517), possibly empty
+/// - `500 INTERNAL SERVER ERROR` if the keys are not configured correctly
+#[tracing::instrument]
+pub async fn well_known_jwks_get() -> impl IntoResponse {
+    match utils::jwt::jwks() {
+        Ok(jwks) => {
+            let mut resp = (StatusCode::OK, Json(jwks)).into_response();
+            resp.headers_mut()
+                .insert(CACHE_CONTROL, HeaderValue::from_static(CACHE_MAX_AGE));
+            resp
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to list the signing keys.");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An error occurred on our end while listing the signing keys",
+            )
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! Session issuance, refresh and revocation.
+//!
+//! A session is a family of rotating refresh tokens. Every short-lived JWT
+//! access token names the session it belongs to in its `jti` claim. Sessions
+//! are stored in the `sessions` table so that users can list them and revoke
+//! them before their tokens expire; the auth middleware rejects access tokens
+//! whose session was revoked, and refreshing fails for them.
+//!
+//! Refresh tokens are opaque and single-use: every refresh exchanges the
+//! presented token for a new one. Presenting an already exchanged token means
+//! it was stolen (or the legitimate client lost the race against a thief), so
+//! the whole session is revoked.
+
+use api_types::auth::ApiAuthTokens;
+use axum::http::header::{SET_COOKIE, USER_AGENT};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use sqlx::{PgConnection, PgPool};
+use std::net::SocketAddr;
+use utils::cookies::{
+    build_access_cookie, build_access_removal_cookie, build_refresh_cookie,
+    build_refresh_removal_cookie,
+};
+use utils::errors::error_response;
+use utils::jwt::{ACCESS_TOKEN_DURATION, SESSION_DURATION};
+use utils::proxies::client_ip;
+use utils::tokens::{generate_token, hash_token};
+use uuid::Uuid;
+
+/// Maximum number of characters of the user agent stored with a session.
+const USER_AGENT_LENGTH: usize = 256;
+
+/// How often ended sessions and their rotated refresh tokens are purged.
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// How long sessions are kept after they were revoked or expired.
+const SESSION_RETENTION: time::Duration = time::Duration::days(30);
+
+/// Describes the client a session is issued to, so users can recognize their devices.
+pub(crate) struct SessionMeta {
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl SessionMeta {
+    /// Collects the client's user agent and IP address from the request.
+    ///
+    /// The IP address is the socket's, or the one forwarded by a trusted
+    /// proxy, see [`utils::proxies`].
+    pub(crate) fn from_request(headers: &HeaderMap, addr: SocketAddr) -> Self {
+        let user_agent = headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(USER_AGENT_LENGTH).collect());
+
+        Self {
+            user_agent,
+            ip_address: Some(client_ip(headers, addr.ip()).to_string()),
+        }
+    }
+
+    /// Returns the client's user agent.
+    pub(crate) fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    /// Returns the client's IP address.
+    pub(crate) fn ip_address(&self) -> Option<&str> {
+        self.ip_address.as_deref()
+    }
+}
+
+/// Tokens issued for a session.
+pub(crate) struct SessionTokens {
+    /// Short-lived JWT access token.
+    pub access_token: String,
+    /// Opaque refresh token, exchanged for new tokens at `/api/auth/refresh`.
+    pub refresh_token: String,
+}
+
+impl SessionTokens {
+    /// Signs an access token for the session, next to its refresh token.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(SessionTokens)` on success
+    /// - `Err(Response)` - An error response if signing fails
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn new(
+        user_id: i64,
+        session_id: Uuid,
+        refresh_token: String,
+    ) -> Result<Self, Response> {
+        let access_token = utils::jwt::sign_jwt(user_id.to_string(), session_id.to_string())
+            .map_err(|e| {
+                tracing::error!(error = ?e, "Failed to sign JWT.");
+                error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("An error occurred on our end: {}", e),
+                )
+            })?;
+
+        Ok(Self {
+            access_token,
+            refresh_token,
+        })
+    }
+
+    /// Adds the Set-Cookie headers carrying both tokens to a response.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` once both cookies were added
+    /// - `Err(Response)` - An error response if a cookie could not be built
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn set_cookies(&self, resp: &mut Response) -> Result<(), Response> {
+        let cookies = build_access_cookie(self.access_token.as_str())
+            .and_then(|access| Ok((access, build_refresh_cookie(self.refresh_token.as_str())?)));
+
+        let (access, refresh) = cookies.map_err(|e| {
+            tracing::error!(error = ?e, "Failed to build cookie.");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("An error occurred on our end: {}", e),
+            )
+        })?;
+
+        resp.headers_mut().append(SET_COOKIE, access);
+        resp.headers_mut().append(SET_COOKIE, refresh);
+        Ok(())
+    }
+}
+
+impl From<SessionTokens> for ApiAuthTokens {
+    fn from(tokens: SessionTokens) -> Self {
+        ApiAuthTokens {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_in: ACCESS_TOKEN_DURATION.whole_seconds(),
+        }
+    }
+}
+
+/// Adds the Set-Cookie headers removing both session cookies to a response.
+pub(crate) fn clear_cookies(resp: &mut Response) {
+    resp.headers_mut()
+        .append(SET_COOKIE, build_access_removal_cookie());
+    resp.headers_mut()
+        .append(SET_COOKIE, build_refresh_removal_cookie());
+}
+
+/// Starts a new session for the user and issues its first tokens.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `user_id` - The user the session is issued to
+/// * `meta` - The client the session is issued to
+///
+/// # Returns
+///
+/// - `Ok(SessionTokens)` - The access and refresh tokens on success
+/// - `Err(Response)` - An error response if the session could not be stored or the token signed
+#[allow(clippy::result_large_err)]
+pub(crate) async fn issue_session(
+    pool: &PgPool,
+    user_id: i64,
+    meta: SessionMeta,
+) -> Result<SessionTokens, Response> {
+    let expires_at = time::OffsetDateTime::now_utc() + SESSION_DURATION;
+    let refresh_token = generate_token();
+
+    let session_id = sqlx::query_scalar!(
+        r#"
+        WITH session AS (
+            INSERT INTO sessions (user_id, user_agent, ip_address, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+        ), token AS (
+            INSERT INTO refresh_tokens (session_id, token_hash)
+            SELECT id, $5 FROM session
+        )
+        SELECT id AS "id!" FROM session
+        "#,
+        user_id,
+        meta.user_agent,
+        meta.ip_address,
+        expires_at,
+        hash_token(&refresh_token)
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        tracing::error!(error = ?e, "Failed to create session.");
+        error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "An error occurred on our end while creating your session",
+        )
+    })?;
+
+    SessionTokens::new(user_id, session_id, refresh_token)
+}
+
+/// A session whose refresh token was just rotated.
+pub(crate) struct RotatedSession {
+    /// The user the session belongs to.
+    pub user_id: i64,
+    /// The refreshed session.
+    pub session_id: Uuid,
+    /// The refresh token replacing the presented one.
+    pub refresh_token: String,
+}
+
+/// Errors returned when refreshing a session.
+#[derive(Debug)]
+pub(crate) enum RefreshError {
+    /// The refresh token is unknown, or its session was revoked or expired.
+    Invalid,
+    /// The refresh token was already exchanged; its session has been revoked.
+    Reused,
+    /// A database operation failed.
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for RefreshError {
+    fn from(e: sqlx::Error) -> Self {
+        RefreshError::Database(e)
+    }
+}
+
+/// Exchanges a refresh token for new tokens, extending the session.
+///
+/// The presented token is marked as rotated. Presenting a rotated token again
+/// revokes the whole session.
+///
+/// # Arguments
+///
+/// * `pool` - The PostgreSQL connection pool
+/// * `refresh_token` - The refresh token presented by the client
+///
+/// # Returns
+///
+/// - `Ok(RotatedSession)` with the new refresh token on success
+/// - `Err(RefreshError)` if the token cannot be exchanged
+pub(crate) async fn refresh_session(
+    pool: &PgPool,
+    refresh_token: &str,
+) -> Result<RotatedSession, RefreshError> {
+    let mut tx = pool.begin().await?;
+
+    // Lock the token so that concurrent refreshes with it are serialized
+    let Some(current) = sqlx::query!(
+        r#"
+        SELECT rt.id, rt.session_id, rt.rotated_at, s.user_id,
+               (s.revoked_at IS NULL AND s.expires_at > NOW()) AS "active!"
+        FROM refresh_tokens rt
+        JOIN sessions s ON s.id = rt.session_id
+        WHERE rt.token_hash = $1
+        FOR UPDATE OF rt
+        "#,
+        hash_token(refresh_token)
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Err(RefreshError::Invalid);
+    };
+
+    if current.rotated_at.is_some() {
+        sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+            current.session_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::warn!(
+            user_id = current.user_id,
+            session_id = %current.session_id,
+            "Refresh token reuse detected, session revoked"
+        );
+        return Err(RefreshError::Reused);
+    }
+
+    if !current.active {
+        return Err(RefreshError::Invalid);
+    }
+
+    let next_token = generate_token();
+    let expires_at = time::OffsetDateTime::now_utc() + SESSION_DURATION;
+
+    sqlx::query!(
+        "UPDATE refresh_tokens SET rotated_at = NOW() WHERE id = $1",
+        current.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO refresh_tokens (session_id, token_hash) VALUES ($1, $2)",
+        current.session_id,
+        hash_token(&next_token)
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE sessions SET expires_at = $2, last_used_at = NOW() WHERE id = $1",
+        current.session_id,
+        expires_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(RotatedSession {
+        user_id: current.user_id,
+        session_id: current.session_id,
+        refresh_token: next_token,
+    })
+}
+
+/// Revokes every active session of the user.
+///
+/// Takes a connection so that it can run inside the caller's transaction.
+///
+/// # Returns
+///
+/// - `Ok(u64)` with the number of revoked sessions
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn revoke_all_sessions(
+    conn: &mut PgConnection,
+    user_id: i64,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = NOW()
+        WHERE user_id = $1
+          AND revoked_at IS NULL
+          AND expires_at > NOW()
+        "#,
+        user_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Revokes every active session of the user except the given one.
+///
+/// # Returns
+///
+/// - `Ok(u64)` with the number of revoked sessions
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn revoke_other_sessions(
+    pool: &PgPool,
+    user_id: i64,
+    keep: Uuid,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = NOW()
+        WHERE user_id = $1
+          AND id <> $2
+          AND revoked_at IS NULL
+          AND expires_at > NOW()
+        "#,
+        user_id,
+        keep
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Deletes the rotated refresh tokens of expired and revoked sessions.
+///
+/// Rotated tokens are only kept to detect their reuse, which can't revive a
+/// session that already ended: presenting one afterwards fails all the same.
+///
+/// # Returns
+///
+/// - `Ok(u64)` with the number of deleted tokens
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn purge_rotated_tokens(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM refresh_tokens rt
+        USING sessions s
+        WHERE s.id = rt.session_id
+          AND rt.rotated_at IS NOT NULL
+          AND (s.revoked_at IS NOT NULL OR s.expires_at <= NOW())
+        "#
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Deletes the sessions revoked or expired more than [`SESSION_RETENTION`]
+/// ago, along with their remaining refresh tokens.
+///
+/// # Returns
+///
+/// - `Ok(u64)` with the number of deleted sessions
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn purge_ended_sessions(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM sessions
+        WHERE LEAST(revoked_at, expires_at) < $1
+        "#,
+        time::OffsetDateTime::now_utc() - SESSION_RETENTION
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Starts the background task purging, every [`SWEEP_INTERVAL`], the rotated
+/// refresh tokens of ended sessions, as every refresh leaves one behind, and
+/// the sessions that ended longer than [`SESSION_RETENTION`] ago.
+pub(crate) fn spawn_sweeper(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_rotated_tokens(&pool).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "Purged rotated refresh tokens"),
+                Err(e) => tracing::error!(error = ?e, "Failed to purge rotated refresh tokens"),
+            }
+            match purge_ended_sessions(&pool).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "Purged ended sessions"),
+                Err(e) => tracing::error!(error = ?e, "Failed to purge ended sessions"),
+            }
+        }
+    });
+}
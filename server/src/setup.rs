@@ -7,6 +7,19 @@ use sqlx::postgres::PgPoolOptions;
 use sqlx::{Pool, Postgres};
 use std::env;
 
+/// Public URL used when `PUBLIC_URL` is not set.
+const DEFAULT_PUBLIC_URL: &str = "http://localhost:2607";
+
+/// Returns the URL the application is reached at, used to build links sent to users.
+///
+/// Reads the `PUBLIC_URL` environment variable, without trailing slash.
+pub(crate) fn public_url() -> String {
+    env::var("PUBLIC_URL")
+        .unwrap_or_else(|_| DEFAULT_PUBLIC_URL.into())
+        .trim_end_matches('/')
+        .to_string()
+}
+
 /// Sets up the PostgreSQL database connection pool.
 ///
 /// Reads the `DATABASE_URL` environment variable and creates a connection pool
@@ -33,15 +46,13 @@ pub(crate) async fn setup_db() -> Pool<Postgres> {
         .connect(&db_url)
         .await;
 
-    let pool = match pool {
+    match pool {
         Ok(p) => p,
         Err(e) => {
             tracing::error!(error = ?e, "Failed to connect to the database. Exiting.");
             std::process::exit(1);
         }
-    };
-
-    pool
+    }
 }
 
 use tracing_subscriber::{filter::Targets, fmt, prelude::*};
@@ -0,0 +1,74 @@
+//! Shared application state.
+//!
+//! Handlers extract the individual parts they need (e.g. `State<PgPool>`)
+//! through the [`FromRef`] implementations below.
+
+use crate::chat_codes::ChatCodePolicies;
+use crate::mailer::SharedMailer;
+use crate::notifications::NotificationHub;
+use crate::oidc::OidcProviders;
+use crate::presence::Presence;
+use crate::verification::UnverifiedLimits;
+use axum::extract::FromRef;
+use sqlx::PgPool;
+
+/// State shared by every route.
+#[derive(Clone)]
+pub(crate) struct AppState {
+    /// The PostgreSQL connection pool.
+    pub pool: PgPool,
+    /// The process-wide notification hub used by WebSockets.
+    pub hub: NotificationHub,
+    /// Connected socket counts used for online presence.
+    pub presence: Presence,
+    /// Delivers outgoing emails.
+    pub mailer: SharedMailer,
+    /// What accounts with an unverified email address can't do.
+    pub unverified_limits: UnverifiedLimits,
+    /// The configured OpenID Connect providers.
+    pub oidc: OidcProviders,
+    /// The chat code limits of each user tier.
+    pub chat_code_policies: ChatCodePolicies,
+}
+
+impl FromRef<AppState> for PgPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for NotificationHub {
+    fn from_ref(state: &AppState) -> Self {
+        state.hub.clone()
+    }
+}
+
+impl FromRef<AppState> for Presence {
+    fn from_ref(state: &AppState) -> Self {
+        state.presence.clone()
+    }
+}
+
+impl FromRef<AppState> for SharedMailer {
+    fn from_ref(state: &AppState) -> Self {
+        state.mailer.clone()
+    }
+}
+
+impl FromRef<AppState> for UnverifiedLimits {
+    fn from_ref(state: &AppState) -> Self {
+        state.unverified_limits.clone()
+    }
+}
+
+impl FromRef<AppState> for OidcProviders {
+    fn from_ref(state: &AppState) -> Self {
+        state.oidc.clone()
+    }
+}
+
+impl FromRef<AppState> for ChatCodePolicies {
+    fn from_ref(state: &AppState) -> Self {
+        state.chat_code_policies.clone()
+    }
+}
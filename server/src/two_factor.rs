@@ -0,0 +1,298 @@
+//! Two-factor authentication with TOTP codes and recovery codes.
+//!
+//! Users enroll by scanning a secret into an authenticator app and confirming
+//! a first code. From then on, logging in with a password only yields an "MFA
+//! pending" challenge token, which is exchanged for a session together with a
+//! TOTP code or one of the recovery codes handed out at enrollment.
+//!
+//! TOTP secrets are stored encrypted, see [`utils::encryption`], and only
+//! decrypted to check a code.
+
+use sqlx::PgPool;
+use std::env;
+use time::OffsetDateTime;
+use utils::encryption::{decrypt_totp_secret, encrypt_totp_secret, is_current};
+use utils::tokens::{generate_token, hash_token};
+use utils::totp;
+use uuid::Uuid;
+
+/// How long a login waits for its second factor.
+pub(crate) const CHALLENGE_DURATION: time::Duration = time::Duration::minutes(5);
+
+/// Number of wrong codes after which a challenge is dropped and the user has to log in again.
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+/// Issuer shown in authenticator apps when `TOTP_ISSUER` is not set.
+const DEFAULT_ISSUER: &str = "GDG Chat";
+
+/// Returns the issuer name shown in authenticator apps.
+pub(crate) fn issuer() -> String {
+    env::var("TOTP_ISSUER").unwrap_or_else(|_| DEFAULT_ISSUER.to_string())
+}
+
+/// Verifies a TOTP code, refusing codes of a time step that was already used.
+///
+/// # Arguments
+///
+/// * `secret` - The user's TOTP secret as stored, decrypted here
+///
+/// # Returns
+///
+/// - `Ok(true)` if the code is valid and was not used before
+/// - `Ok(false)` otherwise
+/// - `Err(sqlx::Error)` if the database operation fails, or the secret can't
+///   be decrypted
+pub(crate) async fn verify_totp(
+    pool: &PgPool,
+    user_id: i64,
+    secret: &str,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    let secret =
+        decrypt_totp_secret(secret, user_id).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+    let now = OffsetDateTime::now_utc().unix_timestamp().max(0) as u64;
+    let Some(step) = totp::verify(&secret, code, now) else {
+        return Ok(false);
+    };
+
+    // Record the step, unless it (or a later one) was already used
+    let result = sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_last_step = $2
+        WHERE id = $1
+          AND (totp_last_step IS NULL OR totp_last_step < $2)
+        "#,
+        user_id,
+        step as i64
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        tracing::warn!(user_id, "Rejected reused TOTP code");
+    }
+    Ok(result.rows_affected() == 1)
+}
+
+/// Consumes one of the user's unused recovery codes.
+///
+/// # Returns
+///
+/// - `Ok(true)` if the code was valid and is now used up
+/// - `Ok(false)` otherwise
+/// - `Err(sqlx::Error)` if the database operation fails
+async fn use_recovery_code(pool: &PgPool, user_id: i64, code: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE recovery_codes
+        SET used_at = NOW()
+        WHERE user_id = $1
+          AND code_hash = $2
+          AND used_at IS NULL
+        "#,
+        user_id,
+        hash_token(totp::normalize_recovery_code(code))
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() > 0 {
+        tracing::info!(user_id, "Recovery code used");
+    }
+    Ok(result.rows_affected() > 0)
+}
+
+/// Verifies a second factor: a TOTP code, or else a recovery code.
+///
+/// # Returns
+///
+/// - `Ok(true)` if the code is a valid TOTP code or an unused recovery code
+/// - `Ok(false)` otherwise
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn verify_second_factor(
+    pool: &PgPool,
+    user_id: i64,
+    secret: &str,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    if verify_totp(pool, user_id, secret, code).await? {
+        return Ok(true);
+    }
+    use_recovery_code(pool, user_id, code).await
+}
+
+/// Replaces the user's recovery codes with new ones.
+///
+/// # Returns
+///
+/// - `Ok(Vec<String>)` with the new codes, to be shown to the user once
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn replace_recovery_codes(
+    tx: &mut sqlx::PgConnection,
+    user_id: i64,
+) -> Result<Vec<String>, sqlx::Error> {
+    let codes = totp::generate_recovery_codes();
+    let hashes: Vec<String> = codes
+        .iter()
+        .map(|code| hash_token(totp::normalize_recovery_code(code)))
+        .collect();
+
+    sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO recovery_codes (user_id, code_hash)
+        SELECT $1, code_hash FROM UNNEST($2::TEXT[]) AS code_hash
+        "#,
+        user_id,
+        &hashes
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(codes)
+}
+
+/// A login waiting for its second factor.
+pub(crate) struct Challenge {
+    /// ID of the challenge.
+    pub id: Uuid,
+    /// The user who passed the password check.
+    pub user_id: i64,
+    /// The user's TOTP secret, encrypted as stored.
+    pub totp_secret: String,
+}
+
+/// Starts a challenge for a user who passed the password check.
+///
+/// # Returns
+///
+/// - `Ok(String)` with the challenge token handed to the client
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn create_challenge(pool: &PgPool, user_id: i64) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let expires_at = OffsetDateTime::now_utc() + CHALLENGE_DURATION;
+
+    sqlx::query!(
+        r#"
+        WITH expired AS (
+            DELETE FROM mfa_challenges
+            WHERE user_id = $1 AND expires_at <= NOW()
+        )
+        INSERT INTO mfa_challenges (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        user_id,
+        hash_token(&token),
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(token)
+}
+
+/// Claims an attempt at a pending challenge, looked up by its token.
+///
+/// The attempt is counted before the code is checked, in a single statement,
+/// so that concurrent requests with the same token can't get more than
+/// [`MAX_CHALLENGE_ATTEMPTS`] guesses between them.
+///
+/// # Returns
+///
+/// - `Ok(Some(Challenge))` if the challenge exists, has not expired and had attempts left
+/// - `Ok(None)` otherwise
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn claim_challenge_attempt(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<Challenge>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE mfa_challenges c
+        SET attempts = c.attempts + 1
+        FROM users u
+        WHERE u.id = c.user_id
+          AND c.token_hash = $1
+          AND c.expires_at > NOW()
+          AND c.attempts < $2
+          AND u.totp_enabled_at IS NOT NULL
+          AND u.totp_secret IS NOT NULL
+        RETURNING c.id, c.user_id, u.totp_secret AS "totp_secret!"
+        "#,
+        hash_token(token),
+        MAX_CHALLENGE_ATTEMPTS
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| Challenge {
+        id: row.id,
+        user_id: row.user_id,
+        totp_secret: row.totp_secret,
+    }))
+}
+
+/// Consumes a challenge whose second factor was verified.
+///
+/// # Returns
+///
+/// - `Ok(true)` if the challenge was consumed
+/// - `Ok(false)` if it was already consumed by a concurrent request
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn complete_challenge(
+    pool: &PgPool,
+    challenge_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM mfa_challenges WHERE id = $1", challenge_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Encrypts the stored TOTP secrets that are in plain, or encrypted with
+/// another key than the active one, with the active key.
+///
+/// Run at startup, so that secrets stored before encryption was introduced
+/// are encrypted, and retired keys can be dropped once it has run.
+///
+/// # Returns
+///
+/// - `Ok(u64)` with the number of encrypted secrets
+/// - `Err(String)` if a secret can't be decrypted or the database operation fails
+pub(crate) async fn encrypt_stored_secrets(pool: &PgPool) -> Result<u64, String> {
+    let users = sqlx::query!(
+        r#"SELECT id, totp_secret AS "totp_secret!" FROM users WHERE totp_secret IS NOT NULL"#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut encrypted = 0;
+    for user in users {
+        if is_current(&user.totp_secret).map_err(|e| e.to_string())? {
+            continue;
+        }
+        let secret = decrypt_totp_secret(&user.totp_secret, user.id)
+            .map_err(|e| format!("TOTP secret of user {}: {e}", user.id))?;
+        let stored = encrypt_totp_secret(&secret, user.id).map_err(|e| e.to_string())?;
+
+        // Leave secrets replaced in the meantime alone
+        let result = sqlx::query!(
+            "UPDATE users SET totp_secret = $3 WHERE id = $1 AND totp_secret = $2",
+            user.id,
+            user.totp_secret,
+            stored
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        encrypted += result.rows_affected();
+    }
+
+    Ok(encrypted)
+}
@@ -0,0 +1,237 @@
+//! Email address verification.
+//!
+//! Registering and changing the email address send a link carrying a
+//! single-use token to the new address. Confirming the link marks the address
+//! as verified; for an email change, the new address only replaces the old one
+//! at that point, so a typo can't lock anyone out of their account.
+//!
+//! What unverified accounts can't do is configured with `UNVERIFIED_LIMITS`.
+
+use crate::mailer::{Email, SharedMailer, send_in_background};
+use crate::setup::public_url;
+use axum::http::StatusCode;
+use axum::response::Response;
+use sqlx::PgPool;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use time::Duration;
+use utils::errors::error_response;
+use utils::tokens::{generate_token, hash_token};
+
+/// How long a verification link stays valid.
+pub(crate) const VERIFICATION_TOKEN_DURATION: Duration = Duration::hours(24);
+
+/// Limits applied when `UNVERIFIED_LIMITS` is not set.
+const DEFAULT_LIMITS: &str = "chat-codes";
+
+/// An action unverified accounts can be kept from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Restriction {
+    /// Creating chat codes.
+    ChatCodes,
+    /// Starting conversations by redeeming chat codes.
+    Chats,
+    /// Sending messages.
+    Messages,
+}
+
+impl Restriction {
+    /// Returns the name used in `UNVERIFIED_LIMITS`.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Restriction::ChatCodes => "chat-codes",
+            Restriction::Chats => "chats",
+            Restriction::Messages => "messages",
+        }
+    }
+
+    /// Describes the action, completing "Verify your email address to ...".
+    fn action(self) -> &'static str {
+        match self {
+            Restriction::ChatCodes => "create chat codes",
+            Restriction::Chats => "start conversations",
+            Restriction::Messages => "send messages",
+        }
+    }
+
+    /// Returns the message telling the user to verify their email address first.
+    pub(crate) fn message(self) -> String {
+        format!("Verify your email address to {}.", self.action())
+    }
+}
+
+impl fmt::Display for Restriction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Restriction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chat-codes" => Ok(Restriction::ChatCodes),
+            "chats" => Ok(Restriction::Chats),
+            "messages" => Ok(Restriction::Messages),
+            other => Err(format!(
+                "Unknown limit {other}, expected chat-codes, chats or messages"
+            )),
+        }
+    }
+}
+
+/// The actions unverified accounts can't perform.
+#[derive(Debug, Clone)]
+pub(crate) struct UnverifiedLimits(Arc<Vec<Restriction>>);
+
+impl UnverifiedLimits {
+    /// Reads the limits from the `UNVERIFIED_LIMITS` environment variable, a
+    /// comma separated list of restrictions, or `none`.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(UnverifiedLimits)` with the configured limits
+    /// - `Err(String)` describing an unknown restriction
+    pub(crate) fn from_env() -> Result<Self, String> {
+        let value = env::var("UNVERIFIED_LIMITS").unwrap_or_else(|_| DEFAULT_LIMITS.into());
+        if value.trim() == "none" {
+            return Ok(Self(Arc::new(Vec::new())));
+        }
+
+        let restrictions = value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Restriction>, String>>()?;
+        Ok(Self(Arc::new(restrictions)))
+    }
+
+    /// Returns whether unverified accounts are kept from the action.
+    pub(crate) fn restricts(&self, restriction: Restriction) -> bool {
+        self.0.contains(&restriction)
+    }
+
+    /// Checks that the user may perform the action.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the action isn't restricted or the user verified their email address
+    /// - `Err(Response)` - `403 FORBIDDEN` if it is restricted and they didn't,
+    ///   or `500 INTERNAL SERVER ERROR` if the database operation fails
+    #[allow(clippy::result_large_err)]
+    pub(crate) async fn require(
+        &self,
+        pool: &PgPool,
+        user_id: i64,
+        restriction: Restriction,
+    ) -> Result<(), Response> {
+        if !self.restricts(restriction) {
+            return Ok(());
+        }
+
+        match is_verified(pool, user_id).await {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                tracing::info!(user_id, %restriction, "Unverified account hit a limit");
+                Err(error_response(StatusCode::FORBIDDEN, restriction.message()))
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to check email verification.");
+                Err(error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "An error occurred on our end while checking your account",
+                ))
+            }
+        }
+    }
+}
+
+/// Returns whether the user verified their current email address.
+///
+/// # Returns
+///
+/// - `Ok(bool)` - `false` as well if the user doesn't exist
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn is_verified(pool: &PgPool, user_id: i64) -> Result<bool, sqlx::Error> {
+    let verified = sqlx::query_scalar!(
+        r#"SELECT email_verified_at IS NOT NULL AS "verified!" FROM users WHERE id = $1"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(verified.unwrap_or(false))
+}
+
+/// Creates a verification token for the email address, replacing the user's
+/// outstanding ones so that only the latest link works.
+///
+/// # Returns
+///
+/// - `Ok(String)` with the token to send
+/// - `Err(sqlx::Error)` if the database operation fails
+async fn create_verification(
+    pool: &PgPool,
+    user_id: i64,
+    email: &str,
+) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let expires_at = time::OffsetDateTime::now_utc() + VERIFICATION_TOKEN_DURATION;
+
+    sqlx::query!(
+        r#"
+        WITH removed AS (
+            DELETE FROM email_verifications WHERE user_id = $1
+        )
+        INSERT INTO email_verifications (user_id, email, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        user_id,
+        email,
+        hash_token(&token),
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(token)
+}
+
+/// Emails the verification link for the token in the background.
+fn send_verification_email(mailer: &SharedMailer, email: &str, token: &str) {
+    let link = format!("{}/verify-email?token={}", public_url(), token);
+    send_in_background(
+        mailer,
+        Email {
+            to: email.to_string(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Open this link within {} hours to verify your email address:\n{}\n\n\
+                 If you didn't use this address for an account, you can ignore this email.\n",
+                VERIFICATION_TOKEN_DURATION.whole_hours(),
+                link
+            ),
+        },
+    );
+}
+
+/// Creates a verification token for the email address and emails its link.
+///
+/// # Returns
+///
+/// - `Ok(())` once the token is stored; the email is sent in the background
+/// - `Err(sqlx::Error)` if the database operation fails
+pub(crate) async fn start_verification(
+    pool: &PgPool,
+    mailer: &SharedMailer,
+    user_id: i64,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    let token = create_verification(pool, user_id, email).await?;
+    send_verification_email(mailer, email, &token);
+    Ok(())
+}
@@ -1,16 +1,43 @@
-use axum::http::{HeaderValue, StatusCode, header::InvalidHeaderValue};
+use axum::http::{HeaderValue, header::InvalidHeaderValue};
 use cookie::Cookie;
 
-use crate::errors;
+use crate::jwt::{ACCESS_TOKEN_DURATION, SESSION_DURATION};
 
-/// Builds an HTTP cookie for session management.
+/// Name of the cookie carrying the JWT access token.
+pub const ACCESS_COOKIE: &str = "session_token";
+
+/// Name of the cookie carrying the opaque refresh token.
+pub const REFRESH_COOKIE: &str = "refresh_token";
+
+/// Path the refresh cookie is scoped to, so it is only sent to the auth endpoints.
+pub const REFRESH_COOKIE_PATH: &str = "/api/auth";
+
+/// Name of the cookie binding an OpenID Connect login to the browser that started it.
+pub const OIDC_STATE_COOKIE: &str = "oidc_state";
+
+/// Path the OpenID Connect state cookie is scoped to.
+pub const OIDC_STATE_COOKIE_PATH: &str = "/api/auth/oidc";
+
+/// How long an OpenID Connect login may take.
+pub const OIDC_STATE_DURATION: time::Duration = time::Duration::minutes(10);
+
+/// Name of the cookie binding a magic login link to the browser that requested it.
+pub const MAGIC_LINK_COOKIE: &str = "magic_link_nonce";
+
+/// Path the magic link cookie is scoped to.
+pub const MAGIC_LINK_COOKIE_PATH: &str = "/api/auth/magic-link";
+
+/// How long a magic login link stays valid.
+pub const MAGIC_LINK_DURATION: time::Duration = time::Duration::minutes(15);
+
+/// Builds an HTTP cookie carrying the access token.
 ///
 /// Creates a secure HTTP-only cookie named `session_token` with the following properties:
 /// - Path: `/`
 /// - HTTP-only: true (not accessible via JavaScript)
 /// - Secure: false (set to true in production with HTTPS)
 /// - SameSite: Lax
-/// - Max-Age: 7 days
+/// - Max-Age: [`ACCESS_TOKEN_DURATION`]
 ///
 /// # Arguments
 ///
@@ -24,45 +51,153 @@ use crate::errors;
 /// # Example
 ///
 /// ```ignore
-/// let cookie = build_cookie(jwt_token)?;
-/// response.headers_mut().insert(SET_COOKIE, cookie);
+/// let cookie = build_access_cookie(jwt_token)?;
+/// response.headers_mut().append(SET_COOKIE, cookie);
 /// ```
-pub fn build_cookie<S: Into<String>>(value: S) -> Result<HeaderValue, InvalidHeaderValue> {
-    let cookie = Cookie::build(("session_token", value.into()))
-        .path("/")
-        .http_only(true)
-        .secure(false)
-        .same_site(cookie::SameSite::Lax)
-        .max_age(time::Duration::days(7))
-        .build();
-    cookie.to_string().parse()
+pub fn build_access_cookie<S: Into<String>>(value: S) -> Result<HeaderValue, InvalidHeaderValue> {
+    build(ACCESS_COOKIE, value.into(), "/", ACCESS_TOKEN_DURATION)
+}
+
+/// Builds an HTTP cookie carrying the refresh token.
+///
+/// Same properties as the access cookie, except:
+/// - Path: [`REFRESH_COOKIE_PATH`], so browsers don't send it with every request
+/// - Max-Age: [`SESSION_DURATION`]
+///
+/// # Arguments
+///
+/// * `value` - The opaque refresh token to store in the cookie
+///
+/// # Returns
+///
+/// - `Ok(HeaderValue)` containing the formatted cookie header
+/// - `Err(InvalidHeaderValue)` if the cookie string contains invalid characters
+pub fn build_refresh_cookie<S: Into<String>>(value: S) -> Result<HeaderValue, InvalidHeaderValue> {
+    build(
+        REFRESH_COOKIE,
+        value.into(),
+        REFRESH_COOKIE_PATH,
+        SESSION_DURATION,
+    )
+}
+
+/// Builds an HTTP cookie that removes the access cookie from the client.
+///
+/// # Returns
+///
+/// The Set-Cookie header value expiring the cookie immediately.
+pub fn build_access_removal_cookie() -> HeaderValue {
+    build(ACCESS_COOKIE, String::new(), "/", time::Duration::ZERO)
+        .expect("Removal cookie is a valid header value")
+}
+
+/// Builds an HTTP cookie that removes the refresh cookie from the client.
+///
+/// # Returns
+///
+/// The Set-Cookie header value expiring the cookie immediately.
+pub fn build_refresh_removal_cookie() -> HeaderValue {
+    build(
+        REFRESH_COOKIE,
+        String::new(),
+        REFRESH_COOKIE_PATH,
+        time::Duration::ZERO,
+    )
+    .expect("Removal cookie is a valid header value")
 }
 
-/// Creates an authentication cookie with the JWT token.
+/// Builds an HTTP cookie carrying the `state` of an OpenID Connect login.
+///
+/// The provider redirects back with the same `state`, which must match the
+/// cookie, so that a login can only be completed by the browser that started it.
+/// SameSite=Lax lets the cookie through on that top-level redirect.
 ///
 /// # Arguments
 ///
-/// * `user_id` - The user ID to encode in the JWT
+/// * `value` - The `state` sent to the provider
 ///
 /// # Returns
 ///
-/// - `Ok(HeaderValue)` - The Set-Cookie header value on success
-/// - `Err(Response)` - An error response if JWT generation or cookie building fails
-#[inline]
-pub fn create_auth_cookie(user_id: i64) -> Result<HeaderValue, axum::response::Response> {
-    let jwt_token = crate::jwt::sign_jwt(user_id.to_string()).map_err(|e| {
-        tracing::error!(error = ?e, "Failed to sign JWT.");
-        errors::error_response(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("An error occurred on our end: {}", e),
-        )
-    })?;
-
-    build_cookie(jwt_token).map_err(|e| {
-        tracing::error!(error = ?e, "Failed to build cookie.");
-        errors::error_response(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("An error occurred on our end: {}", e),
-        )
-    })
+/// - `Ok(HeaderValue)` containing the formatted cookie header
+/// - `Err(InvalidHeaderValue)` if the cookie string contains invalid characters
+pub fn build_oidc_state_cookie<S: Into<String>>(
+    value: S,
+) -> Result<HeaderValue, InvalidHeaderValue> {
+    build(
+        OIDC_STATE_COOKIE,
+        value.into(),
+        OIDC_STATE_COOKIE_PATH,
+        OIDC_STATE_DURATION,
+    )
+}
+
+/// Builds an HTTP cookie that removes the OpenID Connect state cookie from the client.
+///
+/// # Returns
+///
+/// The Set-Cookie header value expiring the cookie immediately.
+pub fn build_oidc_state_removal_cookie() -> HeaderValue {
+    build(
+        OIDC_STATE_COOKIE,
+        String::new(),
+        OIDC_STATE_COOKIE_PATH,
+        time::Duration::ZERO,
+    )
+    .expect("Removal cookie is a valid header value")
+}
+
+/// Builds an HTTP cookie carrying the nonce of a magic login link.
+///
+/// The link only logs in when it is opened in a browser holding the nonce,
+/// so that a leaked link can't be used from elsewhere.
+///
+/// # Arguments
+///
+/// * `value` - The nonce the link was issued with
+///
+/// # Returns
+///
+/// - `Ok(HeaderValue)` containing the formatted cookie header
+/// - `Err(InvalidHeaderValue)` if the cookie string contains invalid characters
+pub fn build_magic_link_cookie<S: Into<String>>(
+    value: S,
+) -> Result<HeaderValue, InvalidHeaderValue> {
+    build(
+        MAGIC_LINK_COOKIE,
+        value.into(),
+        MAGIC_LINK_COOKIE_PATH,
+        MAGIC_LINK_DURATION,
+    )
+}
+
+/// Builds an HTTP cookie that removes the magic link cookie from the client.
+///
+/// # Returns
+///
+/// The Set-Cookie header value expiring the cookie immediately.
+pub fn build_magic_link_removal_cookie() -> HeaderValue {
+    build(
+        MAGIC_LINK_COOKIE,
+        String::new(),
+        MAGIC_LINK_COOKIE_PATH,
+        time::Duration::ZERO,
+    )
+    .expect("Removal cookie is a valid header value")
+}
+
+/// Builds an HTTP-only, SameSite=Lax cookie.
+fn build(
+    name: &'static str,
+    value: String,
+    path: &'static str,
+    max_age: time::Duration,
+) -> Result<HeaderValue, InvalidHeaderValue> {
+    let cookie = Cookie::build((name, value))
+        .path(path)
+        .http_only(true)
+        .secure(false)
+        .same_site(cookie::SameSite::Lax)
+        .max_age(max_age)
+        .build();
+    cookie.to_string().parse()
 }
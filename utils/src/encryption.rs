@@ -0,0 +1,380 @@
+//! Encryption of TOTP secrets at rest, using AES-256-GCM.
+//!
+//! TOTP secrets have to be read back to check codes, so unlike passwords they
+//! can't be hashed. They are encrypted with a server-side key that never
+//! reaches the database, and bound to their user so that a secret copied to
+//! another row doesn't decrypt. Like password hashes naming their pepper,
+//! encrypted secrets name their key in a `keyid` parameter, so keys can be
+//! rotated: [`is_current`] reports secrets encrypted with another key, for the
+//! caller to encrypt them again.
+//!
+//! Encrypted secrets look like `$aes-256-gcm$keyid=k1$<nonce>$<ciphertext>`,
+//! with the nonce and the ciphertext in unpadded base64. Secrets stored before
+//! encryption was introduced are plain base32, which never starts with `$`.
+
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Identifier of the algorithm, the first field of encrypted secrets.
+const ALGORITHM: &str = "aes-256-gcm";
+
+/// Number of bytes of a nonce.
+const NONCE_BYTES: usize = 12;
+
+/// Minimum number of bytes of a key's secret.
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// Error encrypting or decrypting a secret.
+#[derive(Debug)]
+pub enum EncryptionError {
+    /// The encryption configuration is invalid.
+    Config(String),
+    /// The stored value isn't an encrypted secret.
+    Malformed,
+    /// The secret names a key that isn't configured.
+    UnknownKey(String),
+    /// The secret doesn't decrypt with its key, or for its user.
+    Crypto,
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionError::Config(e) => write!(f, "Invalid TOTP encryption configuration: {e}"),
+            EncryptionError::Malformed => write!(f, "Malformed encrypted secret"),
+            EncryptionError::UnknownKey(id) => {
+                write!(
+                    f,
+                    "Secret encrypted with the key {id}, which isn't configured"
+                )
+            }
+            EncryptionError::Crypto => write!(f, "Secret could not be decrypted"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// A server-side key encrypting secrets.
+struct Key {
+    /// ID stored in the `keyid` parameter of the secrets encrypted with it.
+    id: String,
+    cipher: Aes256Gcm,
+}
+
+impl Key {
+    /// Derives the AES-256 key from a secret of at least [`MIN_SECRET_LENGTH`] bytes.
+    fn new(id: &str, secret: &[u8]) -> Self {
+        Self {
+            id: id.to_string(),
+            cipher: Aes256Gcm::new(&Sha256::digest(secret)),
+        }
+    }
+}
+
+/// The configured keys.
+struct Keyring {
+    keys: Vec<Key>,
+    /// Index of the key of new secrets.
+    active: usize,
+}
+
+impl Keyring {
+    /// Reads the keys from the environment.
+    ///
+    /// - `TOTP_ENCRYPTION_KEYS` lists the key IDs, each with its secret in
+    ///   `TOTP_ENCRYPTION_KEY_<ID>`
+    /// - `TOTP_ACTIVE_ENCRYPTION_KEY` names the key of new secrets, the first
+    ///   listed by default
+    fn from_env() -> Result<Self, String> {
+        let keys = env::var("TOTP_ENCRYPTION_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                if id.contains(['$', ',', '=']) {
+                    return Err(format!("The key ID {id} can't contain '$', ',' or '='"));
+                }
+                let name = format!(
+                    "TOTP_ENCRYPTION_KEY_{}",
+                    id.to_uppercase().replace('-', "_")
+                );
+                match env::var(&name) {
+                    Ok(secret) if secret.len() >= MIN_SECRET_LENGTH => {
+                        Ok(Key::new(id, secret.as_bytes()))
+                    }
+                    Ok(_) => Err(format!(
+                        "{name} must be at least {MIN_SECRET_LENGTH} bytes long"
+                    )),
+                    Err(_) => Err(format!("{name} must be set")),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if keys.is_empty() {
+            return Err("TOTP_ENCRYPTION_KEYS must list at least one key".to_string());
+        }
+
+        let active = match env::var("TOTP_ACTIVE_ENCRYPTION_KEY") {
+            Ok(id) => keys.iter().position(|key| key.id == id).ok_or_else(|| {
+                format!("TOTP_ACTIVE_ENCRYPTION_KEY {id} isn't listed in TOTP_ENCRYPTION_KEYS")
+            })?,
+            Err(_) => 0,
+        };
+
+        Ok(Self { keys, active })
+    }
+
+    /// Encrypts a secret with the active key, for the given user.
+    fn encrypt(&self, secret: &str, user_id: i64) -> Result<String, EncryptionError> {
+        let key = &self.keys[self.active];
+        let mut nonce = [0u8; NONCE_BYTES];
+        OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = key
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: secret.as_bytes(),
+                    aad: &user_id.to_be_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::Crypto)?;
+
+        Ok(format!(
+            "${ALGORITHM}$keyid={}${}${}",
+            key.id,
+            STANDARD_NO_PAD.encode(nonce),
+            STANDARD_NO_PAD.encode(ciphertext)
+        ))
+    }
+
+    /// Decrypts a stored secret of the given user, with the key it names.
+    fn decrypt(&self, stored: &str, user_id: i64) -> Result<String, EncryptionError> {
+        let Some(encrypted) = parse(stored)? else {
+            return Ok(stored.to_string());
+        };
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.id == encrypted.keyid)
+            .ok_or_else(|| EncryptionError::UnknownKey(encrypted.keyid.to_string()))?;
+
+        let plaintext = key
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&encrypted.nonce),
+                Payload {
+                    msg: &encrypted.ciphertext,
+                    aad: &user_id.to_be_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::Crypto)?;
+        String::from_utf8(plaintext).map_err(|_| EncryptionError::Crypto)
+    }
+
+    /// Returns whether a stored secret is encrypted with the active key.
+    fn is_current(&self, stored: &str) -> bool {
+        matches!(parse(stored), Ok(Some(encrypted)) if encrypted.keyid == self.keys[self.active].id)
+    }
+}
+
+/// The fields of an encrypted secret.
+struct Encrypted<'a> {
+    keyid: &'a str,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// Splits a stored secret into its fields.
+///
+/// # Returns
+///
+/// - `Ok(Some(Encrypted))` with the fields of an encrypted secret
+/// - `Ok(None)` if the secret was stored before encryption was introduced
+/// - `Err(EncryptionError::Malformed)` if it can't be parsed
+fn parse(stored: &str) -> Result<Option<Encrypted<'_>>, EncryptionError> {
+    let Some(fields) = stored.strip_prefix('$') else {
+        return Ok(None);
+    };
+    let [algorithm, keyid, nonce, ciphertext] = fields
+        .split('$')
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| EncryptionError::Malformed)?;
+    if algorithm != ALGORITHM {
+        return Err(EncryptionError::Malformed);
+    }
+    let keyid = keyid
+        .strip_prefix("keyid=")
+        .ok_or(EncryptionError::Malformed)?;
+    let nonce = STANDARD_NO_PAD
+        .decode(nonce)
+        .ok()
+        .filter(|nonce| nonce.len() == NONCE_BYTES)
+        .ok_or(EncryptionError::Malformed)?;
+    let ciphertext = STANDARD_NO_PAD
+        .decode(ciphertext)
+        .map_err(|_| EncryptionError::Malformed)?;
+
+    Ok(Some(Encrypted {
+        keyid,
+        nonce,
+        ciphertext,
+    }))
+}
+
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+/// Loads the keys from the `TOTP_ENCRYPTION_KEY*` and
+/// `TOTP_ACTIVE_ENCRYPTION_KEY` variables.
+///
+/// Called at startup so that a missing or broken configuration is reported
+/// right away; otherwise it is loaded on first use.
+///
+/// # Returns
+///
+/// - `Ok(())` once the keys are loaded
+/// - `Err(String)` describing the invalid configuration
+pub fn init_totp_encryption() -> Result<(), String> {
+    keyring_or_load().map(|_| ())
+}
+
+/// Returns the keyring, loading it on first use.
+fn keyring_or_load() -> Result<&'static Keyring, String> {
+    if let Some(keyring) = KEYRING.get() {
+        return Ok(keyring);
+    }
+    let keyring = Keyring::from_env()?;
+    Ok(KEYRING.get_or_init(|| keyring))
+}
+
+/// Returns the keyring.
+fn keyring() -> Result<&'static Keyring, EncryptionError> {
+    keyring_or_load().map_err(EncryptionError::Config)
+}
+
+/// Encrypts a user's TOTP secret with the active key, to be stored.
+///
+/// # Arguments
+///
+/// * `secret` - The base32 encoded secret
+/// * `user_id` - ID of the user the secret belongs to
+///
+/// # Returns
+///
+/// - `Ok(String)` containing the encrypted secret
+/// - `Err(EncryptionError)` if no key is configured or encryption fails
+pub fn encrypt_totp_secret(secret: &str, user_id: i64) -> Result<String, EncryptionError> {
+    keyring()?.encrypt(secret, user_id)
+}
+
+/// Decrypts a user's stored TOTP secret, with the key it names.
+///
+/// Secrets stored before encryption was introduced are returned as they are.
+///
+/// # Arguments
+///
+/// * `stored` - The secret as stored in the database
+/// * `user_id` - ID of the user the secret belongs to
+///
+/// # Returns
+///
+/// - `Ok(String)` containing the base32 encoded secret
+/// - `Err(EncryptionError)` if the secret is malformed, names a key that
+///   isn't configured, or doesn't decrypt for the user
+pub fn decrypt_totp_secret(stored: &str, user_id: i64) -> Result<String, EncryptionError> {
+    keyring()?.decrypt(stored, user_id)
+}
+
+/// Returns whether a stored TOTP secret is encrypted with the active key.
+///
+/// Secrets stored in plain or with another key should be encrypted again
+/// with [`encrypt_totp_secret`].
+pub fn is_current(stored: &str) -> Result<bool, EncryptionError> {
+    Ok(keyring()?.is_current(stored))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+
+    fn keyring(ids: &[&str], active: usize) -> Keyring {
+        Keyring {
+            keys: ids
+                .iter()
+                .map(|id| Key::new(id, format!("{id}-secret-of-at-least-32-bytes").as_bytes()))
+                .collect(),
+            active,
+        }
+    }
+
+    #[test]
+    fn decrypts_for_the_same_user() {
+        let keyring = keyring(&["k1"], 0);
+        let stored = keyring.encrypt(SECRET, 7).unwrap();
+
+        assert!(stored.starts_with("$aes-256-gcm$keyid=k1$"));
+        assert!(!stored.contains(SECRET));
+        assert_eq!(keyring.decrypt(&stored, 7).unwrap(), SECRET);
+        assert!(matches!(
+            keyring.decrypt(&stored, 8),
+            Err(EncryptionError::Crypto)
+        ));
+    }
+
+    #[test]
+    fn decrypts_with_retired_keys() {
+        let old = keyring(&["k1"], 0);
+        let stored = old.encrypt(SECRET, 7).unwrap();
+
+        let rotated = keyring(&["k2", "k1"], 0);
+        assert!(!rotated.is_current(&stored));
+        assert_eq!(rotated.decrypt(&stored, 7).unwrap(), SECRET);
+        assert!(rotated.is_current(&rotated.encrypt(SECRET, 7).unwrap()));
+
+        let dropped = keyring(&["k2"], 0);
+        assert!(matches!(
+            dropped.decrypt(&stored, 7),
+            Err(EncryptionError::UnknownKey(id)) if id == "k1"
+        ));
+    }
+
+    #[test]
+    fn reads_secrets_stored_in_plain() {
+        let keyring = keyring(&["k1"], 0);
+
+        assert_eq!(keyring.decrypt(SECRET, 7).unwrap(), SECRET);
+        assert!(!keyring.is_current(SECRET));
+    }
+
+    #[test]
+    fn rejects_tampered_secrets() {
+        let keyring = keyring(&["k1"], 0);
+        let stored = keyring.encrypt(SECRET, 7).unwrap();
+        let (prefix, ciphertext) = stored.rsplit_once('$').unwrap();
+        let mut bytes = STANDARD_NO_PAD.decode(ciphertext).unwrap();
+        bytes[0] ^= 1;
+        let tampered = format!("{prefix}${}", STANDARD_NO_PAD.encode(bytes));
+
+        assert!(matches!(
+            keyring.decrypt(&tampered, 7),
+            Err(EncryptionError::Crypto)
+        ));
+        assert!(matches!(
+            keyring.decrypt("$aes-256-gcm$keyid=k1$nonce", 7),
+            Err(EncryptionError::Malformed)
+        ));
+    }
+}
@@ -2,14 +2,232 @@
 //!
 //! This module provides secure password hashing and verification functionality
 //! using the Argon2 algorithm with random salts.
+//!
+//! The Argon2 cost parameters are configurable, and passwords can be combined
+//! with a server-side secret (a pepper) that never reaches the database. Hashes
+//! name their pepper in the PHC `keyid` parameter, so peppers can be rotated:
+//! [`verify_password`] reports hashes made with other parameters or another
+//! pepper, for the caller to rehash the password while it has it at hand.
 
-use argon2::Argon2;
+use argon2::{Algorithm, Argon2, KeyId, Params, ParamsBuilder, Version};
 use password_hash::rand_core::OsRng;
 use password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use std::env;
+use std::sync::OnceLock;
 use tracing::trace;
 
+/// Argon2 variant of new hashes.
+const ALGORITHM: Algorithm = Algorithm::Argon2id;
+
+/// Argon2 version of new hashes.
+const VERSION: Version = Version::V0x13;
+
+/// A server-side secret mixed into password hashes.
+struct Pepper {
+    /// ID stored in the `keyid` parameter of the hashes made with it.
+    id: String,
+    secret: Vec<u8>,
+}
+
+/// Password hashing configuration.
+struct Config {
+    /// Cost parameters of new hashes, with the active pepper's key ID.
+    params: Params,
+    peppers: Vec<Pepper>,
+    /// Index of the pepper of new hashes, if any.
+    active: Option<usize>,
+}
+
+impl Config {
+    /// Reads the configuration from the environment.
+    ///
+    /// - `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and `ARGON2_PARALLELISM` set
+    ///   the cost of new hashes, OWASP's recommended minimum by default
+    /// - `PASSWORD_PEPPERS` lists the pepper IDs (at most 8 bytes each), each
+    ///   with its secret in `PASSWORD_PEPPER_<ID>`
+    /// - `PASSWORD_ACTIVE_PEPPER` names the pepper of new hashes, the first
+    ///   listed by default
+    fn from_env() -> Result<Self, String> {
+        let cost = |name: &str, default: u32| -> Result<u32, String> {
+            match env::var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("{name} must be a positive integer, got {value:?}")),
+                Err(_) => Ok(default),
+            }
+        };
+        let m_cost = cost("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST)?;
+        let t_cost = cost("ARGON2_ITERATIONS", Params::DEFAULT_T_COST)?;
+        let p_cost = cost("ARGON2_PARALLELISM", Params::DEFAULT_P_COST)?;
+
+        let peppers = env::var("PASSWORD_PEPPERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                if id.len() > Params::MAX_KEYID_LEN {
+                    return Err(format!(
+                        "The pepper ID {id} is longer than {} bytes",
+                        Params::MAX_KEYID_LEN
+                    ));
+                }
+                let name = format!("PASSWORD_PEPPER_{}", id.to_uppercase().replace('-', "_"));
+                match env::var(&name) {
+                    Ok(secret) if !secret.is_empty() => Ok(Pepper {
+                        id: id.to_string(),
+                        secret: secret.into_bytes(),
+                    }),
+                    _ => Err(format!("{name} must be set")),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let active = match env::var("PASSWORD_ACTIVE_PEPPER") {
+            Ok(id) => Some(
+                peppers
+                    .iter()
+                    .position(|pepper| pepper.id == id)
+                    .ok_or_else(|| {
+                        format!("PASSWORD_ACTIVE_PEPPER {id} isn't listed in PASSWORD_PEPPERS")
+                    })?,
+            ),
+            Err(_) if peppers.is_empty() => None,
+            Err(_) => Some(0),
+        };
+
+        let mut builder = ParamsBuilder::new();
+        builder.m_cost(m_cost).t_cost(t_cost).p_cost(p_cost);
+        if let Some(active) = active {
+            let keyid = KeyId::new(peppers[active].id.as_bytes())
+                .map_err(|e| format!("Invalid pepper ID {}: {e}", peppers[active].id))?;
+            builder.keyid(keyid);
+        }
+        let params = builder
+            .build()
+            .map_err(|e| format!("Invalid Argon2 parameters: {e}"))?;
+
+        Ok(Self {
+            params,
+            peppers,
+            active,
+        })
+    }
+
+    /// Returns the pepper a hash was made with, by its key ID.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(None)` if the hash has no key ID, so no pepper
+    /// - `Ok(Some(&Pepper))` containing the pepper named by the key ID
+    /// - `Err(password_hash::Error)` if no configured pepper has the key ID
+    fn pepper(&self, params: &Params) -> Result<Option<&Pepper>, password_hash::Error> {
+        let keyid = params.keyid();
+        if keyid.is_empty() {
+            return Ok(None);
+        }
+        let pepper = self
+            .peppers
+            .iter()
+            .find(|pepper| pepper.id.as_bytes() == keyid);
+        match pepper {
+            Some(pepper) => Ok(Some(pepper)),
+            None => {
+                tracing::error!(
+                    keyid = %String::from_utf8_lossy(keyid),
+                    "Password hash made with a pepper that isn't configured"
+                );
+                Err(password_hash::Error::Crypto)
+            }
+        }
+    }
+
+    /// Returns whether a hash was made with the current algorithm, cost and pepper.
+    fn is_current(&self, hash: &PasswordHash, params: &Params) -> bool {
+        hash.algorithm == ALGORITHM.ident()
+            && hash.version == Some(VERSION.into())
+            && params.m_cost() == self.params.m_cost()
+            && params.t_cost() == self.params.t_cost()
+            && params.p_cost() == self.params.p_cost()
+            && params.keyid() == self.params.keyid()
+    }
+
+    /// Returns the Argon2 context hashing new passwords.
+    fn hasher(&self) -> Result<Argon2<'_>, password_hash::Error> {
+        match self.active {
+            Some(active) => Ok(Argon2::new_with_secret(
+                &self.peppers[active].secret,
+                ALGORITHM,
+                VERSION,
+                self.params.clone(),
+            )?),
+            None => Ok(Argon2::new(ALGORITHM, VERSION, self.params.clone())),
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Loads the hashing configuration from the `ARGON2_*` and `PASSWORD_PEPPER*`
+/// variables.
+///
+/// Called at startup so that a broken configuration is reported right away;
+/// otherwise it is loaded on first use.
+///
+/// # Returns
+///
+/// - `Ok(())` once the configuration is loaded
+/// - `Err(String)` describing the invalid configuration
+pub fn init_password_hashing() -> Result<(), String> {
+    config_or_load().map(|_| ())
+}
+
+/// Returns the configuration, loading it on first use.
+fn config_or_load() -> Result<&'static Config, String> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let config = Config::from_env()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// Returns the configuration.
+///
+/// # Returns
+///
+/// - `Ok(&Config)` containing the configuration
+/// - `Err(password_hash::Error)` if the configuration is invalid
+fn config() -> Result<&'static Config, password_hash::Error> {
+    config_or_load().map_err(|e| {
+        tracing::error!(error = %e, "Invalid password hashing configuration");
+        password_hash::Error::Crypto
+    })
+}
+
+/// Outcome of checking a password against a stored hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// The password doesn't match the hash.
+    Mismatch,
+    /// The password matches the hash, made with the current parameters.
+    Match,
+    /// The password matches the hash, but the hash was made with other
+    /// parameters or another pepper and should be replaced.
+    MatchOutdated,
+}
+
+impl Verification {
+    /// Returns whether the password matches the hash.
+    pub fn is_match(self) -> bool {
+        self != Verification::Mismatch
+    }
+}
+
 /// Hashes a password using Argon2 with a random salt.
 ///
+/// Uses the configured cost parameters, and the active pepper if any.
+///
 /// # Arguments
 ///
 /// * `password` - The plaintext password to hash
@@ -27,7 +245,8 @@ use tracing::trace;
 pub fn hash_password<S: AsRef<str>>(password: S) -> Result<String, password_hash::Error> {
     trace!("Hashing password");
     let salt = SaltString::generate(&mut OsRng);
-    let hash = Argon2::default()
+    let hash = config()?
+        .hasher()?
         .hash_password(password.as_ref().as_bytes(), &salt)?
         .to_string();
     trace!("Password hashed successfully");
@@ -36,6 +255,9 @@ pub fn hash_password<S: AsRef<str>>(password: S) -> Result<String, password_hash
 
 /// Verifies a password against a stored hash.
 ///
+/// The hash is checked with the parameters and the pepper it names, so that
+/// hashes made before a configuration change keep working.
+///
 /// # Arguments
 ///
 /// * `password` - The plaintext password to verify
@@ -43,67 +265,42 @@ pub fn hash_password<S: AsRef<str>>(password: S) -> Result<String, password_hash
 ///
 /// # Returns
 ///
-/// - `Ok(true)` if the password matches the hash
-/// - `Ok(false)` if the password does not match
-/// - `Err(password_hash::Error)` if verification fails due to invalid hash format
+/// - `Ok(Verification::Match)` if the password matches the hash
+/// - `Ok(Verification::MatchOutdated)` if the password matches a hash that
+///   should be replaced with [`hash_password`]
+/// - `Ok(Verification::Mismatch)` if the password does not match
+/// - `Err(password_hash::Error)` if verification fails due to invalid hash
+///   format, or the hash names a pepper that isn't configured
 ///
 /// # Example
 ///
 /// ```ignore
-/// let is_valid = verify_password("my_password", &stored_hash)?;
+/// let verification = verify_password("my_password", &stored_hash)?;
 /// ```
 pub fn verify_password<S: AsRef<str>>(
     password: S,
     stored_hash: S,
-) -> Result<bool, password_hash::Error> {
+) -> Result<Verification, password_hash::Error> {
     trace!("Verifying password");
+    let config = config()?;
     let parsed_hash = PasswordHash::new(stored_hash.as_ref())?;
-    let result = Argon2::default()
+    let params = Params::try_from(&parsed_hash)?;
+
+    // The hash's own algorithm, version and parameters are used for verifying
+    let verifier = match config.pepper(&params)? {
+        Some(pepper) => {
+            Argon2::new_with_secret(&pepper.secret, ALGORITHM, VERSION, Params::default())?
+        }
+        None => Argon2::default(),
+    };
+    let matches = verifier
         .verify_password(password.as_ref().as_bytes(), &parsed_hash)
         .is_ok();
-    trace!(success = result, "Password verification completed");
-    Ok(result)
-}
-
-/// Validates that a password meets complexity requirements.
-///
-/// Password must meet the following criteria:
-/// - At least 6 characters long
-/// - Contains at least one uppercase letter
-/// - Contains at least one lowercase letter
-/// - Contains at least one digit
-///
-/// # Arguments
-///
-/// * `password` - The plaintext password to validate
-///
-/// # Returns
-///
-/// - `Ok(())` if the password meets all requirements
-/// - `Err(String)` containing a description of what requirement was not met
-///
-/// # Example
-///
-/// ```ignore
-/// is_password_suitable("MyPass123")?;
-/// // Error: Password must be at least 6 characters
-/// is_password_suitable("short")?;
-/// ```
-pub fn is_password_suitable<S: AsRef<str>>(password: S) -> Result<(), String> {
-    let password = password.as_ref();
-    if password.len() < 6 {
-        tracing::debug!("Password is too short");
-        return Err("Password must be at least 6 characters".into());
-    }
-
-    let mut chars = password.chars();
-    let has_upper = chars.any(|c| c.is_ascii_uppercase());
-    let has_lower = chars.any(|c| c.is_ascii_lowercase());
-    let has_digit = chars.any(|c| c.is_ascii_digit());
+    trace!(success = matches, "Password verification completed");
 
-    if !(has_upper && has_lower && has_digit) {
-        tracing::debug!("Password does not meet complexity requirements");
-        return Err("Password must contain at least one uppercase letter, one lowercase letter, and one digit".into());
-    }
-    Ok(())
+    Ok(match matches {
+        false => Verification::Mismatch,
+        true if config.is_current(&parsed_hash, &params) => Verification::Match,
+        true => Verification::MatchOutdated,
+    })
 }
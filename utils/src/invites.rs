@@ -0,0 +1,188 @@
+//! Invite codes and signed invite links.
+//!
+//! Invite codes are a high-entropy alternative to the 5-digit chat codes: 15
+//! random Crockford base32 symbols (75 bits) followed by a Crockford check
+//! symbol, shown in groups of four such as `7K3M-Q9TX-C2HD-W5R*`. The alphabet
+//! leaves out `I`, `L`, `O` and `U`, and parsing is forgiving: case, hyphens
+//! and spaces don't matter, and `I`/`L` and `O` are read as `1` and `0`. The
+//! check symbol catches typos before any lookup.
+//!
+//! Invite links carry a token naming a chat code and its expiry, signed with
+//! HMAC-SHA256 under `INVITE_LINK_SECRET`, so they can't be forged or altered.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use password_hash::rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+use std::env;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+
+/// Crockford base32 alphabet of the code symbols.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Crockford check symbols, the alphabet followed by the 5 extra symbols.
+const CHECK_ALPHABET: &[u8; 37] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ*~$=U";
+
+/// Number of random symbols in an invite code.
+pub const INVITE_CODE_SYMBOLS: usize = 15;
+
+/// Number of symbols between hyphens when an invite code is shown.
+const GROUP_LENGTH: usize = 4;
+
+/// Minimum number of bytes of `INVITE_LINK_SECRET`.
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// Generates a random invite code.
+///
+/// # Returns
+///
+/// The code in canonical form: the random symbols followed by the check
+/// symbol, without hyphens. See [`format_invite_code`] to show it to users.
+pub fn generate_invite_code() -> String {
+    let mut bytes = [0u8; INVITE_CODE_SYMBOLS];
+    OsRng.fill_bytes(&mut bytes);
+
+    let mut code: String = bytes
+        .iter()
+        .map(|byte| ALPHABET[(byte % 32) as usize] as char)
+        .collect();
+    code.push(check_symbol(&code));
+    code
+}
+
+/// Computes the Crockford check symbol of canonical code symbols.
+fn check_symbol(symbols: &str) -> char {
+    let value = symbols.bytes().fold(0u128, |value, symbol| {
+        let digit = ALPHABET.iter().position(|&c| c == symbol).unwrap_or(0);
+        (value << 5) | digit as u128
+    });
+    CHECK_ALPHABET[(value % 37) as usize] as char
+}
+
+/// Formats a canonical invite code in groups of four symbols, for users.
+pub fn format_invite_code(code: &str) -> String {
+    code.as_bytes()
+        .chunks(GROUP_LENGTH)
+        .map(|group| String::from_utf8_lossy(group))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Parses an invite code typed or pasted by a user.
+///
+/// # Returns
+///
+/// - `Some(String)` with the code in canonical form
+/// - `None` if it has the wrong length, invalid symbols, or a check symbol
+///   that doesn't match
+pub fn parse_invite_code(input: &str) -> Option<String> {
+    let code: String = input
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect();
+    if code.len() != INVITE_CODE_SYMBOLS + 1 || !code.is_ascii() {
+        return None;
+    }
+
+    let (symbols, check) = code.split_at(INVITE_CODE_SYMBOLS);
+    if !symbols.bytes().all(|symbol| ALPHABET.contains(&symbol)) {
+        return None;
+    }
+    if !check.starts_with(check_symbol(symbols)) {
+        return None;
+    }
+    Some(code)
+}
+
+static SECRET: OnceLock<Option<Vec<u8>>> = OnceLock::new();
+
+/// Loads the invite link secret from `INVITE_LINK_SECRET`.
+///
+/// Invite links are disabled without it. Called at startup so that a broken
+/// configuration is reported right away.
+///
+/// # Returns
+///
+/// - `Ok(())` once the secret is loaded, or if it isn't set
+/// - `Err(String)` if the secret is too short
+pub fn init_invite_links() -> Result<(), String> {
+    let secret = match env::var("INVITE_LINK_SECRET") {
+        Ok(secret) if secret.len() < MIN_SECRET_LENGTH => {
+            return Err(format!(
+                "INVITE_LINK_SECRET must be at least {MIN_SECRET_LENGTH} bytes long"
+            ));
+        }
+        Ok(secret) => Some(secret.into_bytes()),
+        Err(_) => None,
+    };
+    let _ = SECRET.set(secret);
+    Ok(())
+}
+
+/// Returns the invite link secret, if invite links are enabled.
+fn secret() -> Option<&'static [u8]> {
+    SECRET
+        .get_or_init(|| {
+            env::var("INVITE_LINK_SECRET")
+                .ok()
+                .filter(|secret| secret.len() >= MIN_SECRET_LENGTH)
+                .map(String::into_bytes)
+        })
+        .as_deref()
+}
+
+/// Returns whether invite links are enabled.
+pub fn invite_links_enabled() -> bool {
+    secret().is_some()
+}
+
+/// Computes the signature of an invite link payload.
+fn sign(secret: &[u8], payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(b"chat-invite:");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Signs an invite link token for a chat code.
+///
+/// # Arguments
+///
+/// * `code_id` - ID of the chat code the link redeems
+/// * `expires_at` - When the link stops working, the code's expiry
+///
+/// # Returns
+///
+/// - `Some(String)` containing the token, `<code id>.<expiry>.<signature>`
+/// - `None` if invite links are disabled
+pub fn sign_invite_token(code_id: i64, expires_at: OffsetDateTime) -> Option<String> {
+    let payload = format!("{code_id}.{}", expires_at.unix_timestamp());
+    let signature = sign(secret()?, &payload).finalize().into_bytes();
+    Some(format!("{payload}.{}", URL_SAFE_NO_PAD.encode(signature)))
+}
+
+/// Verifies an invite link token.
+///
+/// # Returns
+///
+/// - `Some(i64)` with the ID of the chat code the token redeems
+/// - `None` if invite links are disabled, or the token is malformed, forged or expired
+pub fn verify_invite_token(token: &str) -> Option<i64> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    sign(secret()?, payload).verify_slice(&signature).ok()?;
+
+    let (code_id, expires_at) = payload.split_once('.')?;
+    let expires_at = OffsetDateTime::from_unix_timestamp(expires_at.parse().ok()?).ok()?;
+    if expires_at <= OffsetDateTime::now_utc() {
+        return None;
+    }
+    code_id.parse().ok()
+}
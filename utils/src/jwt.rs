@@ -2,12 +2,37 @@
 //!
 //! This module provides utilities for creating and verifying JWT tokens,
 //! as well as building secure HTTP cookies for session management.
+//!
+//! Tokens are signed with a keyring of HS256, ES256 and EdDSA keys, each with
+//! a key ID (`kid`). Keys can be rotated without logging everyone out: the
+//! previous key keeps verifying its tokens until a configured cutoff. The
+//! public keys are published as a JWKS for other services to verify tokens.
 
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+    PublicKeyUse,
+};
 use jsonwebtoken::{
-    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode, get_current_timestamp,
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+    get_current_timestamp,
 };
+use p256::elliptic_curve::sec1::ToEncodedPoint;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// How long an access token, and the cookie carrying it, stays valid.
+pub const ACCESS_TOKEN_DURATION: time::Duration = time::Duration::minutes(15);
+
+/// How long a session stays valid without being refreshed. Also the lifetime
+/// of refresh tokens and of the cookie carrying them.
+pub const SESSION_DURATION: time::Duration = time::Duration::days(7);
 
 /// JWT claims structure.
 ///
@@ -20,69 +45,360 @@ pub struct Claims {
     pub iat: usize,
     /// Expiration time (Unix timestamp)
     pub exp: usize,
+    /// JWT ID: the ID of the session the token belongs to
+    pub jti: String,
+}
+
+/// Key ID given to the key configured with `JWT_SECRET_KEY`. Tokens without
+/// a `kid` header, issued before keys had IDs, are verified with this key.
+pub const LEGACY_KEY_ID: &str = "default";
+
+/// A key of the keyring.
+struct Key {
+    /// Key ID, sent in the `kid` header of the tokens it signs.
+    kid: String,
+    algorithm: Algorithm,
+    /// Signing key, absent for keys configured with only a public key.
+    encoding: Option<EncodingKey>,
+    decoding: DecodingKey,
+    /// Public key, published in the JWKS. Absent for HMAC secrets.
+    jwk: Option<Jwk>,
+    /// Tokens signed with the key are rejected from this time on.
+    verify_until: Option<OffsetDateTime>,
+}
+
+impl Key {
+    /// Reads the key's configuration from its `JWT_KEY_<KID>_*` environment variables.
+    fn from_env(kid: &str) -> Result<Self, String> {
+        let prefix = format!("JWT_KEY_{}_", kid.to_uppercase().replace('-', "_"));
+        let var = |name: &str| env::var(format!("{prefix}{name}")).ok();
+        let read_file = |name: &str| -> Result<Option<String>, String> {
+            var(name)
+                .map(|path| {
+                    std::fs::read_to_string(&path)
+                        .map_err(|e| format!("Failed to read {prefix}{name} ({path}): {e}"))
+                })
+                .transpose()
+        };
+
+        let verify_until = var("VERIFY_UNTIL")
+            .map(|value| OffsetDateTime::parse(&value, &Rfc3339))
+            .transpose()
+            .map_err(|e| format!("Invalid {prefix}VERIFY_UNTIL, expected an RFC 3339 time: {e}"))?;
+
+        let algorithm = var("ALG").unwrap_or_else(|| "HS256".to_string());
+        match algorithm.as_str() {
+            "HS256" => {
+                let secret =
+                    var("SECRET").ok_or_else(|| format!("{prefix}SECRET must be set for {kid}"))?;
+                Ok(Self::hmac(kid, &secret, verify_until))
+            }
+            "ES256" | "EdDSA" => {
+                let private_pem = read_file("PRIVATE_KEY_FILE")?;
+                let public_pem = read_file("PUBLIC_KEY_FILE")?;
+                let invalid = |e: String| format!("Invalid key for {kid}: {e}");
+
+                let (algorithm, public_key, encoding) = if algorithm == "ES256" {
+                    let public_key = match (&private_pem, &public_pem) {
+                        (Some(pem), _) => p256::SecretKey::from_pkcs8_pem(pem)
+                            .map_err(|e| invalid(e.to_string()))?
+                            .public_key(),
+                        (None, Some(pem)) => p256::PublicKey::from_public_key_pem(pem)
+                            .map_err(|e| invalid(e.to_string()))?,
+                        (None, None) => return Err(missing_key(&prefix, kid)),
+                    };
+                    let encoding = private_pem
+                        .map(|pem| EncodingKey::from_ec_pem(pem.as_bytes()))
+                        .transpose()
+                        .map_err(|e| invalid(e.to_string()))?;
+                    (Algorithm::ES256, PublicKey::P256(public_key), encoding)
+                } else {
+                    let public_key = match (&private_pem, &public_pem) {
+                        (Some(pem), _) => ed25519_dalek::SigningKey::from_pkcs8_pem(pem)
+                            .map_err(|e| invalid(e.to_string()))?
+                            .verifying_key(),
+                        (None, Some(pem)) => ed25519_dalek::VerifyingKey::from_public_key_pem(pem)
+                            .map_err(|e| invalid(e.to_string()))?,
+                        (None, None) => return Err(missing_key(&prefix, kid)),
+                    };
+                    let encoding = private_pem
+                        .map(|pem| EncodingKey::from_ed_pem(pem.as_bytes()))
+                        .transpose()
+                        .map_err(|e| invalid(e.to_string()))?;
+                    (Algorithm::EdDSA, PublicKey::Ed25519(public_key), encoding)
+                };
+
+                let jwk = public_key.to_jwk(kid, algorithm);
+                let decoding = DecodingKey::from_jwk(&jwk).map_err(|e| invalid(e.to_string()))?;
+                Ok(Self {
+                    kid: kid.to_string(),
+                    algorithm,
+                    encoding,
+                    decoding,
+                    jwk: Some(jwk),
+                    verify_until,
+                })
+            }
+            other => Err(format!(
+                "Unsupported {prefix}ALG {other:?}, use HS256, ES256 or EdDSA"
+            )),
+        }
+    }
+
+    /// Builds an HS256 key from a shared secret.
+    fn hmac(kid: &str, secret: &str, verify_until: Option<OffsetDateTime>) -> Self {
+        Self {
+            kid: kid.to_string(),
+            algorithm: Algorithm::HS256,
+            encoding: Some(EncodingKey::from_secret(secret.as_bytes())),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            jwk: None,
+            verify_until,
+        }
+    }
+
+    /// Returns whether tokens signed with the key are still accepted.
+    fn verifies(&self, now: OffsetDateTime) -> bool {
+        self.verify_until.is_none_or(|cutoff| now < cutoff)
+    }
+}
+
+/// Returns the error for an asymmetric key configured without any PEM file.
+fn missing_key(prefix: &str, kid: &str) -> String {
+    format!("{prefix}PRIVATE_KEY_FILE or {prefix}PUBLIC_KEY_FILE must be set for {kid}")
+}
+
+/// Public half of an asymmetric key.
+enum PublicKey {
+    P256(p256::PublicKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+impl PublicKey {
+    /// Describes the key as a JWK (RFC 7517).
+    fn to_jwk(&self, kid: &str, algorithm: Algorithm) -> Jwk {
+        let algorithm_parameters = match self {
+            PublicKey::P256(key) => {
+                let point = key.to_encoded_point(false);
+                AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                    key_type: EllipticCurveKeyType::EC,
+                    curve: EllipticCurve::P256,
+                    x: URL_SAFE_NO_PAD.encode(point.x().map(|x| x.as_slice()).unwrap_or_default()),
+                    y: URL_SAFE_NO_PAD.encode(point.y().map(|y| y.as_slice()).unwrap_or_default()),
+                })
+            }
+            PublicKey::Ed25519(key) => AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key.as_bytes()),
+            }),
+        };
+
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_id: Some(kid.to_string()),
+                key_algorithm: Some(match algorithm {
+                    Algorithm::ES256 => KeyAlgorithm::ES256,
+                    _ => KeyAlgorithm::EdDSA,
+                }),
+                ..Default::default()
+            },
+            algorithm: algorithm_parameters,
+        }
+    }
+}
+
+/// The keys access tokens are signed and verified with.
+///
+/// One active key signs new tokens. Every key verifies the tokens carrying
+/// its ID in the `kid` header until its `VERIFY_UNTIL` cutoff, so that keys
+/// can be rotated without logging everyone out.
+struct Keyring {
+    keys: Vec<Key>,
+    /// Index of the signing key in `keys`.
+    active: usize,
+}
+
+impl Keyring {
+    /// Loads the keyring from the environment.
+    ///
+    /// `JWT_KEYS` lists the key IDs, each configured with:
+    /// - `JWT_KEY_<KID>_ALG`: `HS256` (default), `ES256` or `EdDSA`
+    /// - `JWT_KEY_<KID>_SECRET`: the shared secret of an `HS256` key
+    /// - `JWT_KEY_<KID>_PRIVATE_KEY_FILE`: PKCS#8 PEM file of an `ES256` or `EdDSA` key
+    /// - `JWT_KEY_<KID>_PUBLIC_KEY_FILE`: PEM file of the public key of a
+    ///   retired key whose private key is gone
+    /// - `JWT_KEY_<KID>_VERIFY_UNTIL`: RFC 3339 time from which the key's
+    ///   tokens are rejected
+    ///
+    /// `JWT_ACTIVE_KEY` names the signing key, the first listed by default.
+    /// Without `JWT_KEYS`, `JWT_SECRET_KEY` is the only key, an `HS256` key
+    /// with the ID [`LEGACY_KEY_ID`].
+    fn from_env() -> Result<Self, String> {
+        let Ok(kids) = env::var("JWT_KEYS") else {
+            let secret = env::var("JWT_SECRET_KEY")
+                .map_err(|_| "JWT_KEYS or JWT_SECRET_KEY must be set".to_string())?;
+            return Ok(Self {
+                keys: vec![Key::hmac(LEGACY_KEY_ID, &secret, None)],
+                active: 0,
+            });
+        };
+
+        let keys = kids
+            .split(',')
+            .map(str::trim)
+            .filter(|kid| !kid.is_empty())
+            .map(Key::from_env)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err("JWT_KEYS doesn't list any key".to_string());
+        }
+
+        let active = match env::var("JWT_ACTIVE_KEY") {
+            Ok(kid) => keys
+                .iter()
+                .position(|key| key.kid == kid)
+                .ok_or_else(|| format!("JWT_ACTIVE_KEY {kid} isn't listed in JWT_KEYS"))?,
+            Err(_) => 0,
+        };
+        let key = &keys[active];
+        if key.encoding.is_none() {
+            return Err(format!("The active key {} has no private key", key.kid));
+        }
+        if key.verify_until.is_some() {
+            return Err(format!(
+                "The active key {} can't have a VERIFY_UNTIL cutoff",
+                key.kid
+            ));
+        }
+
+        Ok(Self { keys, active })
+    }
+
+    /// Returns the key verifying tokens with the given `kid`, if it still does.
+    fn verifying_key(&self, kid: Option<&str>) -> Option<&Key> {
+        let kid = kid.unwrap_or(LEGACY_KEY_ID);
+        let now = OffsetDateTime::now_utc();
+        self.keys
+            .iter()
+            .find(|key| key.kid == kid)
+            .filter(|key| key.verifies(now))
+    }
 }
 
-/// Retrieves the JWT secret key from environment variables.
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+/// Loads the keyring from `JWT_KEYS` and the `JWT_KEY_<KID>_*` variables, or
+/// from `JWT_SECRET_KEY`.
+///
+/// Called at startup so that a broken configuration is reported right away;
+/// otherwise the keys are loaded on first use.
 ///
 /// # Returns
 ///
-/// - `Ok(String)` containing the secret key
-/// - `Err(jsonwebtoken::errors::Error)` if the `JWT_SECRET_KEY` environment variable is not set
-fn get_secret_key() -> Result<String, jsonwebtoken::errors::Error> {
-    match env::var("JWT_SECRET_KEY") {
-        Ok(val) => Ok(val),
-        Err(e) => {
-            tracing::error!(error = ?e, "JWT_SECRET_KEY environment variable not set");
-            Err(jsonwebtoken::errors::Error::from(
-                jsonwebtoken::errors::ErrorKind::InvalidKeyFormat,
-            ))
-        }
+/// - `Ok(())` once the keys are loaded
+/// - `Err(String)` describing the invalid configuration
+pub fn init_keyring() -> Result<(), String> {
+    keyring_or_load().map(|_| ())
+}
+
+/// Returns the keyring, loading it on first use.
+fn keyring_or_load() -> Result<&'static Keyring, String> {
+    if let Some(keyring) = KEYRING.get() {
+        return Ok(keyring);
     }
+    let keyring = Keyring::from_env()?;
+    Ok(KEYRING.get_or_init(|| keyring))
+}
+
+/// Returns the keyring.
+///
+/// # Returns
+///
+/// - `Ok(&Keyring)` containing the keys
+/// - `Err(jsonwebtoken::errors::Error)` if the keys are not configured correctly
+fn keyring() -> Result<&'static Keyring, jsonwebtoken::errors::Error> {
+    keyring_or_load().map_err(|e| {
+        tracing::error!(error = %e, "Invalid JWT key configuration");
+        jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat)
+    })
 }
 
-/// Creates a signed JWT token for a user.
+/// Returns the public keys verifying access tokens, for other services to
+/// verify them without a shared secret.
 ///
-/// The token is valid for 7 days and uses the HS256 algorithm.
+/// HMAC secrets are never published, and keys past their cutoff are left out.
+///
+/// # Returns
+///
+/// - `Ok(JwkSet)` containing the public keys, possibly none
+/// - `Err(jsonwebtoken::errors::Error)` if the keys are not configured correctly
+pub fn jwks() -> Result<JwkSet, jsonwebtoken::errors::Error> {
+    let now = OffsetDateTime::now_utc();
+    let keys = keyring()?
+        .keys
+        .iter()
+        .filter(|key| key.verifies(now))
+        .filter_map(|key| key.jwk.clone())
+        .collect();
+    Ok(JwkSet { keys })
+}
+
+/// Creates a signed JWT access token for a user's session.
+///
+/// The token is valid for [`ACCESS_TOKEN_DURATION`] and signed with the
+/// keyring's active key, whose ID is set as the `kid` header. Longer sessions
+/// are kept alive with refresh tokens.
 ///
 /// # Arguments
 ///
 /// * `user_id` - The user's unique identifier
+/// * `session_id` - The ID of the session, stored as the `jti` claim
 ///
 /// # Returns
 ///
 /// - `Ok(String)` containing the signed JWT token
-/// - `Err(jsonwebtoken::errors::Error)` if signing fails or the secret key is not set
+/// - `Err(jsonwebtoken::errors::Error)` if signing fails or the keys are not configured correctly
 ///
 /// # Example
 ///
 /// ```ignore
-/// let token = sign_jwt("12345")?;
+/// let token = sign_jwt("12345", session_id.to_string())?;
 /// ```
-pub fn sign_jwt<S: AsRef<str>>(user_id: S) -> Result<String, jsonwebtoken::errors::Error> {
+pub fn sign_jwt<S: AsRef<str>, J: AsRef<str>>(
+    user_id: S,
+    session_id: J,
+) -> Result<String, jsonwebtoken::errors::Error> {
     tracing::trace!("Signing JWT");
 
-    let secret = get_secret_key()?;
+    let keyring = keyring()?;
+    let key = &keyring.keys[keyring.active];
+    let Some(encoding) = &key.encoding else {
+        return Err(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat.into());
+    };
     let iat = get_current_timestamp() as usize;
-    let exp = iat + (7 * 24 * 60 * 60); // 1 week
+    let exp = iat + ACCESS_TOKEN_DURATION.whole_seconds() as usize;
 
     let claims = Claims {
         sub: user_id.as_ref().to_string(),
         iat,
         exp,
+        jti: session_id.as_ref().to_string(),
     };
 
-    let header = Header::new(Algorithm::HS256);
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
 
     tracing::trace!("Signing JWT");
-    encode(
-        &header,
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )
+    encode(&header, &claims, encoding)
 }
 
 /// Verifies and decodes a JWT token.
 ///
+/// The token is verified with the key named by its `kid` header, or with
+/// [`LEGACY_KEY_ID`] if it has none, as long as that key hasn't passed its cutoff.
+///
 /// # Arguments
 ///
 /// * `token` - The JWT token string to verify
@@ -90,7 +406,8 @@ pub fn sign_jwt<S: AsRef<str>>(user_id: S) -> Result<String, jsonwebtoken::error
 /// # Returns
 ///
 /// - `Ok(Claims)` containing the decoded claims if the token is valid
-/// - `Err(jsonwebtoken::errors::Error)` if verification fails, the token is expired, or the secret key is not set
+/// - `Err(jsonwebtoken::errors::Error)` if verification fails, the token is expired or its
+///   key is unknown or retired, or the keys are not configured correctly
 ///
 /// # Example
 ///
@@ -101,14 +418,16 @@ pub fn sign_jwt<S: AsRef<str>>(user_id: S) -> Result<String, jsonwebtoken::error
 pub fn verify_jwt<S: AsRef<str>>(token: S) -> Result<Claims, jsonwebtoken::errors::Error> {
     tracing::trace!("Verifying JWT");
 
-    let secret = get_secret_key()?;
-    let validation = Validation::new(Algorithm::HS256);
+    let header = decode_header(token.as_ref())?;
+    let key = keyring()?
+        .verifying_key(header.kid.as_deref())
+        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidSignature)?;
+
+    // Only the key's own algorithm is accepted, so an HS256 token can't be
+    // forged with a public key as the secret
+    let validation = Validation::new(key.algorithm);
 
-    let data = decode::<Claims>(
-        token.as_ref(),
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &validation,
-    )?;
+    let data = decode::<Claims>(token.as_ref(), &key.decoding, &validation)?;
 
     tracing::trace!("JWT verified");
     Ok(data.claims)
@@ -1,11 +1,16 @@
 //! # Utils
 //!
 //! This crate provides utility functions for the GDG realtime chat application.
-//! It includes password hashing and JWT token management.
+//! It includes password hashing and policy checks, opaque token generation,
+//! API key scopes, TOTP codes and the encryption of their secrets, invite
+//! codes, client IP addresses and JWT token management.
 
 /// Password hashing and verification utilities using Argon2.
 pub mod hashing;
 
+/// Password policy checks for new passwords.
+pub mod password_policy;
+
 /// JWT token generation, verification, and cookie building utilities.
 pub mod jwt;
 
@@ -14,3 +19,21 @@ pub mod errors;
 
 /// Cookie management utilities.
 pub mod cookies;
+
+/// Opaque token generation and hashing utilities.
+pub mod tokens;
+
+/// API key scopes.
+pub mod scopes;
+
+/// Time-based one-time passwords and recovery codes for two-factor authentication.
+pub mod totp;
+
+/// Encryption of TOTP secrets at rest.
+pub mod encryption;
+
+/// Invite codes and signed invite links.
+pub mod invites;
+
+/// Client IP addresses behind trusted reverse proxies.
+pub mod proxies;
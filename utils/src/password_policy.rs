@@ -0,0 +1,617 @@
+//! Password policy checks.
+//!
+//! New passwords are checked for their length, their estimated entropy, the
+//! user's own username or email address, and a corpus of breached passwords.
+//! Every failed check is reported as a [`PasswordViolation`] with a stable
+//! code, so that clients can explain the rules in the user's language.
+//!
+//! The breached password corpus is a local file of SHA-1 hashes, looked up by
+//! hash prefix like the k-anonymity range API of Have I Been Pwned: only the
+//! hashes sharing the first 5 hex digits of the password's hash are read.
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Default minimum number of characters.
+pub const DEFAULT_MIN_LENGTH: usize = 8;
+
+/// Default maximum number of characters, which bounds the cost of hashing.
+pub const DEFAULT_MAX_LENGTH: usize = 128;
+
+/// Default minimum estimated entropy, in bits.
+pub const DEFAULT_MIN_ENTROPY_BITS: u32 = 40;
+
+/// Number of hex digits of the hash prefix looked up in the breached corpus.
+const PREFIX_LENGTH: usize = 5;
+
+/// Parts of the username or email address shorter than this aren't looked
+/// for in passwords, as they would reject too many of them.
+const MIN_CONTEXT_LENGTH: usize = 3;
+
+/// A rule a password breaks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum PasswordViolation {
+    /// The password has fewer characters than allowed.
+    TooShort {
+        #[serde(rename = "minLength")]
+        min_length: usize,
+    },
+    /// The password has more characters than allowed.
+    TooLong {
+        #[serde(rename = "maxLength")]
+        max_length: usize,
+    },
+    /// The password is too easy to guess, such as repeated characters or sequences.
+    TooWeak {
+        #[serde(rename = "minEntropyBits")]
+        min_entropy_bits: u32,
+        #[serde(rename = "entropyBits")]
+        entropy_bits: u32,
+    },
+    /// The password contains the user's username.
+    ContainsUsername,
+    /// The password contains the user's email address.
+    ContainsEmail,
+    /// The password appeared in a data breach.
+    Breached,
+}
+
+impl fmt::Display for PasswordViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordViolation::TooShort { min_length } => {
+                write!(f, "Password must be at least {min_length} characters")
+            }
+            PasswordViolation::TooLong { max_length } => {
+                write!(f, "Password must be at most {max_length} characters")
+            }
+            PasswordViolation::TooWeak { .. } => write!(
+                f,
+                "Password is too easy to guess, avoid repeated characters and sequences"
+            ),
+            PasswordViolation::ContainsUsername => {
+                write!(f, "Password must not contain your username")
+            }
+            PasswordViolation::ContainsEmail => {
+                write!(f, "Password must not contain your email address")
+            }
+            PasswordViolation::Breached => write!(
+                f,
+                "Password appeared in a data breach, please choose another one"
+            ),
+        }
+    }
+}
+
+/// The rules a password breaks, at least one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordViolations(pub Vec<PasswordViolation>);
+
+impl fmt::Display for PasswordViolations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, violation) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ". ")?;
+            }
+            write!(f, "{violation}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What is known about the user choosing the password.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PasswordContext<'a> {
+    /// The user's username.
+    pub username: Option<&'a str>,
+    /// The user's email address.
+    pub email: Option<&'a str>,
+}
+
+/// A local corpus of breached password hashes.
+///
+/// The file lists the uppercase hex SHA-1 hashes of breached passwords in
+/// ascending order, one per line, each optionally followed by `:<count>` as
+/// in the files of the Have I Been Pwned downloader.
+struct BreachedCorpus {
+    path: PathBuf,
+}
+
+impl BreachedCorpus {
+    /// Opens the corpus, checking that the file can be read.
+    fn open(path: PathBuf) -> Result<Self, String> {
+        File::open(&path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+        Ok(Self { path })
+    }
+
+    /// Returns whether the password's hash is in the corpus.
+    fn contains(&self, password: &str) -> io::Result<bool> {
+        let hash = Sha1::digest(password.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<String>();
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+        Ok(self.range(prefix)?.iter().any(|s| s == suffix))
+    }
+
+    /// Reads the suffixes of the hashes starting with the prefix.
+    fn range(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        let len = reader.get_ref().metadata()?.len();
+
+        // Find the first line whose hash isn't below the prefix
+        let (mut low, mut high) = (0, len);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match line_at(&mut reader, mid)? {
+                Some(line) if line_prefix(&line).as_str() < prefix => low = mid + 1,
+                _ => high = mid,
+            }
+        }
+
+        let mut suffixes = Vec::new();
+        let mut line = line_at(&mut reader, low)?;
+        while let Some(current) = line {
+            if line_prefix(&current) != prefix {
+                break;
+            }
+            // Lines that aren't hex hashes, such as a stray non-ASCII one, are skipped
+            let hash = current.split(':').next().unwrap_or_default();
+            if let Some(suffix) = hash.get(PREFIX_LENGTH..) {
+                suffixes.push(suffix.to_ascii_uppercase());
+            }
+            line = next_line(&mut reader)?;
+        }
+        Ok(suffixes)
+    }
+}
+
+/// Reads the first line starting at or after the offset.
+fn line_at(reader: &mut BufReader<File>, offset: u64) -> io::Result<Option<String>> {
+    if offset == 0 {
+        reader.seek(SeekFrom::Start(0))?;
+    } else {
+        // Skip the rest of the line the offset falls in, unless it starts there
+        reader.seek(SeekFrom::Start(offset - 1))?;
+        reader.read_until(b'\n', &mut Vec::new())?;
+    }
+    next_line(reader)
+}
+
+/// Reads the next line, without its line ending.
+///
+/// Invalid UTF-8 is replaced rather than failing the lookup, the line then
+/// just doesn't match any hash.
+fn next_line(reader: &mut BufReader<File>) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&line).trim_end().to_string()))
+}
+
+/// Returns the uppercase hash prefix of a corpus line.
+fn line_prefix(line: &str) -> String {
+    line.chars()
+        .take(PREFIX_LENGTH)
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+/// Password policy configuration.
+struct Policy {
+    min_length: usize,
+    max_length: usize,
+    min_entropy_bits: u32,
+    breached: Option<BreachedCorpus>,
+}
+
+impl Policy {
+    /// Reads the policy from the environment.
+    ///
+    /// - `PASSWORD_MIN_LENGTH` and `PASSWORD_MAX_LENGTH` bound the number of characters
+    /// - `PASSWORD_MIN_ENTROPY_BITS` sets the minimum estimated entropy
+    /// - `PASSWORD_BREACHED_CORPUS` is the path of the breached password corpus,
+    ///   which isn't checked without it
+    fn from_env() -> Result<Self, String> {
+        fn number<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+            match env::var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("{name} must be a positive integer, got {value:?}")),
+                Err(_) => Ok(default),
+            }
+        }
+        let min_length = number("PASSWORD_MIN_LENGTH", DEFAULT_MIN_LENGTH)?;
+        let max_length = number("PASSWORD_MAX_LENGTH", DEFAULT_MAX_LENGTH)?;
+        let min_entropy_bits = number("PASSWORD_MIN_ENTROPY_BITS", DEFAULT_MIN_ENTROPY_BITS)?;
+        if min_length == 0 || max_length < min_length {
+            return Err(format!(
+                "PASSWORD_MIN_LENGTH ({min_length}) must be positive and at most \
+                 PASSWORD_MAX_LENGTH ({max_length})"
+            ));
+        }
+
+        let breached = env::var("PASSWORD_BREACHED_CORPUS")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .map(|path| BreachedCorpus::open(PathBuf::from(path)))
+            .transpose()?;
+
+        Ok(Self {
+            min_length,
+            max_length,
+            min_entropy_bits,
+            breached,
+        })
+    }
+}
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Loads the password policy from the `PASSWORD_*` variables.
+///
+/// Called at startup so that a broken configuration is reported right away;
+/// otherwise the default policy is used if the configuration is invalid.
+///
+/// # Returns
+///
+/// - `Ok(())` once the policy is loaded
+/// - `Err(String)` describing the invalid configuration
+pub fn init_password_policy() -> Result<(), String> {
+    if POLICY.get().is_none() {
+        let policy = Policy::from_env()?;
+        let _ = POLICY.set(policy);
+    }
+    Ok(())
+}
+
+/// Returns the policy, loading it on first use.
+fn policy() -> &'static Policy {
+    POLICY.get_or_init(|| {
+        Policy::from_env().unwrap_or_else(|e| {
+            tracing::error!(error = %e, "Invalid password policy configuration, using the default");
+            Policy {
+                min_length: DEFAULT_MIN_LENGTH,
+                max_length: DEFAULT_MAX_LENGTH,
+                min_entropy_bits: DEFAULT_MIN_ENTROPY_BITS,
+                breached: None,
+            }
+        })
+    })
+}
+
+/// Estimates the entropy of a password, in bits.
+///
+/// Each character is worth the bits needed to pick it among the character
+/// classes the password uses (lowercase and uppercase letters, digits,
+/// symbols and other characters). Characters repeating or continuing a
+/// sequence from the previous one, as in `aaa` or `123`, are worth a single bit.
+pub fn estimate_entropy(password: &str) -> u32 {
+    let classes = [
+        (password.chars().any(|c| c.is_ascii_lowercase()), 26),
+        (password.chars().any(|c| c.is_ascii_uppercase()), 26),
+        (password.chars().any(|c| c.is_ascii_digit()), 10),
+        (
+            password
+                .chars()
+                .any(|c| c.is_ascii_punctuation() || c == ' '),
+            33,
+        ),
+        (!password.is_ascii(), 100),
+    ];
+    let pool: u32 = classes
+        .iter()
+        .filter(|(used, _)| *used)
+        .map(|(_, size)| size)
+        .sum();
+    let bits_per_char = f64::from(pool.max(1)).log2();
+
+    let mut previous: Option<char> = None;
+    let mut bits = 0.0;
+    for c in password.chars() {
+        let predictable = previous.is_some_and(|p| (c as i64 - p as i64).abs() <= 1);
+        bits += if predictable { 1.0 } else { bits_per_char };
+        previous = Some(c);
+    }
+    bits as u32
+}
+
+/// Returns whether the password contains a personal detail, ignoring case.
+fn contains_detail(password: &str, detail: &str) -> bool {
+    let detail = detail.trim().to_lowercase();
+    detail.chars().count() >= MIN_CONTEXT_LENGTH && password.contains(&detail)
+}
+
+/// Checks a new password against the password policy.
+///
+/// The breached password corpus is read on the blocking thread pool, so that
+/// the file reads don't stall the async runtime.
+///
+/// # Arguments
+///
+/// * `password` - The plaintext password to check
+/// * `context` - The user's username and email address, which the password must not contain
+///
+/// # Returns
+///
+/// - `Ok(())` if the password follows every rule
+/// - `Err(PasswordViolations)` listing every rule it breaks
+///
+/// # Example
+///
+/// ```ignore
+/// let context = PasswordContext { username: Some("alice"), email: None };
+/// check_password("correct horse battery staple", context).await?;
+/// // Err: contains_username
+/// check_password("alice-in-wonderland", context).await?;
+/// ```
+pub async fn check_password(
+    password: &str,
+    context: PasswordContext<'_>,
+) -> Result<(), PasswordViolations> {
+    let policy = policy();
+    let mut violations = check_rules(policy, password, context);
+
+    // Hashing overly long passwords is wasted work, they are rejected anyway
+    if let Some(corpus) = &policy.breached
+        && password.chars().count() <= policy.max_length
+    {
+        let owned = password.to_string();
+        match tokio::task::spawn_blocking(move || corpus.contains(&owned)).await {
+            Ok(Ok(true)) => violations.push(PasswordViolation::Breached),
+            Ok(Ok(false)) => {}
+            Ok(Err(e)) => {
+                tracing::error!(error = %e, "Failed to read the breached password corpus")
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Breached password corpus lookup panicked")
+            }
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        tracing::debug!(?violations, "Password breaks the password policy");
+        Err(PasswordViolations(violations))
+    }
+}
+
+/// Checks the password against every rule but the breached corpus.
+fn check_rules(
+    policy: &Policy,
+    password: &str,
+    context: PasswordContext,
+) -> Vec<PasswordViolation> {
+    let mut violations = Vec::new();
+
+    let length = password.chars().count();
+    if length < policy.min_length {
+        violations.push(PasswordViolation::TooShort {
+            min_length: policy.min_length,
+        });
+    }
+    if length > policy.max_length {
+        violations.push(PasswordViolation::TooLong {
+            max_length: policy.max_length,
+        });
+    }
+
+    let entropy_bits = estimate_entropy(password);
+    if entropy_bits < policy.min_entropy_bits {
+        violations.push(PasswordViolation::TooWeak {
+            min_entropy_bits: policy.min_entropy_bits,
+            entropy_bits,
+        });
+    }
+
+    let lowercase = password.to_lowercase();
+    if context
+        .username
+        .is_some_and(|username| contains_detail(&lowercase, username))
+    {
+        violations.push(PasswordViolation::ContainsUsername);
+    }
+    if let Some(email) = context.email {
+        let local_part = email.split('@').next().unwrap_or_default();
+        if contains_detail(&lowercase, email) || contains_detail(&lowercase, local_part) {
+            violations.push(PasswordViolation::ContainsEmail);
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    /// The default policy, without a breached corpus.
+    fn default_policy() -> Policy {
+        Policy {
+            min_length: DEFAULT_MIN_LENGTH,
+            max_length: DEFAULT_MAX_LENGTH,
+            min_entropy_bits: DEFAULT_MIN_ENTROPY_BITS,
+            breached: None,
+        }
+    }
+
+    /// Returns the uppercase hex SHA-1 hash of the password.
+    fn sha1_hex(password: &str) -> String {
+        Sha1::digest(password.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect()
+    }
+
+    /// Writes a corpus file with the given lines, sorted, and opens it.
+    fn corpus(name: &str, mut lines: Vec<Vec<u8>>) -> BreachedCorpus {
+        lines.sort();
+        let path = env::temp_dir().join(format!("breached-{name}-{}.txt", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        for line in lines {
+            file.write_all(&line).unwrap();
+            file.write_all(b"\r\n").unwrap();
+        }
+        BreachedCorpus::open(path).unwrap()
+    }
+
+    #[test]
+    fn entropy_of_empty_password_is_zero() {
+        assert_eq!(estimate_entropy(""), 0);
+    }
+
+    #[test]
+    fn entropy_counts_character_classes() {
+        // 8 lowercase letters, log2(26) bits each
+        assert_eq!(estimate_entropy("qzmxtrbf"), 37);
+        // Adding uppercase letters, digits and symbols widens the pool
+        assert!(estimate_entropy("qZmX8r!f") > estimate_entropy("qzmxtrbf"));
+        assert!(estimate_entropy("qzmxtrbfé") > estimate_entropy("qzmxtrbfq"));
+    }
+
+    #[test]
+    fn entropy_discounts_repeats_and_sequences() {
+        // The first character, then one bit for each predictable one
+        assert_eq!(estimate_entropy("aaaaaaaaaaaa"), 15);
+        assert_eq!(estimate_entropy("abcdefghijkl"), 15);
+        assert_eq!(estimate_entropy("987654321098"), 16);
+        assert!(estimate_entropy("aaaaaaaaaaaa") < DEFAULT_MIN_ENTROPY_BITS);
+    }
+
+    #[test]
+    fn rejects_guessable_passwords() {
+        let violations = check_rules(
+            &default_policy(),
+            "aaaaaaaaaaaa",
+            PasswordContext::default(),
+        );
+        assert_eq!(
+            violations,
+            vec![PasswordViolation::TooWeak {
+                min_entropy_bits: DEFAULT_MIN_ENTROPY_BITS,
+                entropy_bits: 15,
+            }]
+        );
+        assert!(
+            check_rules(
+                &default_policy(),
+                "Glacier-Mosaic-47",
+                PasswordContext::default()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn rejects_passwords_containing_username() {
+        let context = PasswordContext {
+            username: Some("Alice"),
+            email: None,
+        };
+        assert_eq!(
+            check_rules(&default_policy(), "xALICEx-Wonder-42", context),
+            vec![PasswordViolation::ContainsUsername]
+        );
+        assert!(check_rules(&default_policy(), "Glacier-Mosaic-47", context).is_empty());
+    }
+
+    #[test]
+    fn rejects_passwords_containing_email() {
+        let context = PasswordContext {
+            username: None,
+            email: Some("bob.smith@example.com"),
+        };
+        // The whole address, or its local part
+        assert_eq!(
+            check_rules(&default_policy(), "Bob.Smith@Example.com!", context),
+            vec![PasswordViolation::ContainsEmail]
+        );
+        assert_eq!(
+            check_rules(&default_policy(), "Zx9-bob.smith-Qw", context),
+            vec![PasswordViolation::ContainsEmail]
+        );
+        // Not just the domain
+        assert!(check_rules(&default_policy(), "Zx9-example.com-Qw", context).is_empty());
+    }
+
+    #[test]
+    fn ignores_short_personal_details() {
+        let context = PasswordContext {
+            username: Some("al"),
+            email: Some("al@x.io"),
+        };
+        assert!(check_rules(&default_policy(), "Glacier-al-Mosaic-47", context).is_empty());
+    }
+
+    #[test]
+    fn finds_breached_passwords_in_corpus() {
+        let breached = ["password", "123456", "Tr0ub4dor&3", "letmein"];
+        let mut lines: Vec<Vec<u8>> = breached
+            .iter()
+            .map(|password| format!("{}:42", sha1_hex(password)).into_bytes())
+            .collect();
+        // Filler spread over the prefixes, so the search has to narrow down
+        for i in 0..2000_u32 {
+            lines.push(format!("{:05X}{i:035X}:1", i * 524).into_bytes());
+        }
+        // Hashes without counts are accepted too
+        lines.push(sha1_hex("dragon").into_bytes());
+        let corpus = corpus("found", lines);
+
+        for password in breached.iter().chain(&["dragon"]) {
+            assert!(corpus.contains(password).unwrap(), "{password} not found");
+        }
+        for password in ["Glacier-Mosaic-47", "passw0rd", ""] {
+            assert!(!corpus.contains(password).unwrap(), "{password} found");
+        }
+        fs::remove_file(corpus.path).unwrap();
+    }
+
+    #[test]
+    fn finds_breached_passwords_in_lowercase_corpus() {
+        let lines = ["password", "123456", "letmein"]
+            .iter()
+            .map(|password| sha1_hex(password).to_lowercase().into_bytes())
+            .collect();
+        let corpus = corpus("lowercase", lines);
+
+        assert!(corpus.contains("letmein").unwrap());
+        assert!(!corpus.contains("Glacier-Mosaic-47").unwrap());
+        fs::remove_file(corpus.path).unwrap();
+    }
+
+    #[test]
+    fn skips_malformed_corpus_lines() {
+        let hash = sha1_hex("password");
+        let prefix = &hash[..PREFIX_LENGTH];
+        let lines = vec![
+            hash.clone().into_bytes(),
+            // Sharing the prefix, but with multibyte characters right after it
+            format!("{prefix}ÄÖÜ").into_bytes(),
+            format!("{prefix}é").into_bytes(),
+            // Invalid UTF-8
+            [prefix.as_bytes(), &[0xFF, 0xFE]].concat(),
+            // Shorter than a prefix
+            "ÄÖ".as_bytes().to_vec(),
+            b"00".to_vec(),
+        ];
+        let corpus = corpus("malformed", lines);
+
+        assert!(corpus.contains("password").unwrap());
+        assert!(!corpus.contains("Glacier-Mosaic-47").unwrap());
+        assert_eq!(corpus.range(prefix).unwrap().len(), 4);
+        fs::remove_file(corpus.path).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+//! Client IP addresses behind reverse proxies.
+//!
+//! The `X-Forwarded-For` header is set by the client like any other header,
+//! so it only says where a request came from when a proxy we trust wrote it.
+//! Trusted proxies are listed in `TRUSTED_PROXIES`, as comma-separated IP
+//! addresses or CIDR ranges. Without it, the header is ignored and the
+//! client's address is the one of the socket.
+//!
+//! With trusted proxies, the header is read from the right: every proxy
+//! appends the address it received the request from, so the rightmost entry
+//! not belonging to a trusted proxy is the client's. Entries further left
+//! were sent by the client and may be forged.
+
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use std::env;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+static TRUSTED_PROXIES: OnceLock<Vec<IpNet>> = OnceLock::new();
+
+/// Parses a comma-separated list of IP addresses and CIDR ranges.
+fn parse_trusted_proxies(value: &str) -> Result<Vec<IpNet>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("TRUSTED_PROXIES has an invalid address: {entry}"))
+        })
+        .collect()
+}
+
+/// Loads the trusted proxies from `TRUSTED_PROXIES`.
+///
+/// No proxy is trusted without it. Called at startup so that a broken
+/// configuration is reported right away.
+///
+/// # Returns
+///
+/// - `Ok(())` once the proxies are loaded, or if it isn't set
+/// - `Err(String)` if an entry isn't an IP address or CIDR range
+pub fn init_trusted_proxies() -> Result<(), String> {
+    let proxies = match env::var("TRUSTED_PROXIES") {
+        Ok(value) => parse_trusted_proxies(&value)?,
+        Err(_) => Vec::new(),
+    };
+    let _ = TRUSTED_PROXIES.set(proxies);
+    Ok(())
+}
+
+/// Returns the trusted proxies.
+fn trusted_proxies() -> &'static [IpNet] {
+    TRUSTED_PROXIES.get_or_init(|| {
+        env::var("TRUSTED_PROXIES")
+            .ok()
+            .and_then(|value| parse_trusted_proxies(&value).ok())
+            .unwrap_or_default()
+    })
+}
+
+/// Returns whether the address belongs to a trusted proxy.
+fn is_trusted(ip: IpAddr) -> bool {
+    trusted_proxies().iter().any(|net| net.contains(&ip))
+}
+
+/// Returns the address of the client a request came from.
+///
+/// # Arguments
+///
+/// * `headers` - The request headers, read for `X-Forwarded-For`
+/// * `peer` - The address of the socket the request was received on
+pub fn client_ip(headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    let mut client = peer;
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for entry in forwarded.into_iter().rev() {
+        let Ok(ip) = entry.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = ip;
+        if !is_trusted(ip) {
+            break;
+        }
+    }
+    client
+}
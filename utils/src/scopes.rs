@@ -0,0 +1,68 @@
+//! API key scopes.
+//!
+//! Requests authenticated with a session may do anything the user can do.
+//! Requests authenticated with an API key are limited to the scopes granted to
+//! the key when it was created.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A permission that can be granted to an API key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    /// Read the user's profile.
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    /// List the user's conversations.
+    #[serde(rename = "chats:read")]
+    ChatsRead,
+    /// Create chat codes and join conversations.
+    #[serde(rename = "chats:write")]
+    ChatsWrite,
+    /// Read messages, including over the WebSocket.
+    #[serde(rename = "messages:read")]
+    MessagesRead,
+    /// Send, edit and delete messages, and mark them as read.
+    #[serde(rename = "messages:write")]
+    MessagesWrite,
+}
+
+impl Scope {
+    /// Every scope, in the order they are documented.
+    pub const ALL: [Scope; 5] = [
+        Scope::ProfileRead,
+        Scope::ChatsRead,
+        Scope::ChatsWrite,
+        Scope::MessagesRead,
+        Scope::MessagesWrite,
+    ];
+
+    /// Returns the name of the scope, as stored and exchanged with clients.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::ProfileRead => "profile:read",
+            Scope::ChatsRead => "chats:read",
+            Scope::ChatsWrite => "chats:write",
+            Scope::MessagesRead => "messages:read",
+            Scope::MessagesWrite => "messages:write",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("Unknown scope: {s}"))
+    }
+}
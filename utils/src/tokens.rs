@@ -0,0 +1,48 @@
+//! Opaque token generation and hashing.
+//!
+//! Opaque tokens are random strings handed to clients, such as refresh
+//! tokens. Only their SHA-256 digest is stored, so that leaking the database
+//! does not leak usable tokens. Unlike passwords, tokens carry full entropy,
+//! so a fast hash is sufficient.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Number of random bytes in a generated token.
+pub const TOKEN_BYTES: usize = 32;
+
+/// Prefix of API keys, telling them apart from JWT access tokens.
+pub const API_KEY_PREFIX: &str = "ck_";
+
+/// Generates a random opaque token.
+///
+/// # Returns
+///
+/// A URL-safe base64 string (without padding) encoding [`TOKEN_BYTES`] random bytes.
+///
+/// # Example
+///
+/// ```ignore
+/// let token = generate_token();
+/// let stored = hash_token(&token);
+/// ```
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hashes an opaque token for storage and lookup.
+///
+/// # Arguments
+///
+/// * `token` - The token as handed to the client
+///
+/// # Returns
+///
+/// The lowercase hex-encoded SHA-256 digest of the token.
+pub fn hash_token<S: AsRef<str>>(token: S) -> String {
+    format!("{:x}", Sha256::digest(token.as_ref().as_bytes()))
+}
@@ -0,0 +1,156 @@
+//! Time-based one-time passwords (RFC 6238) and recovery codes.
+//!
+//! Codes are 6 digits derived with HMAC-SHA1 from a shared secret and the
+//! current 30 second time step, which is what authenticator apps expect by
+//! default. Verification accepts the previous and next step as well, to
+//! tolerate clock drift between the server and the user's device.
+
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use password_hash::rand_core::{OsRng, RngCore};
+use sha1::Sha1;
+
+/// Number of random bytes in a TOTP secret (160 bits, as RFC 4226 recommends).
+pub const SECRET_BYTES: usize = 20;
+
+/// Length of a time step in seconds.
+pub const STEP_SECONDS: u64 = 30;
+
+/// Number of digits in a code.
+pub const DIGITS: u32 = 6;
+
+/// Number of steps before and after the current one that are still accepted.
+const SKEW_STEPS: u64 = 1;
+
+/// Number of recovery codes generated at enrollment.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Characters recovery codes are made of, without look-alikes such as `0`/`o` or `1`/`l`.
+const RECOVERY_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Number of characters in each half of a recovery code.
+const RECOVERY_HALF_LENGTH: usize = 5;
+
+/// Base32 encoding used for secrets, as expected by authenticator apps.
+const SECRET_ALPHABET: Alphabet = Alphabet::Rfc4648 { padding: false };
+
+/// Generates a random TOTP secret.
+///
+/// # Returns
+///
+/// The secret as an unpadded base32 string, ready to be shown to the user.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    base32::encode(SECRET_ALPHABET, &bytes)
+}
+
+/// Computes the code for a secret at the given time step.
+fn code_at(secret: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation, RFC 4226 section 5.3
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Verifies a code against a secret.
+///
+/// # Arguments
+///
+/// * `secret` - The base32 encoded secret
+/// * `code` - The code entered by the user; whitespace is ignored
+/// * `unix_time` - The current Unix timestamp
+///
+/// # Returns
+///
+/// - `Some(step)` with the time step the code belongs to if it is valid, so
+///   that callers can refuse to accept the same step twice
+/// - `None` if the code is malformed or invalid, or the secret cannot be decoded
+pub fn verify(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let secret = base32::decode(SECRET_ALPHABET, secret)?;
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+
+    let current = unix_time / STEP_SECONDS;
+    (current.saturating_sub(SKEW_STEPS)..=current + SKEW_STEPS)
+        .find(|&step| code_at(&secret, step) == code)
+}
+
+/// Builds the `otpauth://` URI that authenticator apps import, usually from a QR code.
+///
+/// # Arguments
+///
+/// * `issuer` - The name of the service, shown in the authenticator app
+/// * `account` - The user's account name, e.g. their username
+/// * `secret` - The base32 encoded secret
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+/// Percent-encodes everything but unreserved characters (RFC 3986).
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Generates a set of single-use recovery codes.
+///
+/// # Returns
+///
+/// [`RECOVERY_CODE_COUNT`] codes formatted like `abcde-fghjk`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; RECOVERY_HALF_LENGTH * 2];
+            OsRng.fill_bytes(&mut bytes);
+            let chars: Vec<char> = bytes
+                .iter()
+                .map(|b| RECOVERY_ALPHABET[*b as usize % RECOVERY_ALPHABET.len()] as char)
+                .collect();
+            let (first, second) = chars.split_at(RECOVERY_HALF_LENGTH);
+            format!(
+                "{}-{}",
+                first.iter().collect::<String>(),
+                second.iter().collect::<String>()
+            )
+        })
+        .collect()
+}
+
+/// Normalizes a recovery code entered by the user before hashing it.
+///
+/// Case, dashes and whitespace are ignored, so `ABCDE FGHJK` matches `abcde-fghjk`.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}